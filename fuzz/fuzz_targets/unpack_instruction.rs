@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use solana_escrow::instruction::EscrowInstruction;
+
+// any input either fails to unpack or unpacks to an instruction that packs back to itself;
+// a panic or an out-of-bounds slice anywhere in between is a crash
+fuzz_target!(|data: &[u8]| {
+    if let Ok(instruction) = EscrowInstruction::unpack(data) {
+        assert_eq!(EscrowInstruction::unpack(&instruction.pack()), Ok(instruction));
+    }
+});
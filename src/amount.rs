@@ -0,0 +1,79 @@
+use solana_program::pubkey::Pubkey;
+
+use crate::error::EscrowError;
+
+/// Whether `mint` is wrapped SOL, so callers special-case it in one place
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id()
+}
+
+/// Parses a decimal UI amount such as `"1.25"` into base units of a mint with
+/// `decimals` decimals, e.g. `1_250_000_000` for 9 decimals. Amounts with more
+/// fractional digits than the mint supports are rejected rather than rounded.
+pub fn parse_ui_amount(s: &str, decimals: u8) -> Result<u64, EscrowError> {
+    let (whole, fraction) = match s.split_once('.') {
+        Some((_, "")) => return Err(EscrowError::InvalidUiAmount),
+        Some(parts) => parts,
+        None => (s, ""),
+    };
+    let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return Err(EscrowError::InvalidUiAmount);
+    }
+    let padding = (decimals as usize)
+        .checked_sub(fraction.len())
+        .ok_or(EscrowError::InvalidUiAmount)?;
+
+    let base_units = format!("{}{}{}", whole, fraction, "0".repeat(padding));
+    // only digits are left, so a failed parse can only be an overflow
+    base_units.parse().map_err(|_| EscrowError::AmountOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_only_the_native_mint() {
+        assert!(is_native_mint(&spl_token::native_mint::id()));
+        assert!(!is_native_mint(&Pubkey::new_unique()));
+        assert!(!is_native_mint(&spl_token::id()));
+    }
+
+    #[test]
+    fn parses_valid_amounts() {
+        assert_eq!(parse_ui_amount("1.25", 9), Ok(1_250_000_000));
+        assert_eq!(parse_ui_amount("1", 6), Ok(1_000_000));
+        assert_eq!(parse_ui_amount("0.000001", 6), Ok(1));
+        assert_eq!(parse_ui_amount("42", 0), Ok(42));
+        assert_eq!(parse_ui_amount("18446744073709551615", 0), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn rejects_too_precise_or_malformed_amounts() {
+        assert_eq!(
+            parse_ui_amount("0.0000001", 6),
+            Err(EscrowError::InvalidUiAmount)
+        );
+        assert_eq!(parse_ui_amount("1.5", 0), Err(EscrowError::InvalidUiAmount));
+        for malformed in &["", ".", "1.", ".5", "-1", "1.2.3", "1,5", " 1"] {
+            assert_eq!(
+                parse_ui_amount(malformed, 6),
+                Err(EscrowError::InvalidUiAmount)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_amounts() {
+        assert_eq!(
+            parse_ui_amount("18446744073709551616", 0),
+            Err(EscrowError::AmountOverflow)
+        );
+        assert_eq!(
+            parse_ui_amount("18446744073710", 6),
+            Err(EscrowError::AmountOverflow)
+        );
+        assert_eq!(parse_ui_amount("1", 20), Err(EscrowError::AmountOverflow));
+    }
+}
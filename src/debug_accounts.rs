@@ -0,0 +1,119 @@
+//! Optional role markers a client can append to instruction data so the program says
+//! exactly which account slot is wrong, instead of failing somewhere further in.
+//!
+//! Marked data is the normal payload, one role byte per account slot to check, the
+//! number of role bytes, then `TRAILER_MAGIC`. Unmarked data passes through untouched.
+
+use solana_program::{
+    account_info::AccountInfo, msg, program_error::ProgramError, program_pack::Pack,
+    pubkey::Pubkey, sysvar,
+};
+use spl_token::state::Account as TokenAccount;
+
+use crate::{error::EscrowError, state::Escrow, token};
+
+pub const TRAILER_MAGIC: [u8; 4] = *b"ROLE";
+
+/// What a client expects to find in an account slot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccountRole {
+    Any,
+    Signer,
+    TokenAccount,
+    EscrowState,
+    TokenProgram,
+    RentSysvar,
+    ClockSysvar,
+}
+
+impl AccountRole {
+    fn from_u8(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => AccountRole::Any,
+            1 => AccountRole::Signer,
+            2 => AccountRole::TokenAccount,
+            3 => AccountRole::EscrowState,
+            4 => AccountRole::TokenProgram,
+            5 => AccountRole::RentSysvar,
+            6 => AccountRole::ClockSysvar,
+            _ => return None,
+        })
+    }
+
+    fn matches(&self, info: &AccountInfo, program_id: &Pubkey) -> bool {
+        match self {
+            AccountRole::Any => true,
+            AccountRole::Signer => info.is_signer,
+            AccountRole::TokenAccount => {
+                // a Token-2022 account with extensions runs past the spl-token length
+                token::is_token_program(info.owner) && info.data_len() >= TokenAccount::LEN
+            }
+            AccountRole::EscrowState => info.owner == program_id && info.data_len() == Escrow::LEN,
+            AccountRole::TokenProgram => token::is_token_program(info.key),
+            AccountRole::RentSysvar => *info.key == sysvar::rent::id(),
+            AccountRole::ClockSysvar => *info.key == sysvar::clock::id(),
+        }
+    }
+}
+
+/// Appends role markers for `roles` to an instruction payload
+pub fn append_roles(data: &mut Vec<u8>, roles: &[AccountRole]) {
+    data.extend(roles.iter().map(|role| *role as u8));
+    data.push(roles.len() as u8);
+    data.extend_from_slice(&TRAILER_MAGIC);
+}
+
+/// Splits role markers off the end of `data`, if it carries any
+pub fn split_roles(data: &[u8]) -> Result<(&[u8], Option<Vec<AccountRole>>), ProgramError> {
+    let body = match data.strip_suffix(&TRAILER_MAGIC[..]) {
+        Some(body) => body,
+        None => return Ok((data, None)),
+    };
+    let (count, body) = body
+        .split_last()
+        .ok_or(EscrowError::InvalidInstruction)?;
+    let payload_len = body
+        .len()
+        .checked_sub(*count as usize)
+        .ok_or(EscrowError::InvalidInstruction)?;
+    let (payload, role_bytes) = body.split_at(payload_len);
+    let roles = role_bytes
+        .iter()
+        .map(|byte| AccountRole::from_u8(*byte))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(EscrowError::InvalidInstruction)?;
+    Ok((payload, Some(roles)))
+}
+
+/// The first slot whose account doesn't play its marked role, or that is marked but missing
+pub fn first_mismatch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    roles: &[AccountRole],
+) -> Option<usize> {
+    roles
+        .iter()
+        .enumerate()
+        .find(|(slot, role)| {
+            !accounts
+                .get(*slot)
+                .is_some_and(|info| role.matches(info, program_id))
+        })
+        .map(|(slot, _)| slot)
+}
+
+/// Checks marked data against `accounts` and returns the payload without the markers
+pub fn check<'d>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &'d [u8],
+) -> Result<&'d [u8], ProgramError> {
+    let (payload, roles) = split_roles(data)?;
+    if let Some(roles) = roles {
+        if let Some(slot) = first_mismatch(program_id, accounts, &roles) {
+            msg!("Account {} is not a {:?}", slot, roles[slot]);
+            return Err(EscrowError::AccountRoleMismatch.into());
+        }
+    }
+    Ok(payload)
+}
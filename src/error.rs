@@ -1,11 +1,17 @@
+use num_traits::FromPrimitive;
+use std::convert::TryFrom;
 use thiserror::Error;
 
-use solana_program::program_error::ProgramError;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
 
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
 pub enum EscrowError {
     /// Invalid instruction
-    #[error("Invalid Instruction")]
+    #[error("Invalid instruction")]
     InvalidInstruction,
     /// Not Rent Exempt
     #[error("Not Rent Exempt")]
@@ -16,6 +22,250 @@ pub enum EscrowError {
     /// Amount Overflow
     #[error("Amount Overflow")]
     AmountOverflow,
+    /// Authority Transfer Failed
+    #[error("Authority Transfer Failed")]
+    AuthorityTransferFailed,
+    /// Duplicate Account
+    #[error("Duplicate Account")]
+    DuplicateAccount,
+    /// Rent Unavailable
+    #[error("Rent Unavailable")]
+    RentUnavailable,
+    /// Insufficient Deposit
+    #[error("Insufficient Deposit")]
+    InsufficientDeposit,
+    /// Wrong Account Count
+    #[error("Wrong Account Count")]
+    WrongAccountCount,
+    /// Unexpected Account
+    #[error("Unexpected Account")]
+    UnexpectedAccount,
+    /// Invalid UI Amount
+    #[error("Invalid UI Amount")]
+    InvalidUiAmount,
+    /// Account Mismatch
+    #[error("Account Mismatch")]
+    AccountMismatch,
+    /// Source Total Mismatch
+    #[error("Source Total Mismatch")]
+    SourceTotalMismatch,
+    /// Insufficient Taker Funds
+    #[error("Insufficient Taker Funds")]
+    InsufficientTakerFunds,
+    /// Not Arbiter
+    #[error("Not Arbiter")]
+    NotArbiter,
+    /// Vault Amount Changed
+    #[error("Vault Amount Changed")]
+    VaultAmountChanged,
+    /// Instruction Too Large
+    #[error("Instruction Too Large")]
+    InstructionTooLarge,
+    /// Invalid Price
+    #[error("Invalid Price")]
+    InvalidPrice,
+    /// Account Frozen
+    #[error("Account Frozen")]
+    AccountFrozen,
+    /// Invalid Side
+    #[error("Invalid Side")]
+    InvalidSide,
+    /// Account Role Mismatch
+    #[error("Account Role Mismatch")]
+    AccountRoleMismatch,
+    /// Escrow Expired
+    #[error("Escrow Expired")]
+    EscrowExpired,
+    /// Mint Mismatch
+    #[error("Mint Mismatch")]
+    MintMismatch,
+    /// Invalid Fill Amount
+    #[error("Invalid Fill Amount")]
+    InvalidFillAmount,
+    /// Invalid Fee Basis Points
+    #[error("Invalid Fee Basis Points")]
+    InvalidFeeBps,
+    /// Unsupported Version
+    #[error("Unsupported Version")]
+    UnsupportedVersion,
+    /// Not Initializer
+    #[error("Not Initializer")]
+    NotInitializer,
+    /// Invalid Token Program
+    #[error("Invalid Token Program")]
+    InvalidTokenProgram,
+    /// Invalid PDA
+    #[error("Invalid PDA")]
+    InvalidPda,
+    /// Escrow Not Initialized
+    #[error("Escrow Not Initialized")]
+    EscrowNotInitialized,
+    /// Invalid Amount
+    #[error("Invalid Amount")]
+    InvalidAmount,
+    /// Invalid Account Owner
+    #[error("Invalid Account Owner")]
+    InvalidAccountOwner,
+    /// Unauthorized Taker
+    #[error("Unauthorized Taker")]
+    UnauthorizedTaker,
+    /// Escrow Not Expired
+    #[error("Escrow Not Expired")]
+    EscrowNotExpired,
+    /// Program Paused
+    #[error("Program Paused")]
+    ProgramPaused,
+    /// Not Admin
+    #[error("Not Admin")]
+    NotAdmin,
+    /// Account Not Writable
+    #[error("Account Not Writable")]
+    AccountNotWritable,
+    /// Invalid State Transition
+    #[error("Invalid State Transition")]
+    InvalidStateTransition,
+    /// Too Many Accounts
+    #[error("Too Many Accounts")]
+    TooManyAccounts,
+    /// Escrow Not Started
+    #[error("Escrow Not Started")]
+    EscrowNotStarted,
+    /// Auction Price Moved
+    #[error("Auction Price Moved")]
+    AuctionPriceMoved,
+    /// Invalid Account Discriminator
+    #[error("Invalid Account Discriminator")]
+    InvalidAccountDiscriminator,
+    /// Nothing To Refund
+    #[error("Nothing To Refund")]
+    NothingToRefund,
+    /// Fill Too Small
+    #[error("Fill Too Small")]
+    FillTooSmall,
+    /// Still Paused
+    #[error("Still Paused")]
+    StillPaused,
+    /// Unsupported Token Extension
+    #[error("Unsupported Token Extension")]
+    UnsupportedTokenExtension,
+    /// Expiry Too Far
+    #[error("Expiry Too Far")]
+    ExpiryTooFar,
+}
+
+impl EscrowError {
+    /// Every variant, in code order. New variants must be appended here too.
+    pub const ALL: [EscrowError; 47] = [
+        EscrowError::InvalidInstruction,
+        EscrowError::NotRentExempt,
+        EscrowError::ExpectedAmountMismatch,
+        EscrowError::AmountOverflow,
+        EscrowError::AuthorityTransferFailed,
+        EscrowError::DuplicateAccount,
+        EscrowError::RentUnavailable,
+        EscrowError::InsufficientDeposit,
+        EscrowError::WrongAccountCount,
+        EscrowError::UnexpectedAccount,
+        EscrowError::InvalidUiAmount,
+        EscrowError::AccountMismatch,
+        EscrowError::SourceTotalMismatch,
+        EscrowError::InsufficientTakerFunds,
+        EscrowError::NotArbiter,
+        EscrowError::VaultAmountChanged,
+        EscrowError::InstructionTooLarge,
+        EscrowError::InvalidPrice,
+        EscrowError::AccountFrozen,
+        EscrowError::InvalidSide,
+        EscrowError::AccountRoleMismatch,
+        EscrowError::EscrowExpired,
+        EscrowError::MintMismatch,
+        EscrowError::InvalidFillAmount,
+        EscrowError::InvalidFeeBps,
+        EscrowError::UnsupportedVersion,
+        EscrowError::NotInitializer,
+        EscrowError::InvalidTokenProgram,
+        EscrowError::InvalidPda,
+        EscrowError::EscrowNotInitialized,
+        EscrowError::InvalidAmount,
+        EscrowError::InvalidAccountOwner,
+        EscrowError::UnauthorizedTaker,
+        EscrowError::EscrowNotExpired,
+        EscrowError::ProgramPaused,
+        EscrowError::NotAdmin,
+        EscrowError::AccountNotWritable,
+        EscrowError::InvalidStateTransition,
+        EscrowError::TooManyAccounts,
+        EscrowError::EscrowNotStarted,
+        EscrowError::AuctionPriceMoved,
+        EscrowError::InvalidAccountDiscriminator,
+        EscrowError::NothingToRefund,
+        EscrowError::FillTooSmall,
+        EscrowError::StillPaused,
+        EscrowError::UnsupportedTokenExtension,
+        EscrowError::ExpiryTooFar,
+    ];
+
+    /// The variant name, as clients should display it
+    pub fn name(&self) -> &'static str {
+        match self {
+            EscrowError::InvalidInstruction => "InvalidInstruction",
+            EscrowError::NotRentExempt => "NotRentExempt",
+            EscrowError::ExpectedAmountMismatch => "ExpectedAmountMismatch",
+            EscrowError::AmountOverflow => "AmountOverflow",
+            EscrowError::AuthorityTransferFailed => "AuthorityTransferFailed",
+            EscrowError::DuplicateAccount => "DuplicateAccount",
+            EscrowError::RentUnavailable => "RentUnavailable",
+            EscrowError::InsufficientDeposit => "InsufficientDeposit",
+            EscrowError::WrongAccountCount => "WrongAccountCount",
+            EscrowError::UnexpectedAccount => "UnexpectedAccount",
+            EscrowError::InvalidUiAmount => "InvalidUiAmount",
+            EscrowError::AccountMismatch => "AccountMismatch",
+            EscrowError::SourceTotalMismatch => "SourceTotalMismatch",
+            EscrowError::InsufficientTakerFunds => "InsufficientTakerFunds",
+            EscrowError::NotArbiter => "NotArbiter",
+            EscrowError::VaultAmountChanged => "VaultAmountChanged",
+            EscrowError::InstructionTooLarge => "InstructionTooLarge",
+            EscrowError::InvalidPrice => "InvalidPrice",
+            EscrowError::AccountFrozen => "AccountFrozen",
+            EscrowError::InvalidSide => "InvalidSide",
+            EscrowError::AccountRoleMismatch => "AccountRoleMismatch",
+            EscrowError::EscrowExpired => "EscrowExpired",
+            EscrowError::MintMismatch => "MintMismatch",
+            EscrowError::InvalidFillAmount => "InvalidFillAmount",
+            EscrowError::InvalidFeeBps => "InvalidFeeBps",
+            EscrowError::UnsupportedVersion => "UnsupportedVersion",
+            EscrowError::NotInitializer => "NotInitializer",
+            EscrowError::InvalidTokenProgram => "InvalidTokenProgram",
+            EscrowError::InvalidPda => "InvalidPda",
+            EscrowError::EscrowNotInitialized => "EscrowNotInitialized",
+            EscrowError::InvalidAmount => "InvalidAmount",
+            EscrowError::InvalidAccountOwner => "InvalidAccountOwner",
+            EscrowError::UnauthorizedTaker => "UnauthorizedTaker",
+            EscrowError::EscrowNotExpired => "EscrowNotExpired",
+            EscrowError::ProgramPaused => "ProgramPaused",
+            EscrowError::NotAdmin => "NotAdmin",
+            EscrowError::AccountNotWritable => "AccountNotWritable",
+            EscrowError::InvalidStateTransition => "InvalidStateTransition",
+            EscrowError::TooManyAccounts => "TooManyAccounts",
+            EscrowError::EscrowNotStarted => "EscrowNotStarted",
+            EscrowError::AuctionPriceMoved => "AuctionPriceMoved",
+            EscrowError::InvalidAccountDiscriminator => "InvalidAccountDiscriminator",
+            EscrowError::NothingToRefund => "NothingToRefund",
+            EscrowError::FillTooSmall => "FillTooSmall",
+            EscrowError::StillPaused => "StillPaused",
+            EscrowError::UnsupportedTokenExtension => "UnsupportedTokenExtension",
+            EscrowError::ExpiryTooFar => "ExpiryTooFar",
+        }
+    }
+}
+
+/// Lists every error as its `ProgramError::Custom` code and name, so clients can
+/// generate a decoder map instead of hardcoding the codes.
+pub fn all_errors() -> Vec<(u32, &'static str)> {
+    EscrowError::ALL
+        .iter()
+        .map(|e| (*e as u32, e.name()))
+        .collect()
 }
 
 impl From<EscrowError> for ProgramError {
@@ -23,3 +273,103 @@ impl From<EscrowError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+// by hand off `ALL` rather than derived, codes are positions there anyway
+impl FromPrimitive for EscrowError {
+    fn from_i64(n: i64) -> Option<Self> {
+        u64::try_from(n).ok().and_then(Self::from_u64)
+    }
+
+    fn from_u64(n: u64) -> Option<Self> {
+        let index = usize::try_from(n).ok()?;
+        EscrowError::ALL.get(index).copied()
+    }
+}
+
+impl<T> DecodeError<T> for EscrowError {
+    fn type_of() -> &'static str {
+        "EscrowError"
+    }
+}
+
+impl PrintProgramError for EscrowError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        msg!("Error: {}: {}", self.name(), self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_errors_covers_every_variant() {
+        // exhaustive on purpose: a new variant fails to compile here until it is listed
+        fn position(e: EscrowError) -> usize {
+            match e {
+                EscrowError::InvalidInstruction => 0,
+                EscrowError::NotRentExempt => 1,
+                EscrowError::ExpectedAmountMismatch => 2,
+                EscrowError::AmountOverflow => 3,
+                EscrowError::AuthorityTransferFailed => 4,
+                EscrowError::DuplicateAccount => 5,
+                EscrowError::RentUnavailable => 6,
+                EscrowError::InsufficientDeposit => 7,
+                EscrowError::WrongAccountCount => 8,
+                EscrowError::UnexpectedAccount => 9,
+                EscrowError::InvalidUiAmount => 10,
+                EscrowError::AccountMismatch => 11,
+                EscrowError::SourceTotalMismatch => 12,
+                EscrowError::InsufficientTakerFunds => 13,
+                EscrowError::NotArbiter => 14,
+                EscrowError::VaultAmountChanged => 15,
+                EscrowError::InstructionTooLarge => 16,
+                EscrowError::InvalidPrice => 17,
+                EscrowError::AccountFrozen => 18,
+                EscrowError::InvalidSide => 19,
+                EscrowError::AccountRoleMismatch => 20,
+                EscrowError::EscrowExpired => 21,
+                EscrowError::MintMismatch => 22,
+                EscrowError::InvalidFillAmount => 23,
+                EscrowError::InvalidFeeBps => 24,
+                EscrowError::UnsupportedVersion => 25,
+                EscrowError::NotInitializer => 26,
+                EscrowError::InvalidTokenProgram => 27,
+                EscrowError::InvalidPda => 28,
+                EscrowError::EscrowNotInitialized => 29,
+                EscrowError::InvalidAmount => 30,
+                EscrowError::InvalidAccountOwner => 31,
+                EscrowError::UnauthorizedTaker => 32,
+                EscrowError::EscrowNotExpired => 33,
+                EscrowError::ProgramPaused => 34,
+                EscrowError::NotAdmin => 35,
+                EscrowError::AccountNotWritable => 36,
+                EscrowError::InvalidStateTransition => 37,
+                EscrowError::TooManyAccounts => 38,
+                EscrowError::EscrowNotStarted => 39,
+                EscrowError::AuctionPriceMoved => 40,
+                EscrowError::InvalidAccountDiscriminator => 41,
+                EscrowError::NothingToRefund => 42,
+                EscrowError::FillTooSmall => 43,
+                EscrowError::StillPaused => 44,
+                EscrowError::UnsupportedTokenExtension => 45,
+                EscrowError::ExpiryTooFar => 46,
+            }
+        }
+
+        let errors = all_errors();
+        assert_eq!(errors.len(), EscrowError::ALL.len());
+        for (i, e) in EscrowError::ALL.iter().enumerate() {
+            assert_eq!(position(*e), i);
+            assert_eq!(errors[i], (i as u32, e.name()));
+            assert_eq!(ProgramError::from(*e), ProgramError::Custom(errors[i].0));
+            assert_eq!(EscrowError::decode_custom_error_to_enum(errors[i].0), Some(*e));
+        }
+        let unknown: Option<EscrowError> =
+            EscrowError::decode_custom_error_to_enum(EscrowError::ALL.len() as u32);
+        assert_eq!(unknown, None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Events the program logs for off-chain indexers, so they never have to parse the
+//! free-form `msg!` lines.
+//!
+//! Each event is one log line, `EVENT ` followed by the event in base58. The decoded
+//! bytes are an `EscrowEvent` as Borsh packs it: a variant byte, then the variant's
+//! fields in order, keys as 32 raw bytes and amounts as little endian u64s.
+//!
+//! 0. `Initialized`: `[0]`, escrow, initializer, temp account, expected amount, offered amount
+//! 1. `Exchanged`: `[1]`, escrow, taker, initializer, amount, payment
+//!
+//! Variants and fields are only ever appended, so a decoder keeps reading older logs.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+
+/// Prefix of every event line, after the runtime's own `Program log: `
+pub const EVENT_PREFIX: &str = "EVENT ";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub enum EscrowEvent {
+    Initialized(EscrowInitialized),
+    Exchanged(EscrowExchanged),
+}
+
+/// An escrow went live and its offer sits in `temp_account`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct EscrowInitialized {
+    pub escrow: Pubkey,
+    pub initializer: Pubkey,
+    /// The vault, the escrow account itself for an offer of SOL
+    pub temp_account: Pubkey,
+    pub expected_amount: u64,
+    pub offered_amount: u64,
+}
+
+/// A taker took `amount` of the offer for `payment`, the fee included. The escrow stays
+/// open if `amount` was only part of the offer
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct EscrowExchanged {
+    pub escrow: Pubkey,
+    pub taker: Pubkey,
+    pub initializer: Pubkey,
+    pub amount: u64,
+    pub payment: u64,
+}
+
+impl EscrowEvent {
+    /// Writes the event to the program log as one `EVENT ` line
+    pub fn emit(&self) -> Result<(), ProgramError> {
+        let data = self
+            .try_to_vec()
+            .map_err(|error| ProgramError::BorshIoError(error.to_string()))?;
+        msg!("{}{}", EVENT_PREFIX, bs58::encode(data).into_string());
+        Ok(())
+    }
+
+    /// Reads an event back from a log line, `None` for any line that isn't one.
+    /// The runtime's `Program log: ` prefix may be left on.
+    pub fn from_log(line: &str) -> Option<Self> {
+        let line = line.strip_prefix("Program log: ").unwrap_or(line);
+        let data = bs58::decode(line.strip_prefix(EVENT_PREFIX)?).into_vec().ok()?;
+        Self::try_from_slice(&data).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_layout_is_stable() {
+        let event = EscrowEvent::Exchanged(EscrowExchanged {
+            escrow: Pubkey::new_from_array([1; 32]),
+            taker: Pubkey::new_from_array([2; 32]),
+            initializer: Pubkey::new_from_array([3; 32]),
+            amount: 0x0102_0304_0506_0708,
+            payment: 0x1112_1314_1516_1718,
+        });
+        let data = event.try_to_vec().unwrap();
+        let expected = [
+            &[1][..],
+            &[1; 32],
+            &[2; 32],
+            &[3; 32],
+            &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01],
+            &[0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11],
+        ]
+        .concat();
+        assert_eq!(data, expected);
+        assert_eq!(EscrowEvent::try_from_slice(&data).unwrap(), event);
+
+        let line = format!("Program log: {}{}", EVENT_PREFIX, bs58::encode(&data).into_string());
+        assert_eq!(EscrowEvent::from_log(&line), Some(event));
+        assert_eq!(EscrowEvent::from_log("Program log: Instruction: Exchange"), None);
+    }
+}
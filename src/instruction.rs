@@ -1,8 +1,38 @@
-use solana_program::program_error::ProgramError;
-use std::convert::TryInto;
+use borsh::BorshDeserialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar,
+};
 
-use crate::error::EscrowError::InvalidInstruction;
+use crate::{
+    error::{
+        EscrowError,
+        EscrowError::{DuplicateAccount, InvalidInstruction, UnexpectedAccount, WrongAccountCount},
+    },
+    state::{
+        config_address, vault_authority, Escrow, RoundingMode, Side,
+        DEFAULT_MAX_EXPIRY_HORIZON_SECS, MAX_BASKET_LEN,
+    },
+    token,
+};
+
+/// The most instruction data the program will read. Well above the largest variant
+/// (InitEscrowWithFee, 52 bytes), so new fields don't have to move it.
+pub const MAX_INSTRUCTION_DATA_LEN: usize = 128;
+
+/// Which way an arbiter settles an escrow
+#[cfg(feature = "arbitration")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArbiterDecision {
+    /// The taker gets the offered tokens, any surplus goes back to the initializer
+    Release,
+    /// The initializer gets the whole temp token account back
+    Refund,
+}
 
+#[derive(Debug, PartialEq)]
 pub enum EscrowInstruction {
     /// Starts the trade by creating and populating an escrow account and transferring ownership of the given temp token account to the PDA
     ///
@@ -14,10 +44,113 @@ pub enum EscrowInstruction {
     /// 2. `[]` The initializer's token account for the token they will receive should the trade go through
     /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
     /// 4. `[]` The rent sysvar
-    /// 5. `[]` The token program
+    /// 5. `[]` The token program, spl-token or Token-2022, which must own accounts 1 and 2
+    /// 6. `[]` The program's config account, at `config_address`
     InitEscrow {
         /// The amount party A expects to receive of token Y
         amount: u64,
+        /// The amount of token X party A offers, the temp token account must hold at least this much
+        offered_amount: u64,
+        /// How front-ends should label the order, a trailing byte that defaults to `Sell` when left off
+        side: Side,
+        /// Unix time after which the escrow can no longer be taken, 8 bytes after `side` that default
+        /// to `0`, meaning never
+        expiry_unix_timestamp: i64,
+        /// Unix time before which the escrow can't be taken yet, 8 bytes after the expiry that
+        /// default to `0`, meaning at once
+        start_unix_timestamp: i64,
+        /// The least a PartialExchange may take unless it takes the rest, 8 bytes after the
+        /// start that default to `0`, meaning any fill
+        min_fill_amount: u64,
+        /// Who keeps the remainder when a partial fill's share of the price is rounded, a byte
+        /// after the minimum fill that defaults to `FloorToMaker`
+        rounding: RoundingMode,
+    },
+    /// Same as InitEscrow, but the offer is split across several of the initializer's token
+    /// accounts; each extra source is emptied into the temp token account before it goes to the PDA
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0-6. As for InitEscrow
+    /// 7. `[]` The mint of the offered token
+    /// 8.. `[writable]` `sources` more token accounts of the offered mint, owned by the initializer
+    InitEscrowMulti {
+        /// The amount party A expects to receive of token Y
+        amount: u64,
+        /// The combined amount of token X offered, the temp token account and the sources must hold exactly this much
+        offered_amount: u64,
+        /// The number of extra source token accounts
+        sources: u8,
+    },
+    /// Same as InitEscrow, but names an arbiter who may later force-settle the escrow with Arbitrate
+    ///
+    ///
+    /// Accounts expected: as for InitEscrow
+    #[cfg(feature = "arbitration")]
+    InitEscrowWithArbiter {
+        /// The amount party A expects to receive of token Y
+        amount: u64,
+        /// The amount of token X party A offers, the temp token account must hold at least this much
+        offered_amount: u64,
+        /// The key that must sign Arbitrate
+        arbiter: Pubkey,
+    },
+    /// Settles a disputed escrow on the arbiter's word, without the taker paying through the program
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The arbiter stored in the escrow
+    /// 1. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 2. `[writable]` The initializer's main account to send their rent fees to
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The PDA account
+    /// 6. `[writable]` The taker's token account that will receive the offer, for `Release` only
+    /// 7. `[]` The mint of the offered token, for `Release` only
+    #[cfg(feature = "arbitration")]
+    Arbitrate { decision: ArbiterDecision },
+    /// Exchange into a token account the taker creates in the same instruction
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable, signer]` The account of the person taking the trade, pays for the new account
+    /// 1. `[writable]` The taker's token account for the token they send
+    /// 2. `[writable, signer]` A fresh keypair with no lamports, becomes the taker's token account for the token they receive
+    /// 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 4. `[writable]` The initializer's main account to send their rent fees to
+    /// 5. `[writable]` The initializer's token account that will receive tokens
+    /// 6. `[writable]` The escrow account holding the escrow info
+    /// 7. `[]` The token program the escrow was made with
+    /// 8. `[]` The PDA account
+    /// 9. `[]` The clock sysvar
+    /// 10. `[writable]` The fee account stored in the escrow
+    /// 11. `[]` The mint of the token the taker pays
+    /// 12. `[]` The mint of the offered token, which the new account is created for
+    /// 13. `[]` The program's config account, at `config_address`
+    /// 14. `[]` The system program
+    /// 15. `[]` The rent sysvar
+    #[cfg(feature = "create-receive-account")]
+    ExchangeIntoNewAccount {
+        /// the amount the taker expects to be paid in the other token
+        amount: u64,
+        /// if given, the most the taker pays, as for Exchange; left off the end to skip it
+        max_payment: Option<u64>,
+    },
+    /// Same as InitEscrow, but the taker pays a price per offered token instead of a fixed amount,
+    /// and takes the whole temp token account at whatever it holds when they settle
+    ///
+    ///
+    /// Accounts expected: as for InitEscrow
+    InitEscrowAtPrice {
+        /// The amount of token X party A offers, the temp token account must hold at least this much
+        offered_amount: u64,
+        /// Base units of token Y asked per `denominator` base units of token X
+        numerator: u64,
+        /// Base units of token X the `numerator` buys
+        denominator: u64,
     },
     /// Accepts a trade
     ///
@@ -31,36 +164,2093 @@ pub enum EscrowInstruction {
     /// 4. `[writable]` The initializer's main account to send their rent fees to
     /// 5. `[writable]` The initializer's token account that will receive tokens
     /// 6. `[writable]` The escrow account holding the escrow info
-    /// 7. `[]` The token program
+    /// 7. `[]` The token program the escrow was made with
     /// 8. `[]` The PDA account
+    /// 9. `[]` The clock sysvar
+    /// 10. `[writable]` The fee account stored in the escrow, which only receives anything if it charges a fee
+    /// 11. `[]` The mint of the token the taker pays
+    /// 12. `[]` The mint of the offered token
+    /// 13. `[]` The program's config account, at `config_address`
+    /// 14. `[]` The associated-token-account program, for `create_missing_accounts` only
+    /// 15. `[]` The system program, for `create_missing_accounts` only
     Exchange {
-        /// the amount the taker expects to be paid in the other token, as a u64 because that's the max possible supply of a token
+        /// the least the taker accepts to be paid in the other token, as a u64 because that's the max possible supply of a token;
+        /// the offer may have grown since they saw it, but not shrunk
+        min_acceptable_amount: u64,
+        /// if given, the most the taker pays, fee included; what they quoted can be repriced
+        /// before the trade lands. `u64::MAX` sets no cap, for sending the vault amount alone
+        max_payment: Option<u64>,
+        /// if given, the exact balance the taker quoted for the PDA's temp token account; left off the end of the data to skip the check
+        expected_vault_amount: Option<u64>,
+        /// whether 2. and 5. are created as their owners' associated accounts if they don't
+        /// exist yet, at the taker's cost; a trailing byte that is only ever sent set, and only
+        /// read with the `create-receive-account` feature
+        create_missing_accounts: bool,
+    },
+    /// Accepts a trade that the initializer co-signs, so they keep a veto until settlement
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the trade
+    /// 1. `[writable]` The taker's token account for the token they send
+    /// 2. `[writable]` The taker's token account for the token they will receive should the trade go through
+    /// 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 4. `[writable, signer]` The initializer's main account to send their rent fees to
+    /// 5. `[writable]` The initializer's token account that will receive tokens
+    /// 6. `[writable]` The escrow account holding the escrow info
+    /// 7. `[]` The token program the escrow was made with
+    /// 8. `[]` The PDA account
+    /// 9. `[]` The clock sysvar
+    /// 10. `[writable]` The fee account stored in the escrow, which only receives anything if it charges a fee
+    /// 11. `[]` The mint of the token the taker pays
+    /// 12. `[]` The mint of the offered token
+    /// 13. `[]` The program's config account, at `config_address`
+    CosignedExchange {
+        /// the amount the taker expects to be paid in the other token
+        amount: u64,
+        /// if given, the most the taker pays, as for Exchange; left off the end to skip it
+        max_payment: Option<u64>,
+    },
+    /// Takes part of a trade: the taker gets `fill_amount` of the offered tokens and pays its share
+    /// of the expected amount, rounded up. The escrow stays open with the rest until a fill drains it
+    ///
+    ///
+    /// Accounts expected: as for Exchange
+    PartialExchange {
+        /// the least of the whole offer the taker accepts, checked like Exchange's `min_acceptable_amount`
+        amount: u64,
+        /// how many of the offered tokens to take, at most the whole offer
+        fill_amount: u64,
+        /// if given, the most the taker pays for the fill, as for Exchange; left off the end
+        /// to skip it
+        max_payment: Option<u64>,
+    },
+    /// Same as InitEscrow, but every Exchange skims a fee off the taker's payment before the
+    /// initializer gets the rest
+    ///
+    ///
+    /// Accounts expected: as for InitEscrow
+    InitEscrowWithFee {
+        /// The amount party A expects to receive of token Y, the fee included
+        amount: u64,
+        /// The amount of token X party A offers, the temp token account must hold at least this much
+        offered_amount: u64,
+        /// The fee in hundredths of a percent of each payment, at most 10000
+        fee_basis_points: u16,
+        /// The token account of token Y that collects the fee
+        fee_account: Pubkey,
+        /// Who keeps the remainders when the fee and a partial fill's share of the price are
+        /// rounded, a trailing byte that defaults to `FloorToMaker`
+        rounding: RoundingMode,
+    },
+    /// Moves an escrow written by the original program, `Escrow::V0_LEN` long, into an account
+    /// of the current layout. The old account can't grow in place, so the state is copied into
+    /// a new one, the vault is handed from the original program-wide PDA to the new escrow's
+    /// own and the old account is closed to the initializer. The offer is the whole vault, as
+    /// the original Exchange traded it, and the mints are read off the two token accounts
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable, signer]` The initializer stored in the escrow, who gets the old account's rent
+    /// 1. `[writable]` The old escrow account, closed once migrated
+    /// 2. `[writable]` The new escrow account, owned by this program, rent exempt and `Escrow::LEN` long
+    /// 3. `[writable]` The PDA's temp token account
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The original program's PDA account, at `legacy_vault_authority`
+    /// 6. `[]` The rent sysvar
+    /// 7. `[]` The initializer's token account to receive the expected token, as stored
+    MigrateEscrow,
+    /// Offers SOL rather than a token: the lamports sit in the escrow account on top of its
+    /// rent-exempt minimum, so there is no temp token account to hand to a PDA.
+    /// Exchange and PartialExchange take it with the usual accounts, except that 2. is any
+    /// writable account the taker wants the lamports in, 3. is the escrow account again and
+    /// 12. is the native mint
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[]` The initializer's token account for the token they will receive should the trade go through
+    /// 2. `[writable]` The escrow account, owned by this program and funded with rent plus the offer
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The program's config account, at `config_address`
+    InitEscrowNative {
+        /// The amount party A expects to receive of token Y
+        amount: u64,
+        /// The lamports party A offers, the escrow account must hold at least this much above rent
+        offered_amount: u64,
+    },
+    /// Reprices a live escrow without closing it. A taker's Exchange that lands after it pays
+    /// the new amount, the offered amount they state doesn't cover the price
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer stored in the escrow
+    /// 1. `[writable]` The escrow account holding the escrow info
+    UpdateExpectedAmount {
+        /// What party A now expects to receive of token Y for the rest of the offer
+        new_amount: u64,
+    },
+    /// Same as InitEscrow, but only `allowed_taker` may take the escrow, for a deal agreed
+    /// with one counterparty ahead of time
+    ///
+    ///
+    /// Accounts expected: as for InitEscrow
+    InitEscrowPrivate {
+        /// The amount party A expects to receive of token Y
+        amount: u64,
+        /// The amount of token X party A offers, the temp token account must hold at least this much
+        offered_amount: u64,
+        /// The key that must sign Exchange as the taker
+        allowed_taker: Pubkey,
+    },
+    /// Closes an escrow whose deadline has passed, handing the offer and every rent-exempt
+    /// reserve back to the initializer. Anyone may send it, nobody has to sign.
+    /// An escrow made by InitEscrowNative holds its offer in the escrow account, which is
+    /// simply closed; accounts 0, 1, 5 and 7 are then not read
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The PDA's temp token account, emptied and closed
+    /// 1. `[writable]` The initializer's token account for the offered token, which gets the tokens
+    /// 2. `[writable]` The initializer's main account, which gets the rent
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The clock sysvar
+    /// 7. `[]` The mint of the offered token
+    ReclaimExpired,
+    /// Creates the program's config account with the signer as its admin, unpaused. Only the
+    /// program's upgrade authority may send it
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable, signer]` The admin, the program's upgrade authority, who pays for the account
+    /// 1. `[writable]` The config account, at `config_address`
+    /// 2. `[]` The system program
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The program's ProgramData account, at `program_data_address`
+    InitConfig {
+        /// Seconds every later unpause waits before it takes effect; left off the end of the
+        /// data for none
+        unpause_delay: i64,
+        /// Seconds past the time of init an escrow's expiry may be set to at most; left off
+        /// the end of the data for `DEFAULT_MAX_EXPIRY_HORIZON_SECS`
+        max_expiry_horizon_secs: i64,
+    },
+    /// Stops or restarts every init and exchange. Escrows already made stay as they are, and
+    /// can still be reclaimed once expired. A restart only takes effect once the config's
+    /// `unpause_delay` has passed, while pausing takes effect at once
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin stored in the config
+    /// 1. `[writable]` The config account, at `config_address`
+    SetPaused { paused: bool },
+    /// Same as InitEscrow, but offers a bundle: the whole of the temp token account and of
+    /// each basket vault goes to the PDA, and a taker gets all of them for the one payment
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0-6. As for InitEscrow
+    /// 7.. `[writable]` `basket_len` more temp token accounts, owned by the initializer
+    InitBasketEscrow {
+        /// The amount party A expects to receive of token Y for the whole bundle
+        amount: u64,
+        /// The number of basket vaults, at most `MAX_BASKET_LEN`
+        basket_len: u8,
+    },
+    /// Takes the whole of a basket escrow. A basket escrow can only be taken this way, and
+    /// only whole
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0-13. As for Exchange
+    /// 14.. Three for each of the escrow's basket vaults, in the order stored:
+    ///     `[writable]` the basket vault, emptied and closed, then
+    ///     `[writable]` the taker's token account that receives its tokens, then
+    ///     `[]` the mint of its tokens
+    ExchangeBasket {
+        /// the amount the taker expects to be paid out of the temp token account
+        amount: u64,
+        /// The number of basket vaults, which must match the escrow's
+        basket_len: u8,
+        /// if given, the most the taker pays for the whole bundle, as for Exchange; left off
+        /// the end to skip it
+        max_payment: Option<u64>,
+    },
+    /// Tops up a live escrow's offer: more of the offered token goes into the PDA's temp token
+    /// account, which the PDA already owns, and the stored offer grows by as much. A taker's
+    /// Exchange quoting the old offer still takes the larger one at whatever is asked for it
+    /// now, so only the `max_payment` they sent holds a raise landing first to their quote
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer stored in the escrow
+    /// 1. `[writable]` The initializer's token account of the offered token to take the deposit from
+    /// 2. `[writable]` The PDA's temp token account
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The mint of the offered token
+    DepositMore {
+        /// How much more of token X goes into the offer
+        additional: u64,
+        /// if given, what party A now expects to receive of token Y for the whole offer; left
+        /// off the end of the data to keep the stored amount
+        expected_amount: Option<u64>,
+    },
+    /// Same as InitEscrow, but sells the offer by Dutch auction: what a taker pays for the whole
+    /// of it falls in a straight line from `start_amount` to `end_amount` over the window, and
+    /// holds at either end outside it
+    ///
+    ///
+    /// Accounts expected: as for InitEscrow
+    InitDutchAuction {
+        /// The amount of token X offered, the temp token account must hold at least this much
+        offered_amount: u64,
+        /// The amount of token Y the offer costs up to the start of the window
+        start_amount: u64,
+        /// The amount of token Y the offer costs from the end of the window, above zero and no
+        /// more than `start_amount`
+        end_amount: u64,
+        /// Unix time the price starts falling
+        start_unix_timestamp: i64,
+        /// Unix time the price reaches `end_amount`, after `start_unix_timestamp`
+        end_unix_timestamp: i64,
+    },
+    /// Takes the whole of a Dutch auction escrow at the price the auction asks on the clock.
+    /// An auction can only be taken this way, and only whole
+    ///
+    ///
+    /// Accounts expected: as for Exchange
+    ExchangeDutchAuction {
+        /// the amount the taker expects to be paid in the other token, as for Exchange
+        amount: u64,
+        /// What the taker saw the auction ask when they signed
+        quoted_payment: u64,
+        /// How far the price may have moved from `quoted_payment`, either way, by the time
+        /// the exchange lands
+        tolerance: u64,
+    },
+    /// Hands a live escrow over to a new initializer, who is then paid by Exchange, gets the
+    /// rent back and alone may reprice, top up or reclaim it. The receiving account stays as
+    /// it was unless a new one is given, so payment keeps going there until it is
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer stored in the escrow
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The new token account for token Y, only if `new_token_to_receive_account` is
+    ///    given
+    TransferInitializer {
+        /// The key that takes over as initializer
+        new_initializer: Pubkey,
+        /// if given, the new initializer's token account for token Y; left off the end of the
+        /// data to keep the stored one
+        new_token_to_receive_account: Option<Pubkey>,
+    },
+    /// Sends tokens a taker left in a token account the escrow's PDA owns back to them, for
+    /// a transfer that landed there by mistake. The offer itself can't be refunded this way.
+    /// Nothing records who sent the tokens, so the taker co-signs for them and the refund
+    /// only goes to an account of theirs
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer stored in the escrow, or the admin stored in the config
+    /// 1. `[writable]` The holding token account, owned by the PDA but not one of the escrow's vaults
+    /// 2. `[writable]` The taker's token account that gets the refund, owned by 8.
+    /// 3. `[]` The escrow account holding the escrow info
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The mint of the held token
+    /// 7. `[]` The config account, at `config_address`
+    /// 8. `[signer]` The taker who sent the tokens
+    RefundTaker {
+        /// How much of the held token goes back, at most what the holding account holds
+        amount: u64,
+    },
+    /// Points a live escrow's payment at another of the initializer's token accounts, for when
+    /// the stored one was closed or the proceeds should land in a different wallet
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer stored in the escrow
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The token account for token Y that receives payment from now on
+    SetReceiveAccount,
+    /// Takes part of a live escrow's offer back out of the PDA's temp token account, and the
+    /// stored offer shrinks by as much. The expected amount stays as it was, for what is left.
+    /// Some of the offer has to stay behind, the whole of it only comes back by reclaiming
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer stored in the escrow
+    /// 1. `[writable]` The initializer's token account of the offered token to take the withdrawal
+    /// 2. `[writable]` The PDA's temp token account
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The mint of the offered token
+    Withdraw {
+        /// How much of token X comes out of the offer, less than what it still holds
         amount: u64,
     },
+    /// Points a live escrow's payment at the initializer's associated account for the mint it
+    /// expects, creating that account first at the initializer's cost if it doesn't exist
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable, signer]` The initializer stored in the escrow
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[writable]` The initializer's associated account for token Y
+    /// 3. `[]` The mint of token Y
+    /// 4. `[]` The token program the escrow was made with
+    /// 5. `[]` The associated-token-account program
+    /// 6. `[]` The system program
+    #[cfg(feature = "create-receive-account")]
+    SetReceiveAta,
 }
 
 impl EscrowInstruction {
     /// Unpacks a byte buffer into a [EscrowInstruction](enum.EscrowInstruction.html).
+    ///
+    /// The tag byte is matched by hand and each field is then read off the rest with Borsh.
+    /// A derive on the whole enum would number variants by position, so the feature-gated
+    /// ones would move every later tag, and Borsh's `Option` wants a presence byte the
+    /// optional trailing fields have never had.
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
+        let (tag, mut rest) = input.split_first().ok_or(InvalidInstruction)?;
+        let rest = &mut rest;
 
         Ok(match tag {
-            0 => Self::InitEscrow {
-                amount: Self::unpack_amount(rest)?,
+            0 => {
+                let amount = Self::read(rest)?;
+                let offered_amount = Self::read(rest)?;
+                let side = if rest.is_empty() {
+                    Side::Sell
+                } else {
+                    Side::from_u8(Self::read(rest)?)?
+                };
+                let expiry_unix_timestamp = if rest.is_empty() {
+                    0
+                } else {
+                    Self::read(rest)?
+                };
+                let start_unix_timestamp = if rest.is_empty() {
+                    0
+                } else {
+                    Self::read(rest)?
+                };
+                let min_fill_amount = if rest.is_empty() {
+                    0
+                } else {
+                    Self::read(rest)?
+                };
+                let rounding = if rest.is_empty() {
+                    RoundingMode::default()
+                } else {
+                    RoundingMode::from_u8(Self::read(rest)?).ok_or(InvalidInstruction)?
+                };
+                if !rest.is_empty() {
+                    return Err(InvalidInstruction.into());
+                }
+                Self::InitEscrow {
+                    amount,
+                    offered_amount,
+                    side,
+                    expiry_unix_timestamp,
+                    start_unix_timestamp,
+                    min_fill_amount,
+                    rounding,
+                }
+            }
+            1 => {
+                let min_acceptable_amount = Self::read(rest)?;
+                // one byte can't be an amount, so the flag can follow either or stand alone
+                let max_payment = if rest.len() < 8 {
+                    None
+                } else {
+                    Some(Self::read(rest)?).filter(|max_payment| *max_payment != u64::MAX)
+                };
+                let expected_vault_amount = if rest.len() < 8 {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                };
+                let create_missing_accounts = match rest.split_first() {
+                    None => false,
+                    #[cfg(feature = "create-receive-account")]
+                    Some((1, [])) => true,
+                    Some(_) => return Err(InvalidInstruction.into()),
+                };
+                Self::Exchange {
+                    min_acceptable_amount,
+                    max_payment,
+                    expected_vault_amount,
+                    create_missing_accounts,
+                }
+            }
+            2 => Self::CosignedExchange {
+                amount: Self::read(rest)?,
+                max_payment: if rest.is_empty() {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                },
+            },
+            3 => Self::InitEscrowMulti {
+                amount: Self::read(rest)?,
+                offered_amount: Self::read(rest)?,
+                sources: Self::read(rest)?,
+            },
+            #[cfg(feature = "arbitration")]
+            4 => Self::InitEscrowWithArbiter {
+                amount: Self::read(rest)?,
+                offered_amount: Self::read(rest)?,
+                arbiter: Self::read(rest)?,
+            },
+            #[cfg(feature = "arbitration")]
+            5 => Self::Arbitrate {
+                decision: match Self::read::<u8>(rest)? {
+                    0 => ArbiterDecision::Release,
+                    1 => ArbiterDecision::Refund,
+                    _ => return Err(InvalidInstruction.into()),
+                },
+            },
+            #[cfg(feature = "create-receive-account")]
+            6 => Self::ExchangeIntoNewAccount {
+                amount: Self::read(rest)?,
+                max_payment: if rest.is_empty() {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                },
+            },
+            7 => Self::InitEscrowAtPrice {
+                offered_amount: Self::read(rest)?,
+                numerator: Self::read(rest)?,
+                denominator: Self::read(rest)?,
+            },
+            8 => Self::PartialExchange {
+                amount: Self::read(rest)?,
+                fill_amount: Self::read(rest)?,
+                max_payment: if rest.is_empty() {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                },
+            },
+            9 => Self::InitEscrowWithFee {
+                amount: Self::read(rest)?,
+                offered_amount: Self::read(rest)?,
+                fee_basis_points: Self::read(rest)?,
+                fee_account: Self::read(rest)?,
+                rounding: if rest.is_empty() {
+                    RoundingMode::default()
+                } else {
+                    RoundingMode::from_u8(Self::read(rest)?).ok_or(InvalidInstruction)?
+                },
+            },
+            10 => Self::MigrateEscrow,
+            11 => Self::InitEscrowNative {
+                amount: Self::read(rest)?,
+                offered_amount: Self::read(rest)?,
+            },
+            12 => Self::UpdateExpectedAmount {
+                new_amount: Self::read(rest)?,
+            },
+            13 => Self::InitEscrowPrivate {
+                amount: Self::read(rest)?,
+                offered_amount: Self::read(rest)?,
+                allowed_taker: Self::read(rest)?,
+            },
+            14 => Self::ReclaimExpired,
+            15 => Self::InitConfig {
+                unpause_delay: if rest.is_empty() {
+                    0
+                } else {
+                    Self::read(rest)?
+                },
+                max_expiry_horizon_secs: if rest.is_empty() {
+                    DEFAULT_MAX_EXPIRY_HORIZON_SECS
+                } else {
+                    Self::read(rest)?
+                },
+            },
+            16 => Self::SetPaused {
+                paused: Self::read(rest)?,
+            },
+            17 => Self::InitBasketEscrow {
+                amount: Self::read(rest)?,
+                basket_len: Self::read(rest)?,
             },
-            1 => Self::Exchange {
-                amount: Self::unpack_amount(rest)?,
+            18 => Self::ExchangeBasket {
+                amount: Self::read(rest)?,
+                basket_len: Self::read(rest)?,
+                max_payment: if rest.is_empty() {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                },
             },
+            19 => Self::DepositMore {
+                additional: Self::read(rest)?,
+                expected_amount: if rest.is_empty() {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                },
+            },
+            20 => Self::InitDutchAuction {
+                offered_amount: Self::read(rest)?,
+                start_amount: Self::read(rest)?,
+                end_amount: Self::read(rest)?,
+                start_unix_timestamp: Self::read(rest)?,
+                end_unix_timestamp: Self::read(rest)?,
+            },
+            21 => Self::ExchangeDutchAuction {
+                amount: Self::read(rest)?,
+                quoted_payment: Self::read(rest)?,
+                tolerance: Self::read(rest)?,
+            },
+            22 => Self::TransferInitializer {
+                new_initializer: Self::read(rest)?,
+                new_token_to_receive_account: if rest.is_empty() {
+                    None
+                } else {
+                    Some(Self::read(rest)?)
+                },
+            },
+            23 => Self::RefundTaker {
+                amount: Self::read(rest)?,
+            },
+            24 => Self::SetReceiveAccount,
+            25 => Self::Withdraw {
+                amount: Self::read(rest)?,
+            },
+            #[cfg(feature = "create-receive-account")]
+            26 => Self::SetReceiveAta,
             _ => return Err(InvalidInstruction.into()),
         })
     }
 
-    fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
-        let amount = input
-            .get(..8)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u64::from_le_bytes)
-            .ok_or(InvalidInstruction)?;
-        Ok(amount)
+    /// Packs a [EscrowInstruction](enum.EscrowInstruction.html) into the byte buffer `unpack` reads.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MAX_INSTRUCTION_DATA_LEN);
+        match self {
+            Self::InitEscrow {
+                amount,
+                offered_amount,
+                side,
+                expiry_unix_timestamp,
+                start_unix_timestamp,
+                min_fill_amount,
+                rounding,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.push(*side as u8);
+                buf.extend_from_slice(&expiry_unix_timestamp.to_le_bytes());
+                buf.extend_from_slice(&start_unix_timestamp.to_le_bytes());
+                buf.extend_from_slice(&min_fill_amount.to_le_bytes());
+                buf.push(*rounding as u8);
+            }
+            Self::Exchange {
+                min_acceptable_amount,
+                max_payment,
+                expected_vault_amount,
+                create_missing_accounts,
+            } => {
+                buf.push(1);
+                buf.extend_from_slice(&min_acceptable_amount.to_le_bytes());
+                // the vault amount can only be told apart from the cap by coming second
+                if max_payment.is_some() || expected_vault_amount.is_some() {
+                    buf.extend_from_slice(&max_payment.unwrap_or(u64::MAX).to_le_bytes());
+                }
+                if let Some(vault_amount) = expected_vault_amount {
+                    buf.extend_from_slice(&vault_amount.to_le_bytes());
+                }
+                if *create_missing_accounts {
+                    buf.push(1);
+                }
+            }
+            Self::CosignedExchange {
+                amount,
+                max_payment,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                if let Some(max_payment) = max_payment {
+                    buf.extend_from_slice(&max_payment.to_le_bytes());
+                }
+            }
+            Self::InitEscrowMulti {
+                amount,
+                offered_amount,
+                sources,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.push(*sources);
+            }
+            #[cfg(feature = "arbitration")]
+            Self::InitEscrowWithArbiter {
+                amount,
+                offered_amount,
+                arbiter,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(arbiter.as_ref());
+            }
+            #[cfg(feature = "arbitration")]
+            Self::Arbitrate { decision } => {
+                buf.push(5);
+                buf.push(match decision {
+                    ArbiterDecision::Release => 0,
+                    ArbiterDecision::Refund => 1,
+                });
+            }
+            #[cfg(feature = "create-receive-account")]
+            Self::ExchangeIntoNewAccount {
+                amount,
+                max_payment,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                if let Some(max_payment) = max_payment {
+                    buf.extend_from_slice(&max_payment.to_le_bytes());
+                }
+            }
+            Self::InitEscrowAtPrice {
+                offered_amount,
+                numerator,
+                denominator,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&numerator.to_le_bytes());
+                buf.extend_from_slice(&denominator.to_le_bytes());
+            }
+            Self::PartialExchange {
+                amount,
+                fill_amount,
+                max_payment,
+            } => {
+                buf.push(8);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&fill_amount.to_le_bytes());
+                if let Some(max_payment) = max_payment {
+                    buf.extend_from_slice(&max_payment.to_le_bytes());
+                }
+            }
+            Self::InitEscrowWithFee {
+                amount,
+                offered_amount,
+                fee_basis_points,
+                fee_account,
+                rounding,
+            } => {
+                buf.push(9);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&fee_basis_points.to_le_bytes());
+                buf.extend_from_slice(fee_account.as_ref());
+                buf.push(*rounding as u8);
+            }
+            Self::MigrateEscrow => buf.push(10),
+            Self::InitEscrowNative {
+                amount,
+                offered_amount,
+            } => {
+                buf.push(11);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+            }
+            Self::UpdateExpectedAmount { new_amount } => {
+                buf.push(12);
+                buf.extend_from_slice(&new_amount.to_le_bytes());
+            }
+            Self::InitEscrowPrivate {
+                amount,
+                offered_amount,
+                allowed_taker,
+            } => {
+                buf.push(13);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(allowed_taker.as_ref());
+            }
+            Self::ReclaimExpired => buf.push(14),
+            Self::InitConfig {
+                unpause_delay,
+                max_expiry_horizon_secs,
+            } => {
+                buf.push(15);
+                let default_horizon = *max_expiry_horizon_secs == DEFAULT_MAX_EXPIRY_HORIZON_SECS;
+                if *unpause_delay != 0 || !default_horizon {
+                    buf.extend_from_slice(&unpause_delay.to_le_bytes());
+                }
+                if !default_horizon {
+                    buf.extend_from_slice(&max_expiry_horizon_secs.to_le_bytes());
+                }
+            }
+            Self::SetPaused { paused } => {
+                buf.push(16);
+                buf.push(*paused as u8);
+            }
+            Self::InitBasketEscrow { amount, basket_len } => {
+                buf.push(17);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*basket_len);
+            }
+            Self::ExchangeBasket {
+                amount,
+                basket_len,
+                max_payment,
+            } => {
+                buf.push(18);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*basket_len);
+                if let Some(max_payment) = max_payment {
+                    buf.extend_from_slice(&max_payment.to_le_bytes());
+                }
+            }
+            Self::DepositMore {
+                additional,
+                expected_amount,
+            } => {
+                buf.push(19);
+                buf.extend_from_slice(&additional.to_le_bytes());
+                if let Some(expected_amount) = expected_amount {
+                    buf.extend_from_slice(&expected_amount.to_le_bytes());
+                }
+            }
+            Self::InitDutchAuction {
+                offered_amount,
+                start_amount,
+                end_amount,
+                start_unix_timestamp,
+                end_unix_timestamp,
+            } => {
+                buf.push(20);
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&start_amount.to_le_bytes());
+                buf.extend_from_slice(&end_amount.to_le_bytes());
+                buf.extend_from_slice(&start_unix_timestamp.to_le_bytes());
+                buf.extend_from_slice(&end_unix_timestamp.to_le_bytes());
+            }
+            Self::ExchangeDutchAuction {
+                amount,
+                quoted_payment,
+                tolerance,
+            } => {
+                buf.push(21);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&quoted_payment.to_le_bytes());
+                buf.extend_from_slice(&tolerance.to_le_bytes());
+            }
+            Self::TransferInitializer {
+                new_initializer,
+                new_token_to_receive_account,
+            } => {
+                buf.push(22);
+                buf.extend_from_slice(new_initializer.as_ref());
+                if let Some(new_token_to_receive_account) = new_token_to_receive_account {
+                    buf.extend_from_slice(new_token_to_receive_account.as_ref());
+                }
+            }
+            Self::RefundTaker { amount } => {
+                buf.push(23);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::SetReceiveAccount => buf.push(24),
+            Self::Withdraw { amount } => {
+                buf.push(25);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            #[cfg(feature = "create-receive-account")]
+            Self::SetReceiveAta => buf.push(26),
+        }
+        buf
+    }
+
+    /// An InitEscrow instruction selling `offered_amount` out of `temp_token_account` for `amount`,
+    /// with no expiry. The temp token account must already be funded and owned by `initializer`
+    pub fn init_escrow(
+        program_id: &Pubkey,
+        initializer: &Pubkey,
+        temp_token_account: &Pubkey,
+        initializer_token_to_receive_account: &Pubkey,
+        escrow_account: &Pubkey,
+        amount: u64,
+        offered_amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(*initializer, true),
+                AccountMeta::new(*temp_token_account, false),
+                AccountMeta::new_readonly(*initializer_token_to_receive_account, false),
+                AccountMeta::new(*escrow_account, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(config_address(program_id).0, false),
+            ],
+            data: Self::InitEscrow {
+                amount,
+                offered_amount,
+                side: Side::Sell,
+                expiry_unix_timestamp: 0,
+                start_unix_timestamp: 0,
+                min_fill_amount: 0,
+                rounding: RoundingMode::FloorToMaker,
+            }
+            .pack(),
+        }
+    }
+
+    /// An Exchange instruction taking the whole of `escrow`, as fetched from `escrow_account`.
+    /// Its offered amount is sent as the least the taker accepts and its expected amount as
+    /// the most they pay
+    pub fn exchange(
+        program_id: &Pubkey,
+        taker: &Pubkey,
+        takers_sending_token_account: &Pubkey,
+        takers_token_to_receive_account: &Pubkey,
+        escrow_account: &Pubkey,
+        escrow: &Escrow,
+    ) -> Instruction {
+        Instruction {
+            program_id: *program_id,
+            accounts: Self::exchange_account_metas(
+                program_id,
+                taker,
+                takers_sending_token_account,
+                AccountMeta::new(*takers_token_to_receive_account, false),
+                escrow_account,
+                escrow,
+            ),
+            data: Self::Exchange {
+                min_acceptable_amount: escrow.offered_amount,
+                max_payment: Some(escrow.expected_amount),
+                expected_vault_amount: None,
+                create_missing_accounts: false,
+            }
+            .pack(),
+        }
+    }
+
+    /// An ExchangeBasket instruction taking the whole of basket `escrow`, capped at its expected
+    /// amount as Exchange is. `basket_legs` holds, for each of its vaults in the order stored,
+    /// the taker's token account that receives the vault and the vault's mint
+    pub fn exchange_basket(
+        program_id: &Pubkey,
+        taker: &Pubkey,
+        takers_sending_token_account: &Pubkey,
+        takers_token_to_receive_account: &Pubkey,
+        escrow_account: &Pubkey,
+        escrow: &Escrow,
+        basket_legs: &[(Pubkey, Pubkey)],
+    ) -> Instruction {
+        let mut accounts = Self::exchange_account_metas(
+            program_id,
+            taker,
+            takers_sending_token_account,
+            AccountMeta::new(*takers_token_to_receive_account, false),
+            escrow_account,
+            escrow,
+        );
+        for (vault, (takers_account, mint)) in escrow.basket_vaults().iter().zip(basket_legs) {
+            accounts.extend([
+                AccountMeta::new(*vault, false),
+                AccountMeta::new(*takers_account, false),
+                AccountMeta::new_readonly(*mint, false),
+            ]);
+        }
+        Instruction {
+            program_id: *program_id,
+            accounts,
+            data: Self::ExchangeBasket {
+                amount: escrow.offered_amount,
+                basket_len: escrow.basket_vaults().len() as u8,
+                max_payment: Some(escrow.expected_amount),
+            }
+            .pack(),
+        }
+    }
+
+    /// An ExchangeIntoNewAccount instruction taking the whole of `escrow` into
+    /// `new_token_account`, a fresh keypair that signs alongside the taker, capped at its
+    /// expected amount as Exchange is
+    #[cfg(feature = "create-receive-account")]
+    pub fn exchange_into_new_account(
+        program_id: &Pubkey,
+        taker: &Pubkey,
+        takers_sending_token_account: &Pubkey,
+        new_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+        escrow: &Escrow,
+    ) -> Instruction {
+        let mut accounts = Self::exchange_account_metas(
+            program_id,
+            taker,
+            takers_sending_token_account,
+            AccountMeta::new(*new_token_account, true),
+            escrow_account,
+            escrow,
+        );
+        accounts.extend([
+            AccountMeta::new_readonly(solana_program::system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ]);
+        Instruction {
+            program_id: *program_id,
+            accounts,
+            data: Self::ExchangeIntoNewAccount {
+                amount: escrow.offered_amount,
+                max_payment: Some(escrow.expected_amount),
+            }
+            .pack(),
+        }
+    }
+
+    /// The fourteen accounts every way of taking `escrow` starts with, in Exchange's order
+    fn exchange_account_metas(
+        program_id: &Pubkey,
+        taker: &Pubkey,
+        takers_sending_token_account: &Pubkey,
+        takers_token_to_receive_account: AccountMeta,
+        escrow_account: &Pubkey,
+        escrow: &Escrow,
+    ) -> Vec<AccountMeta> {
+        let (pda, _bump) = vault_authority(program_id, escrow_account);
+        vec![
+            AccountMeta::new_readonly(*taker, true),
+            AccountMeta::new(*takers_sending_token_account, false),
+            takers_token_to_receive_account,
+            AccountMeta::new(escrow.temp_token_account_pubkey, false),
+            AccountMeta::new(escrow.initializer_pubkey, false),
+            AccountMeta::new(escrow.initializer_token_to_receive_account_pubkey, false),
+            AccountMeta::new(*escrow_account, false),
+            AccountMeta::new_readonly(escrow.token_program, false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(escrow.fee_account, false),
+            AccountMeta::new_readonly(escrow.expected_mint, false),
+            AccountMeta::new_readonly(escrow.deposited_mint, false),
+            AccountMeta::new_readonly(config_address(program_id).0, false),
+        ]
+    }
+
+    /// The number of accounts the instruction expects, in the order listed on each variant
+    pub fn accounts_len(&self) -> usize {
+        match self {
+            Self::InitEscrow { .. }
+            | Self::InitEscrowAtPrice { .. }
+            | Self::InitEscrowWithFee { .. }
+            | Self::InitEscrowPrivate { .. }
+            | Self::InitDutchAuction { .. } => 7,
+            Self::InitEscrowMulti { sources, .. } => 8 + *sources as usize,
+            Self::InitBasketEscrow { basket_len, .. } => 7 + *basket_len as usize,
+            Self::ExchangeBasket { basket_len, .. } => 14 + 3 * *basket_len as usize,
+            Self::Exchange {
+                create_missing_accounts: true,
+                ..
+            } => 16,
+            Self::Exchange { .. }
+            | Self::CosignedExchange { .. }
+            | Self::PartialExchange { .. }
+            | Self::ExchangeDutchAuction { .. } => 14,
+            Self::MigrateEscrow => 8,
+            Self::ReclaimExpired => 8,
+            Self::RefundTaker { .. } => 9,
+            Self::InitEscrowNative { .. } => 5,
+            Self::TransferInitializer {
+                new_token_to_receive_account: Some(_),
+                ..
+            } => 3,
+            Self::UpdateExpectedAmount { .. } | Self::TransferInitializer { .. } => 2,
+            Self::DepositMore { .. } => 6,
+            Self::SetReceiveAccount => 3,
+            Self::Withdraw { .. } => 7,
+            Self::InitConfig { .. } => 5,
+            Self::SetPaused { .. } => 2,
+            #[cfg(feature = "create-receive-account")]
+            Self::ExchangeIntoNewAccount { .. } => 16,
+            #[cfg(feature = "create-receive-account")]
+            Self::SetReceiveAta => 7,
+            #[cfg(feature = "arbitration")]
+            Self::InitEscrowWithArbiter { .. } => 7,
+            #[cfg(feature = "arbitration")]
+            Self::Arbitrate { decision } => match decision {
+                ArbiterDecision::Release => 8,
+                ArbiterDecision::Refund => 6,
+            },
+        }
+    }
+
+    /// Client-side check that `account_keys` fit the instruction before anyone signs.
+    /// Only roles that can be told from the key alone are checked: the account count,
+    /// the sysvar and token program slots, and that no token account is both a source
+    /// and a destination.
+    pub fn preflight(&self, account_keys: &[Pubkey]) -> Result<(), EscrowError> {
+        if account_keys.len() != self.accounts_len() {
+            return Err(WrongAccountCount);
+        }
+        if let Self::InitBasketEscrow { basket_len, .. } | Self::ExchangeBasket { basket_len, .. } =
+            self
+        {
+            if *basket_len as usize > MAX_BASKET_LEN {
+                return Err(EscrowError::TooManyAccounts);
+            }
+        }
+        match self {
+            Self::InitEscrow { .. }
+            | Self::InitEscrowMulti { .. }
+            | Self::InitEscrowAtPrice { .. }
+            | Self::InitEscrowWithFee { .. }
+            | Self::InitEscrowPrivate { .. }
+            | Self::InitBasketEscrow { .. }
+            | Self::InitDutchAuction { .. } => {
+                if account_keys[4] != sysvar::rent::id() || !token::is_token_program(&account_keys[5]) {
+                    return Err(UnexpectedAccount);
+                }
+                // every source is drained into the temp account and every basket vault sits
+                // beside it, so none may repeat it or another; the sources follow their mint
+                let extra_accounts = match self {
+                    Self::InitEscrowMulti { .. } => &account_keys[8..],
+                    _ => &account_keys[7..],
+                };
+                let source_accounts = [&account_keys[1..2], extra_accounts].concat();
+                for (i, key) in source_accounts.iter().enumerate() {
+                    if source_accounts[i + 1..].contains(key) {
+                        return Err(DuplicateAccount);
+                    }
+                }
+            }
+            // only unpacked with the feature, which brings the associated-token program along
+            #[cfg(not(feature = "create-receive-account"))]
+            Self::Exchange {
+                create_missing_accounts: true,
+                ..
+            } => return Err(EscrowError::InvalidInstruction),
+            #[cfg(feature = "create-receive-account")]
+            Self::Exchange {
+                create_missing_accounts: true,
+                ..
+            } => {
+                Self::preflight_exchange(&account_keys[..14])?;
+                if account_keys[14] != token::associated_token::id()
+                    || account_keys[15] != solana_program::system_program::id()
+                {
+                    return Err(UnexpectedAccount);
+                }
+            }
+            Self::Exchange { .. }
+            | Self::CosignedExchange { .. }
+            | Self::PartialExchange { .. }
+            | Self::ExchangeDutchAuction { .. } => {
+                Self::preflight_exchange(account_keys)?;
+            }
+            Self::ExchangeBasket { .. } => {
+                Self::preflight_exchange(&account_keys[..14])?;
+                // each vault is emptied into its own receiving account, its mint named after both
+                let mut transfer_accounts =
+                    vec![account_keys[1], account_keys[2], account_keys[3], account_keys[5]];
+                for leg in account_keys[14..].chunks_exact(3) {
+                    transfer_accounts.extend_from_slice(&leg[..2]);
+                }
+                for (i, key) in transfer_accounts.iter().enumerate() {
+                    if transfer_accounts[i + 1..].contains(key) {
+                        return Err(DuplicateAccount);
+                    }
+                }
+            }
+            Self::MigrateEscrow => {
+                if !token::is_token_program(&account_keys[4]) || account_keys[6] != sysvar::rent::id() {
+                    return Err(UnexpectedAccount);
+                }
+                if account_keys[1] == account_keys[2] {
+                    return Err(DuplicateAccount);
+                }
+            }
+            Self::InitEscrowNative { .. } => {
+                if account_keys[3] != sysvar::rent::id() {
+                    return Err(UnexpectedAccount);
+                }
+            }
+            Self::TransferInitializer {
+                new_token_to_receive_account: Some(new_token_to_receive_account),
+                ..
+            } => {
+                if account_keys[2] != *new_token_to_receive_account {
+                    return Err(UnexpectedAccount);
+                }
+            }
+            Self::UpdateExpectedAmount { .. }
+            | Self::SetPaused { .. }
+            | Self::TransferInitializer { .. } => {}
+            Self::SetReceiveAccount => {
+                if account_keys[1] == account_keys[2] {
+                    return Err(DuplicateAccount);
+                }
+            }
+            #[cfg(feature = "create-receive-account")]
+            Self::SetReceiveAta => {
+                if !token::is_token_program(&account_keys[4])
+                    || account_keys[5] != token::associated_token::id()
+                    || account_keys[6] != solana_program::system_program::id()
+                {
+                    return Err(UnexpectedAccount);
+                }
+                if account_keys[1] == account_keys[2] {
+                    return Err(DuplicateAccount);
+                }
+            }
+            Self::DepositMore { .. } | Self::RefundTaker { .. } | Self::Withdraw { .. } => {
+                if !token::is_token_program(&account_keys[4]) {
+                    return Err(UnexpectedAccount);
+                }
+                if account_keys[1] == account_keys[2] {
+                    return Err(DuplicateAccount);
+                }
+            }
+            Self::InitConfig { .. } => {
+                if account_keys[2] != solana_program::system_program::id()
+                    || account_keys[3] != sysvar::rent::id()
+                {
+                    return Err(UnexpectedAccount);
+                }
+            }
+            Self::ReclaimExpired => {
+                if !token::is_token_program(&account_keys[4]) || account_keys[6] != sysvar::clock::id() {
+                    return Err(UnexpectedAccount);
+                }
+                if account_keys[0] == account_keys[1] {
+                    return Err(DuplicateAccount);
+                }
+            }
+            #[cfg(feature = "create-receive-account")]
+            Self::ExchangeIntoNewAccount { .. } => {
+                Self::preflight_exchange(&account_keys[..14])?;
+                if account_keys[14] != solana_program::system_program::id()
+                    || account_keys[15] != sysvar::rent::id()
+                {
+                    return Err(UnexpectedAccount);
+                }
+            }
+            #[cfg(feature = "arbitration")]
+            Self::InitEscrowWithArbiter { .. } => {
+                if account_keys[4] != sysvar::rent::id() || !token::is_token_program(&account_keys[5]) {
+                    return Err(UnexpectedAccount);
+                }
+            }
+            #[cfg(feature = "arbitration")]
+            Self::Arbitrate { decision } => {
+                if !token::is_token_program(&account_keys[4]) {
+                    return Err(UnexpectedAccount);
+                }
+                if *decision == ArbiterDecision::Release && account_keys[6] == account_keys[1] {
+                    return Err(DuplicateAccount);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn preflight_exchange(account_keys: &[Pubkey]) -> Result<(), EscrowError> {
+        if !token::is_token_program(&account_keys[7]) || account_keys[9] != sysvar::clock::id() {
+            return Err(UnexpectedAccount);
+        }
+        // same accounts the program checks for aliasing
+        let transfer_accounts = [
+            account_keys[1],
+            account_keys[2],
+            account_keys[3],
+            account_keys[5],
+        ];
+        for (i, key) in transfer_accounts.iter().enumerate() {
+            if transfer_accounts[i + 1..].contains(key) {
+                return Err(DuplicateAccount);
+            }
+        }
+        Ok(())
+    }
+
+    // reads one field off the front of `input`, fields are little endian as Borsh packs them
+    fn read<T: BorshDeserialize>(input: &mut &[u8]) -> Result<T, ProgramError> {
+        T::deserialize(input).map_err(|_| InvalidInstruction.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{EscrowStatus, ESCROW_VERSION};
+
+    fn exchange_keys() -> Vec<Pubkey> {
+        let mut keys: Vec<Pubkey> = (0..14).map(|_| Pubkey::new_unique()).collect();
+        keys[7] = spl_token::id();
+        keys[9] = sysvar::clock::id();
+        keys
+    }
+
+    #[test]
+    fn preflight_accepts_ordered_accounts() {
+        let init = EscrowInstruction::InitEscrow {
+            amount: 50,
+            offered_amount: 100,
+            side: Side::Sell,
+            expiry_unix_timestamp: 0,
+            start_unix_timestamp: 0,
+            min_fill_amount: 0,
+            rounding: RoundingMode::FloorToMaker,
+        };
+        let mut keys: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+        keys[4] = sysvar::rent::id();
+        keys[5] = spl_token::id();
+
+        assert_eq!(init.preflight(&keys), Ok(()));
+        assert_eq!(
+            EscrowInstruction::Exchange {
+                min_acceptable_amount: 100,
+                max_payment: None,
+                expected_vault_amount: None,
+                create_missing_accounts: false,
+            }
+            .preflight(&exchange_keys()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn preflight_rejects_scrambled_accounts() {
+        let exchange = EscrowInstruction::Exchange {
+            min_acceptable_amount: 100,
+            max_payment: None,
+            expected_vault_amount: None,
+            create_missing_accounts: false,
+        };
+        let keys = exchange_keys();
+
+        assert_eq!(exchange.preflight(&keys[..13]), Err(WrongAccountCount));
+
+        let mut swapped = keys.clone();
+        swapped.swap(7, 8);
+        assert_eq!(exchange.preflight(&swapped), Err(UnexpectedAccount));
+
+        let mut aliased = keys;
+        aliased[1] = aliased[5];
+        assert_eq!(exchange.preflight(&aliased), Err(DuplicateAccount));
+
+        let init = EscrowInstruction::InitEscrow {
+            amount: 50,
+            offered_amount: 100,
+            side: Side::Sell,
+            expiry_unix_timestamp: 0,
+            start_unix_timestamp: 0,
+            min_fill_amount: 0,
+            rounding: RoundingMode::FloorToMaker,
+        };
+        let mut keys: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+        keys[4] = spl_token::id();
+        keys[5] = sysvar::rent::id();
+        assert_eq!(init.preflight(&keys), Err(UnexpectedAccount));
+    }
+
+    #[test]
+    fn migrate_escrow_preflight_wants_a_distinct_new_account() {
+        let mut keys: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        keys[4] = spl_token::id();
+        keys[6] = sysvar::rent::id();
+        let migrate = EscrowInstruction::MigrateEscrow;
+        assert_eq!(migrate.preflight(&keys), Ok(()));
+
+        let mut swapped = keys.clone();
+        swapped.swap(4, 6);
+        assert_eq!(migrate.preflight(&swapped), Err(UnexpectedAccount));
+
+        keys[2] = keys[1];
+        assert_eq!(migrate.preflight(&keys), Err(DuplicateAccount));
+    }
+
+    #[test]
+    fn exchange_cap_and_vault_amount_are_optional() {
+        let mut data = vec![1];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        let optional = |data: &[u8]| match EscrowInstruction::unpack(data).unwrap() {
+            EscrowInstruction::Exchange {
+                max_payment,
+                expected_vault_amount,
+                ..
+            } => (max_payment, expected_vault_amount),
+            _ => panic!("expected Exchange"),
+        };
+        assert_eq!(optional(&data), (None, None));
+
+        data.extend_from_slice(&60u64.to_le_bytes());
+        assert_eq!(optional(&data), (Some(60), None));
+        assert!(EscrowInstruction::unpack(&data[..12]).is_err());
+
+        data.extend_from_slice(&120u64.to_le_bytes());
+        assert_eq!(optional(&data), (Some(60), Some(120)));
+        assert!(EscrowInstruction::unpack(&data[..20]).is_err());
+
+        // the vault amount alone goes behind a cap that caps nothing
+        data[9..17].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(optional(&data), (None, Some(120)));
+    }
+
+    #[cfg(feature = "create-receive-account")]
+    #[test]
+    fn exchange_flag_follows_the_amounts_or_stands_alone() {
+        let mut data = vec![1];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        let amounts = [(None, None), (Some(60u64), None), (Some(60), Some(120))];
+        for (max_payment, vault_amount) in amounts {
+            let mut data = data.clone();
+            for amount in max_payment.iter().chain(&vault_amount) {
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            data.push(1);
+            assert_eq!(
+                EscrowInstruction::unpack(&data),
+                Ok(EscrowInstruction::Exchange {
+                    min_acceptable_amount: 100,
+                    max_payment,
+                    expected_vault_amount: vault_amount,
+                    create_missing_accounts: true,
+                })
+            );
+            // only ever sent set, and nothing after it
+            for bad in [0, 2] {
+                *data.last_mut().unwrap() = bad;
+                assert!(EscrowInstruction::unpack(&data).is_err());
+            }
+            data.extend_from_slice(&[1, 1]);
+            assert!(EscrowInstruction::unpack(&data).is_err());
+        }
+
+        let exchange = EscrowInstruction::Exchange {
+            min_acceptable_amount: 100,
+            max_payment: None,
+            expected_vault_amount: None,
+            create_missing_accounts: true,
+        };
+        let mut keys = exchange_keys();
+        assert_eq!(exchange.preflight(&keys), Err(WrongAccountCount));
+        keys.extend([token::associated_token::id(), solana_program::system_program::id()]);
+        assert_eq!(exchange.preflight(&keys), Ok(()));
+        keys.swap(14, 15);
+        assert_eq!(exchange.preflight(&keys), Err(UnexpectedAccount));
+    }
+
+    #[test]
+    fn unpack_rejects_truncated_data_and_unknown_tags() {
+        let mut data = vec![0];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 50,
+                offered_amount: 100,
+                side: Side::Sell,
+                expiry_unix_timestamp: 0,
+                start_unix_timestamp: 0,
+                min_fill_amount: 0,
+                rounding: RoundingMode::FloorToMaker,
+            })
+        );
+
+        for len in 0..17 {
+            assert_eq!(
+                EscrowInstruction::unpack(&data[..len]),
+                Err(InvalidInstruction.into()),
+                "{} bytes",
+                len
+            );
+        }
+        data[0] = 27;
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(InvalidInstruction.into())
+        );
+        assert_eq!(
+            EscrowInstruction::unpack(&[u8::MAX]),
+            Err(InvalidInstruction.into())
+        );
+    }
+
+    /// A deterministic slice of what the `fuzz/` target checks, so a normal `cargo test`
+    /// catches the crash it would: every tag, with every length of patterned data behind it
+    #[test]
+    fn unpack_round_trips_or_fails_on_any_input() {
+        for tag in 0..=u8::MAX {
+            for len in 0..MAX_INSTRUCTION_DATA_LEN {
+                for fill in [0u8, 1, 0xff] {
+                    let mut data = vec![tag];
+                    data.extend((0..len).map(|i| fill ^ (i as u8).wrapping_mul(31)));
+                    if let Ok(instruction) = EscrowInstruction::unpack(&data) {
+                        assert_eq!(
+                            EscrowInstruction::unpack(&instruction.pack()).as_ref(),
+                            Ok(&instruction),
+                            "{:?}",
+                            data
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bare_tags_parse_only_for_instructions_without_fields() {
+        // the error is the program's own code 0, not the runtime's InvalidInstructionData
+        assert_eq!(EscrowInstruction::unpack(&[]), Err(ProgramError::Custom(0)));
+        for tag in 0..=u8::MAX {
+            let expected = match tag {
+                10 => Ok(EscrowInstruction::MigrateEscrow),
+                14 => Ok(EscrowInstruction::ReclaimExpired),
+                15 => Ok(EscrowInstruction::InitConfig {
+                    unpause_delay: 0,
+                    max_expiry_horizon_secs: DEFAULT_MAX_EXPIRY_HORIZON_SECS,
+                }),
+                24 => Ok(EscrowInstruction::SetReceiveAccount),
+                #[cfg(feature = "create-receive-account")]
+                26 => Ok(EscrowInstruction::SetReceiveAta),
+                _ => Err(InvalidInstruction.into()),
+            };
+            assert_eq!(EscrowInstruction::unpack(&[tag]), expected, "tag {}", tag);
+        }
+
+        // an amount one byte short is as bad as none
+        let mut data = vec![1];
+        data.extend_from_slice(&100u64.to_le_bytes()[..7]);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(InvalidInstruction.into())
+        );
+        data.push(0);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::Exchange {
+                min_acceptable_amount: 100,
+                max_payment: None,
+                expected_vault_amount: None,
+                create_missing_accounts: false,
+            })
+        );
+    }
+
+    #[test]
+    fn init_escrow_side_defaults_to_sell() {
+        let mut data = vec![0];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        let side = |data: &[u8]| match EscrowInstruction::unpack(data) {
+            Ok(EscrowInstruction::InitEscrow { side, .. }) => Ok(side),
+            Ok(_) => panic!("expected InitEscrow"),
+            Err(e) => Err(e),
+        };
+
+        assert_eq!(side(&data), Ok(Side::Sell));
+        data.push(1);
+        assert_eq!(side(&data), Ok(Side::Buy));
+        *data.last_mut().unwrap() = 2;
+        assert_eq!(side(&data), Err(EscrowError::InvalidSide.into()));
+    }
+
+    #[test]
+    fn init_escrow_expiry_follows_side() {
+        let mut data = vec![0];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        let expiry = |data: &[u8]| match EscrowInstruction::unpack(data) {
+            Ok(EscrowInstruction::InitEscrow {
+                expiry_unix_timestamp,
+                ..
+            }) => Ok(expiry_unix_timestamp),
+            Ok(_) => panic!("expected InitEscrow"),
+            Err(e) => Err(e),
+        };
+
+        assert_eq!(expiry(&data), Ok(0));
+        data.push(1);
+        assert_eq!(expiry(&data), Ok(0));
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        assert_eq!(expiry(&data), Ok(1_700_000_000));
+        assert_eq!(expiry(&data[..25]), Err(InvalidInstruction.into()));
+        data.push(0);
+        assert_eq!(expiry(&data), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn init_escrow_start_follows_expiry() {
+        let mut data = vec![0];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&0i64.to_le_bytes());
+        let start = |data: &[u8]| match EscrowInstruction::unpack(data) {
+            Ok(EscrowInstruction::InitEscrow {
+                start_unix_timestamp,
+                ..
+            }) => Ok(start_unix_timestamp),
+            Ok(_) => panic!("expected InitEscrow"),
+            Err(e) => Err(e),
+        };
+
+        assert_eq!(start(&data), Ok(0));
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        assert_eq!(start(&data), Ok(1_700_000_000));
+        assert_eq!(start(&data[..33]), Err(InvalidInstruction.into()));
+        data.push(0);
+        assert_eq!(start(&data), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn init_escrow_min_fill_follows_start() {
+        let mut data = vec![0];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0i64.to_le_bytes());
+        let min_fill = |data: &[u8]| match EscrowInstruction::unpack(data) {
+            Ok(EscrowInstruction::InitEscrow {
+                min_fill_amount, ..
+            }) => Ok(min_fill_amount),
+            Ok(_) => panic!("expected InitEscrow"),
+            Err(e) => Err(e),
+        };
+
+        assert_eq!(min_fill(&data), Ok(0));
+        data.extend_from_slice(&10u64.to_le_bytes());
+        assert_eq!(min_fill(&data), Ok(10));
+        assert_eq!(min_fill(&data[..41]), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn init_escrow_rounding_follows_min_fill() {
+        let mut data = vec![0];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        let rounding = |data: &[u8]| match EscrowInstruction::unpack(data) {
+            Ok(EscrowInstruction::InitEscrow { rounding, .. }) => Ok(rounding),
+            Ok(_) => panic!("expected InitEscrow"),
+            Err(e) => Err(e),
+        };
+
+        assert_eq!(rounding(&data), Ok(RoundingMode::FloorToMaker));
+        data.push(1);
+        assert_eq!(rounding(&data), Ok(RoundingMode::FloorToTaker));
+        *data.last_mut().unwrap() = 2;
+        assert_eq!(rounding(&data), Err(InvalidInstruction.into()));
+        *data.last_mut().unwrap() = 0;
+        data.push(0);
+        assert_eq!(rounding(&data), Err(InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn init_escrow_multi_expects_its_sources() {
+        let mut data = vec![3];
+        data.extend_from_slice(&50u64.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.push(2);
+        let init = EscrowInstruction::unpack(&data).unwrap();
+        assert_eq!(init.accounts_len(), 10);
+
+        let mut keys: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        keys[4] = sysvar::rent::id();
+        keys[5] = spl_token::id();
+        assert_eq!(init.preflight(&keys), Ok(()));
+        assert_eq!(init.preflight(&keys[..9]), Err(WrongAccountCount));
+
+        keys[9] = keys[1];
+        assert_eq!(init.preflight(&keys), Err(DuplicateAccount));
+
+        assert!(EscrowInstruction::unpack(&data[..17]).is_err());
+    }
+
+    #[test]
+    fn basket_instructions_expect_their_vaults() {
+        let exchange = EscrowInstruction::ExchangeBasket {
+            amount: 100,
+            basket_len: 2,
+            max_payment: None,
+        };
+        assert_eq!(exchange.accounts_len(), 20);
+
+        let mut keys = exchange_keys();
+        keys.extend((0..6).map(|_| Pubkey::new_unique()));
+        // both vaults may hold the same mint
+        keys[19] = keys[16];
+        assert_eq!(exchange.preflight(&keys), Ok(()));
+        assert_eq!(exchange.preflight(&keys[..19]), Err(WrongAccountCount));
+
+        // a vault paid into itself, or into the taker's account for the temp token
+        keys[15] = keys[14];
+        assert_eq!(exchange.preflight(&keys), Err(DuplicateAccount));
+        keys[15] = keys[2];
+        assert_eq!(exchange.preflight(&keys), Err(DuplicateAccount));
+
+        let init = EscrowInstruction::InitBasketEscrow {
+            amount: 100,
+            basket_len: MAX_BASKET_LEN as u8 + 1,
+        };
+        let mut keys: Vec<Pubkey> =
+            (0..init.accounts_len()).map(|_| Pubkey::new_unique()).collect();
+        keys[4] = sysvar::rent::id();
+        keys[5] = spl_token::id();
+        assert_eq!(init.preflight(&keys), Err(EscrowError::TooManyAccounts));
+    }
+
+    /// Pins the wire format of every variant; a failure here means clients built
+    /// against an older layout would send instructions this program misreads.
+    #[test]
+    fn packed_layout_is_stable() {
+        let amount = 0x0102_0304_0506_0708u64.to_le_bytes();
+        let other = 0x1112_1314_1516_1718u64.to_le_bytes();
+        let third = 0x2122_2324_2526_2728u64.to_le_bytes();
+        let cases = vec![
+            (
+                EscrowInstruction::InitEscrow {
+                    amount: 0x0102_0304_0506_0708,
+                    offered_amount: 0x1112_1314_1516_1718,
+                    side: Side::Buy,
+                    expiry_unix_timestamp: 0x2122_2324_2526_2728,
+                    start_unix_timestamp: 0x0102_0304_0506_0708,
+                    min_fill_amount: 0x1112_1314_1516_1718,
+                    rounding: RoundingMode::FloorToTaker,
+                },
+                [&[0][..], &amount, &other, &[1], &third, &amount, &other, &[1]].concat(),
+            ),
+            (
+                EscrowInstruction::Exchange {
+                    min_acceptable_amount: 0x0102_0304_0506_0708,
+                    max_payment: None,
+                    expected_vault_amount: None,
+                    create_missing_accounts: false,
+                },
+                [&[1][..], &amount].concat(),
+            ),
+            (
+                EscrowInstruction::Exchange {
+                    min_acceptable_amount: 0x0102_0304_0506_0708,
+                    max_payment: Some(0x1112_1314_1516_1718),
+                    expected_vault_amount: None,
+                    create_missing_accounts: false,
+                },
+                [&[1][..], &amount, &other].concat(),
+            ),
+            (
+                EscrowInstruction::Exchange {
+                    min_acceptable_amount: 0x0102_0304_0506_0708,
+                    max_payment: None,
+                    expected_vault_amount: Some(0x1112_1314_1516_1718),
+                    create_missing_accounts: false,
+                },
+                [&[1][..], &amount, &u64::MAX.to_le_bytes(), &other].concat(),
+            ),
+            (
+                EscrowInstruction::CosignedExchange {
+                    amount: 0x0102_0304_0506_0708,
+                    max_payment: None,
+                },
+                [&[2][..], &amount].concat(),
+            ),
+            (
+                EscrowInstruction::CosignedExchange {
+                    amount: 0x0102_0304_0506_0708,
+                    max_payment: Some(0x1112_1314_1516_1718),
+                },
+                [&[2][..], &amount, &other].concat(),
+            ),
+            (
+                EscrowInstruction::InitEscrowMulti {
+                    amount: 0x0102_0304_0506_0708,
+                    offered_amount: 0x1112_1314_1516_1718,
+                    sources: 3,
+                },
+                [&[3][..], &amount, &other, &[3]].concat(),
+            ),
+            (
+                EscrowInstruction::InitEscrowAtPrice {
+                    offered_amount: 0x0102_0304_0506_0708,
+                    numerator: 0x1112_1314_1516_1718,
+                    denominator: 0x2122_2324_2526_2728,
+                },
+                [&[7][..], &amount, &other, &third].concat(),
+            ),
+            (
+                EscrowInstruction::PartialExchange {
+                    amount: 0x0102_0304_0506_0708,
+                    fill_amount: 0x1112_1314_1516_1718,
+                    max_payment: None,
+                },
+                [&[8][..], &amount, &other].concat(),
+            ),
+            (
+                EscrowInstruction::PartialExchange {
+                    amount: 0x0102_0304_0506_0708,
+                    fill_amount: 0x1112_1314_1516_1718,
+                    max_payment: Some(0x2122_2324_2526_2728),
+                },
+                [&[8][..], &amount, &other, &third].concat(),
+            ),
+            (
+                EscrowInstruction::InitEscrowWithFee {
+                    amount: 0x0102_0304_0506_0708,
+                    offered_amount: 0x1112_1314_1516_1718,
+                    fee_basis_points: 0x3132,
+                    fee_account: Pubkey::new_from_array([5; 32]),
+                    rounding: RoundingMode::FloorToTaker,
+                },
+                [&[9][..], &amount, &other, &[0x32, 0x31], &[5; 32], &[1]].concat(),
+            ),
+            (EscrowInstruction::MigrateEscrow, vec![10]),
+            (
+                EscrowInstruction::InitEscrowNative {
+                    amount: 0x0102_0304_0506_0708,
+                    offered_amount: 0x1112_1314_1516_1718,
+                },
+                [&[11][..], &amount, &other].concat(),
+            ),
+            (
+                EscrowInstruction::UpdateExpectedAmount {
+                    new_amount: 0x0102_0304_0506_0708,
+                },
+                [&[12][..], &amount].concat(),
+            ),
+            (
+                EscrowInstruction::InitEscrowPrivate {
+                    amount: 0x0102_0304_0506_0708,
+                    offered_amount: 0x1112_1314_1516_1718,
+                    allowed_taker: Pubkey::new_from_array([6; 32]),
+                },
+                [&[13][..], &amount, &other, &[6; 32]].concat(),
+            ),
+            (EscrowInstruction::ReclaimExpired, vec![14]),
+            (
+                EscrowInstruction::InitConfig {
+                    unpause_delay: 0,
+                    max_expiry_horizon_secs: DEFAULT_MAX_EXPIRY_HORIZON_SECS,
+                },
+                vec![15],
+            ),
+            (
+                EscrowInstruction::InitConfig {
+                    unpause_delay: 3_600,
+                    max_expiry_horizon_secs: DEFAULT_MAX_EXPIRY_HORIZON_SECS,
+                },
+                [&[15][..], &3_600i64.to_le_bytes()].concat(),
+            ),
+            (
+                EscrowInstruction::InitConfig {
+                    unpause_delay: 0,
+                    max_expiry_horizon_secs: 86_400,
+                },
+                [&[15][..], &0i64.to_le_bytes(), &86_400i64.to_le_bytes()].concat(),
+            ),
+            (EscrowInstruction::SetPaused { paused: true }, vec![16, 1]),
+            (
+                EscrowInstruction::InitBasketEscrow {
+                    amount: 0x0102_0304_0506_0708,
+                    basket_len: 2,
+                },
+                [&[17][..], &amount, &[2]].concat(),
+            ),
+            (
+                EscrowInstruction::ExchangeBasket {
+                    amount: 0x0102_0304_0506_0708,
+                    basket_len: 2,
+                    max_payment: None,
+                },
+                [&[18][..], &amount, &[2]].concat(),
+            ),
+            (
+                EscrowInstruction::ExchangeBasket {
+                    amount: 0x0102_0304_0506_0708,
+                    basket_len: 2,
+                    max_payment: Some(0x1112_1314_1516_1718),
+                },
+                [&[18][..], &amount, &[2], &other].concat(),
+            ),
+            (
+                EscrowInstruction::DepositMore {
+                    additional: 0x0102_0304_0506_0708,
+                    expected_amount: None,
+                },
+                [&[19][..], &amount].concat(),
+            ),
+            (
+                EscrowInstruction::DepositMore {
+                    additional: 0x0102_0304_0506_0708,
+                    expected_amount: Some(0x1112_1314_1516_1718),
+                },
+                [&[19][..], &amount, &other].concat(),
+            ),
+            (
+                EscrowInstruction::InitDutchAuction {
+                    offered_amount: 0x0102_0304_0506_0708,
+                    start_amount: 0x1112_1314_1516_1718,
+                    end_amount: 0x2122_2324_2526_2728,
+                    start_unix_timestamp: 0x1112_1314_1516_1718,
+                    end_unix_timestamp: 0x2122_2324_2526_2728,
+                },
+                [&[20][..], &amount, &other, &third, &other, &third].concat(),
+            ),
+            (
+                EscrowInstruction::ExchangeDutchAuction {
+                    amount: 0x0102_0304_0506_0708,
+                    quoted_payment: 0x1112_1314_1516_1718,
+                    tolerance: 0x2122_2324_2526_2728,
+                },
+                [&[21][..], &amount, &other, &third].concat(),
+            ),
+            (
+                EscrowInstruction::TransferInitializer {
+                    new_initializer: Pubkey::new_from_array([7; 32]),
+                    new_token_to_receive_account: None,
+                },
+                [&[22][..], &[7; 32]].concat(),
+            ),
+            (
+                EscrowInstruction::TransferInitializer {
+                    new_initializer: Pubkey::new_from_array([7; 32]),
+                    new_token_to_receive_account: Some(Pubkey::new_from_array([8; 32])),
+                },
+                [&[22][..], &[7; 32], &[8; 32]].concat(),
+            ),
+            (
+                EscrowInstruction::RefundTaker {
+                    amount: 0x0102_0304_0506_0708,
+                },
+                [&[23][..], &amount].concat(),
+            ),
+            (EscrowInstruction::SetReceiveAccount, vec![24]),
+            (
+                EscrowInstruction::Withdraw {
+                    amount: 0x0102_0304_0506_0708,
+                },
+                [&[25][..], &amount].concat(),
+            ),
+        ];
+        #[cfg(feature = "arbitration")]
+        let cases: Vec<_> = cases.into_iter().chain(vec![
+            (
+                EscrowInstruction::InitEscrowWithArbiter {
+                    amount: 0x0102_0304_0506_0708,
+                    offered_amount: 0x1112_1314_1516_1718,
+                    arbiter: Pubkey::new_from_array([4; 32]),
+                },
+                [&[4][..], &amount, &other, &[4; 32]].concat(),
+            ),
+            (
+                EscrowInstruction::Arbitrate {
+                    decision: ArbiterDecision::Release,
+                },
+                vec![5, 0],
+            ),
+            (
+                EscrowInstruction::Arbitrate {
+                    decision: ArbiterDecision::Refund,
+                },
+                vec![5, 1],
+            ),
+        ]).collect();
+        #[cfg(feature = "create-receive-account")]
+        let cases: Vec<_> = cases.into_iter().chain(vec![
+            (
+                EscrowInstruction::Exchange {
+                    min_acceptable_amount: 0x0102_0304_0506_0708,
+                    max_payment: None,
+                    expected_vault_amount: None,
+                    create_missing_accounts: true,
+                },
+                [&[1][..], &amount, &[1]].concat(),
+            ),
+            (
+                EscrowInstruction::Exchange {
+                    min_acceptable_amount: 0x0102_0304_0506_0708,
+                    max_payment: Some(0x1112_1314_1516_1718),
+                    expected_vault_amount: Some(0x2122_2324_2526_2728),
+                    create_missing_accounts: true,
+                },
+                [&[1][..], &amount, &other, &third, &[1]].concat(),
+            ),
+            (
+                EscrowInstruction::ExchangeIntoNewAccount {
+                    amount: 0x0102_0304_0506_0708,
+                    max_payment: None,
+                },
+                [&[6][..], &amount].concat(),
+            ),
+            (
+                EscrowInstruction::ExchangeIntoNewAccount {
+                    amount: 0x0102_0304_0506_0708,
+                    max_payment: Some(0x1112_1314_1516_1718),
+                },
+                [&[6][..], &amount, &other].concat(),
+            ),
+            (EscrowInstruction::SetReceiveAta, vec![26]),
+        ]).collect();
+
+        for (instruction, bytes) in cases {
+            assert_eq!(instruction.pack(), bytes, "{:?}", instruction);
+            assert_eq!(EscrowInstruction::unpack(&bytes).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn constructors_round_trip_through_unpack() {
+        let program_id = Pubkey::new_unique();
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let ix = EscrowInstruction::init_escrow(
+            &program_id, &keys[0], &keys[1], &keys[2], &keys[3], 50, 100,
+        );
+        let init = EscrowInstruction::unpack(&ix.data).unwrap();
+        assert_eq!(
+            init,
+            EscrowInstruction::InitEscrow {
+                amount: 50,
+                offered_amount: 100,
+                side: Side::Sell,
+                expiry_unix_timestamp: 0,
+                start_unix_timestamp: 0,
+                min_fill_amount: 0,
+                rounding: RoundingMode::FloorToMaker,
+            }
+        );
+        let account_keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(init.preflight(&account_keys), Ok(()));
+        assert!(ix.accounts[0].is_signer);
+
+        let escrow = Escrow {
+            version: ESCROW_VERSION,
+            is_initialized: true,
+            initializer_pubkey: keys[0],
+            temp_token_account_pubkey: keys[1],
+            initializer_token_to_receive_account_pubkey: keys[2],
+            expected_amount: 50,
+            offered_amount: 100,
+            arbiter: None,
+            price: None,
+            side: Side::Sell,
+            bump: 255,
+            expiry_unix_timestamp: 0,
+            expected_mint: Pubkey::new_unique(),
+            deposited_mint: Pubkey::new_unique(),
+            fee_basis_points: 0,
+            fee_account: keys[2],
+            deposited_is_native: false,
+            token_program: token::token_2022::id(),
+            allowed_taker: Pubkey::default(),
+            status: EscrowStatus::Active,
+            basket_len: 0,
+            basket: [Pubkey::default(); MAX_BASKET_LEN],
+            start_unix_timestamp: 0,
+            auction: None,
+            min_fill_amount: 0,
+            rounding: RoundingMode::FloorToMaker,
+        };
+        let taker_keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let ix = EscrowInstruction::exchange(
+            &program_id,
+            &taker_keys[0],
+            &taker_keys[1],
+            &taker_keys[2],
+            &keys[3],
+            &escrow,
+        );
+        let exchange = EscrowInstruction::unpack(&ix.data).unwrap();
+        assert_eq!(
+            exchange,
+            EscrowInstruction::Exchange {
+                min_acceptable_amount: 100,
+                max_payment: Some(50),
+                expected_vault_amount: None,
+                create_missing_accounts: false,
+            }
+        );
+        let account_keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(exchange.preflight(&account_keys), Ok(()));
+        assert_eq!(account_keys[6], keys[3]);
+        assert_eq!(account_keys[7], token::token_2022::id());
+        assert_eq!(account_keys[8], vault_authority(&program_id, &keys[3]).0);
+        assert_eq!(account_keys[13], config_address(&program_id).0);
+
+        #[cfg(feature = "create-receive-account")]
+        {
+            let ix = EscrowInstruction::exchange_into_new_account(
+                &program_id,
+                &taker_keys[0],
+                &taker_keys[1],
+                &taker_keys[2],
+                &keys[3],
+                &escrow,
+            );
+            let exchange = EscrowInstruction::unpack(&ix.data).unwrap();
+            assert_eq!(
+                exchange,
+                EscrowInstruction::ExchangeIntoNewAccount {
+                    amount: 100,
+                    max_payment: Some(50),
+                }
+            );
+            let account_keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+            assert_eq!(exchange.preflight(&account_keys), Ok(()));
+            assert!(ix.accounts[2].is_signer);
+        }
+
+        let mut escrow = escrow;
+        let vault = Pubkey::new_unique();
+        escrow.basket_len = 1;
+        escrow.basket[0] = vault;
+        let leg = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = EscrowInstruction::exchange_basket(
+            &program_id,
+            &taker_keys[0],
+            &taker_keys[1],
+            &taker_keys[2],
+            &keys[3],
+            &escrow,
+            &[leg],
+        );
+        let exchange = EscrowInstruction::unpack(&ix.data).unwrap();
+        assert_eq!(
+            exchange,
+            EscrowInstruction::ExchangeBasket {
+                amount: 100,
+                basket_len: 1,
+                max_payment: Some(50),
+            }
+        );
+        let account_keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(exchange.preflight(&account_keys), Ok(()));
+        assert_eq!(account_keys[14..], [vault, leg.0, leg.1]);
     }
 }
@@ -1,8 +1,17 @@
+pub mod amount;
+#[cfg(feature = "debug-accounts")]
+pub mod debug_accounts;
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod open;
+pub mod order;
+#[cfg(feature = "program")]
 pub mod processor;
+pub mod settlement;
 pub mod state;
+pub mod token;
 
-#[cfg(not(feature = "no-entrypoint"))]
+#[cfg(all(feature = "program", not(feature = "no-entrypoint")))]
 pub mod entrypoint; // if no entrypoint cargo feature while adding dep, this line is not executed
                     // thanks to line above
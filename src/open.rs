@@ -0,0 +1,124 @@
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, sysvar,
+};
+use spl_token::state::Account as TokenAccount;
+
+use crate::{
+    instruction::EscrowInstruction,
+    state::{config_address, Escrow, RoundingMode, Side},
+    token,
+};
+
+/// Everything a maker needs to open an escrow from nothing but a funded token account.
+/// `temp_token_account` and `escrow_account` are fresh keypairs; both sign the
+/// transaction alongside the initializer, who also pays for the new accounts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenEscrow {
+    pub initializer: Pubkey,
+    /// The initializer's account holding the tokens they offer
+    pub initializer_token_account: Pubkey,
+    /// The mint of the offered token
+    pub mint: Pubkey,
+    /// The decimals of `mint`, which the transfer into the temp token account is checked against
+    pub decimals: u8,
+    pub temp_token_account: Pubkey,
+    pub initializer_token_to_receive_account: Pubkey,
+    pub escrow_account: Pubkey,
+    pub amount: u64,
+    pub offered_amount: u64,
+    pub side: Side,
+    /// Unix time after which the escrow can no longer be taken, `0` for never
+    pub expiry_unix_timestamp: i64,
+    /// Unix time before which the escrow can't be taken yet, `0` for at once
+    pub start_unix_timestamp: i64,
+    /// The least a partial fill may take unless it takes the rest, `0` for any fill
+    pub min_fill_amount: u64,
+    /// Who keeps the remainder when a partial fill's share of the price is rounded
+    pub rounding: RoundingMode,
+    /// The program both mints belong to, spl-token or Token-2022
+    pub token_program: Pubkey,
+}
+
+impl OpenEscrow {
+    /// The instructions to send, in order: create and initialize the temp token account,
+    /// move the offer into it, create the escrow state account, then InitEscrow.
+    /// `rent` sizes the rent-exempt balances of the two new accounts.
+    pub fn instructions(
+        &self,
+        program_id: &Pubkey,
+        rent: &Rent,
+    ) -> Result<Vec<Instruction>, ProgramError> {
+        Ok(vec![
+            system_instruction::create_account(
+                &self.initializer,
+                &self.temp_token_account,
+                rent.minimum_balance(TokenAccount::LEN),
+                TokenAccount::LEN as u64,
+                &self.token_program,
+            ),
+            token::retarget(
+                spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    &self.temp_token_account,
+                    &self.mint,
+                    &self.initializer,
+                )?,
+                &self.token_program,
+            ),
+            token::retarget(
+                spl_token::instruction::transfer_checked(
+                    &spl_token::id(),
+                    &self.initializer_token_account,
+                    &self.mint,
+                    &self.temp_token_account,
+                    &self.initializer,
+                    &[],
+                    self.offered_amount,
+                    self.decimals,
+                )?,
+                &self.token_program,
+            ),
+            system_instruction::create_account(
+                &self.initializer,
+                &self.escrow_account,
+                rent.minimum_balance(Escrow::LEN),
+                Escrow::LEN as u64,
+                program_id,
+            ),
+            self.init_escrow_instruction(program_id),
+        ])
+    }
+
+    /// The InitEscrow instruction alone, see `EscrowInstruction::InitEscrow` for the account order
+    pub fn init_escrow_instruction(&self, program_id: &Pubkey) -> Instruction {
+        let data = EscrowInstruction::InitEscrow {
+            amount: self.amount,
+            offered_amount: self.offered_amount,
+            side: self.side,
+            expiry_unix_timestamp: self.expiry_unix_timestamp,
+            start_unix_timestamp: self.start_unix_timestamp,
+            min_fill_amount: self.min_fill_amount,
+            rounding: self.rounding,
+        }
+        .pack();
+
+        Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.temp_token_account, false),
+                AccountMeta::new_readonly(self.initializer_token_to_receive_account, false),
+                AccountMeta::new(self.escrow_account, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(self.token_program, false),
+                AccountMeta::new_readonly(config_address(program_id).0, false),
+            ],
+            data,
+        }
+    }
+}
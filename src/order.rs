@@ -0,0 +1,300 @@
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar,
+};
+
+use arrayref::{array_ref, array_refs, mut_array_refs};
+
+use crate::{
+    error::EscrowError,
+    instruction::EscrowInstruction,
+    state::{config_address, vault_authority, Escrow},
+};
+
+/// The terms of a live escrow, packed into a base58 string a maker can hand to a
+/// specific taker off-chain. Everything the taker needs to build the Exchange
+/// instruction is in here, except their own accounts.
+///
+/// Packed layout:
+///
+/// 0. `[0..32]` The escrow state account
+/// 1. `[32..64]` The PDA's temp token account holding the offered tokens
+/// 2. `[64..96]` The initializer's main account
+/// 3. `[96..128]` The initializer's token account that will receive tokens
+/// 4. `[128..136]` The amount the taker receives, little endian
+/// 5. `[136..144]` The amount the taker pays, little endian
+/// 6. `[144..176]` The fee account the escrow pays into
+/// 7. `[176..208]` The mint of the token the taker pays
+/// 8. `[208..240]` The mint of the offered token
+/// 9. `[240..272]` The token program the escrow was made with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderTicket {
+    pub escrow_pubkey: Pubkey,
+    pub temp_token_account_pubkey: Pubkey,
+    pub initializer_pubkey: Pubkey,
+    pub initializer_token_to_receive_account_pubkey: Pubkey,
+    pub offered_amount: u64,
+    pub expected_amount: u64,
+    pub fee_account_pubkey: Pubkey,
+    pub expected_mint_pubkey: Pubkey,
+    pub deposited_mint_pubkey: Pubkey,
+    pub token_program_pubkey: Pubkey,
+}
+
+impl OrderTicket {
+    pub const LEN: usize = 272;
+
+    pub fn new(escrow_pubkey: Pubkey, escrow: &Escrow) -> Self {
+        OrderTicket {
+            escrow_pubkey,
+            temp_token_account_pubkey: escrow.temp_token_account_pubkey,
+            initializer_pubkey: escrow.initializer_pubkey,
+            initializer_token_to_receive_account_pubkey: escrow
+                .initializer_token_to_receive_account_pubkey,
+            offered_amount: escrow.offered_amount,
+            expected_amount: escrow.expected_amount,
+            fee_account_pubkey: escrow.fee_account,
+            expected_mint_pubkey: escrow.expected_mint,
+            deposited_mint_pubkey: escrow.deposited_mint,
+            token_program_pubkey: escrow.token_program,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut dst = [0u8; OrderTicket::LEN];
+        let (
+            escrow_pubkey_dst,
+            temp_token_account_pubkey_dst,
+            initializer_pubkey_dst,
+            initializer_token_to_receive_account_pubkey_dst,
+            offered_amount_dst,
+            expected_amount_dst,
+            fee_account_pubkey_dst,
+            expected_mint_pubkey_dst,
+            deposited_mint_pubkey_dst,
+            token_program_pubkey_dst,
+        ) = mut_array_refs![&mut dst, 32, 32, 32, 32, 8, 8, 32, 32, 32, 32];
+
+        escrow_pubkey_dst.copy_from_slice(self.escrow_pubkey.as_ref());
+        temp_token_account_pubkey_dst.copy_from_slice(self.temp_token_account_pubkey.as_ref());
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        initializer_token_to_receive_account_pubkey_dst
+            .copy_from_slice(self.initializer_token_to_receive_account_pubkey.as_ref());
+        *offered_amount_dst = self.offered_amount.to_le_bytes();
+        *expected_amount_dst = self.expected_amount.to_le_bytes();
+        fee_account_pubkey_dst.copy_from_slice(self.fee_account_pubkey.as_ref());
+        expected_mint_pubkey_dst.copy_from_slice(self.expected_mint_pubkey.as_ref());
+        deposited_mint_pubkey_dst.copy_from_slice(self.deposited_mint_pubkey.as_ref());
+        token_program_pubkey_dst.copy_from_slice(self.token_program_pubkey.as_ref());
+
+        bs58::encode(&dst[..]).into_string()
+    }
+
+    pub fn decode(payload: &str) -> Result<Self, ProgramError> {
+        let bytes = bs58::decode(payload)
+            .into_vec()
+            .map_err(|_| ProgramError::InvalidArgument)?;
+        if bytes.len() != OrderTicket::LEN {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let src = array_ref![bytes, 0, OrderTicket::LEN];
+        let (
+            escrow_pubkey,
+            temp_token_account_pubkey,
+            initializer_pubkey,
+            initializer_token_to_receive_account_pubkey,
+            offered_amount,
+            expected_amount,
+            fee_account_pubkey,
+            expected_mint_pubkey,
+            deposited_mint_pubkey,
+            token_program_pubkey,
+        ) = array_refs![src, 32, 32, 32, 32, 8, 8, 32, 32, 32, 32];
+
+        Ok(OrderTicket {
+            escrow_pubkey: Pubkey::new_from_array(*escrow_pubkey),
+            temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
+                *initializer_token_to_receive_account_pubkey,
+            ),
+            offered_amount: u64::from_le_bytes(*offered_amount),
+            expected_amount: u64::from_le_bytes(*expected_amount),
+            fee_account_pubkey: Pubkey::new_from_array(*fee_account_pubkey),
+            expected_mint_pubkey: Pubkey::new_from_array(*expected_mint_pubkey),
+            deposited_mint_pubkey: Pubkey::new_from_array(*deposited_mint_pubkey),
+            token_program_pubkey: Pubkey::new_from_array(*token_program_pubkey),
+        })
+    }
+
+    /// Checks the ticket against the escrow as fetched from chain, so a taker never
+    /// signs for terms the maker only claimed off-chain
+    pub fn verify(&self, escrow: &Escrow) -> Result<(), ProgramError> {
+        if self.temp_token_account_pubkey != escrow.temp_token_account_pubkey
+            || self.initializer_pubkey != escrow.initializer_pubkey
+            || self.initializer_token_to_receive_account_pubkey
+                != escrow.initializer_token_to_receive_account_pubkey
+            || self.fee_account_pubkey != escrow.fee_account
+            || self.expected_mint_pubkey != escrow.expected_mint
+            || self.deposited_mint_pubkey != escrow.deposited_mint
+            || self.token_program_pubkey != escrow.token_program
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if self.expected_amount != escrow.expected_amount
+            || self.offered_amount != escrow.offered_amount
+        {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// The Exchange instruction taking this order, see `EscrowInstruction::Exchange` for the account order
+    pub fn exchange_instruction(
+        &self,
+        program_id: &Pubkey,
+        taker: &Pubkey,
+        takers_sending_token_account: &Pubkey,
+        takers_token_to_receive_account: &Pubkey,
+    ) -> Instruction {
+        let (pda, _nonce) = vault_authority(program_id, &self.escrow_pubkey);
+        let data = EscrowInstruction::Exchange {
+            min_acceptable_amount: self.offered_amount,
+            max_payment: Some(self.expected_amount),
+            expected_vault_amount: None,
+            create_missing_accounts: false,
+        }
+        .pack();
+
+        Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(*taker, true),
+                AccountMeta::new(*takers_sending_token_account, false),
+                AccountMeta::new(*takers_token_to_receive_account, false),
+                AccountMeta::new(self.temp_token_account_pubkey, false),
+                AccountMeta::new(self.initializer_pubkey, false),
+                AccountMeta::new(self.initializer_token_to_receive_account_pubkey, false),
+                AccountMeta::new(self.escrow_pubkey, false),
+                AccountMeta::new_readonly(self.token_program_pubkey, false),
+                AccountMeta::new_readonly(pda, false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+                AccountMeta::new(self.fee_account_pubkey, false),
+                AccountMeta::new_readonly(self.expected_mint_pubkey, false),
+                AccountMeta::new_readonly(self.deposited_mint_pubkey, false),
+                AccountMeta::new_readonly(config_address(program_id).0, false),
+            ],
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{EscrowStatus, RoundingMode, Side, ESCROW_VERSION, MAX_BASKET_LEN};
+
+    fn live_escrow() -> Escrow {
+        Escrow {
+            version: ESCROW_VERSION,
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new_unique(),
+            temp_token_account_pubkey: Pubkey::new_unique(),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+            expected_amount: 50,
+            offered_amount: 100,
+            arbiter: None,
+            price: None,
+            side: Side::Sell,
+            bump: 255,
+            expiry_unix_timestamp: 0,
+            expected_mint: Pubkey::new_unique(),
+            deposited_mint: Pubkey::new_unique(),
+            fee_basis_points: 0,
+            fee_account: Pubkey::new_unique(),
+            deposited_is_native: false,
+            token_program: spl_token::id(),
+            allowed_taker: Pubkey::default(),
+            status: EscrowStatus::Active,
+            basket_len: 0,
+            basket: [Pubkey::default(); MAX_BASKET_LEN],
+            start_unix_timestamp: 0,
+            auction: None,
+            min_fill_amount: 0,
+            rounding: RoundingMode::FloorToMaker,
+        }
+    }
+
+    #[test]
+    fn ticket_round_trips_through_base58() {
+        let escrow = live_escrow();
+        let ticket = OrderTicket::new(Pubkey::new_unique(), &escrow);
+
+        let decoded = OrderTicket::decode(&ticket.encode()).unwrap();
+
+        assert_eq!(decoded, ticket);
+        assert_eq!(decoded.verify(&escrow), Ok(()));
+    }
+
+    #[test]
+    fn decode_rejects_malformed_payloads() {
+        let encoded = OrderTicket::new(Pubkey::new_unique(), &live_escrow()).encode();
+
+        assert_eq!(
+            OrderTicket::decode("0OIl"),
+            Err(ProgramError::InvalidArgument)
+        );
+        // dropping a base58 character doesn't always drop a byte, so cut the bytes instead
+        let bytes = bs58::decode(&encoded).into_vec().unwrap();
+        let short = bs58::encode(&bytes[..bytes.len() - 1]).into_string();
+        assert_eq!(
+            OrderTicket::decode(&short),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn verify_rejects_terms_that_differ_from_chain() {
+        let escrow = live_escrow();
+        let ticket = OrderTicket::new(Pubkey::new_unique(), &escrow);
+
+        let mut repriced = escrow;
+        repriced.expected_amount = 60;
+        assert_eq!(
+            ticket.verify(&repriced),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+        let other = live_escrow();
+        assert_eq!(ticket.verify(&other), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn exchange_instruction_carries_the_quoted_amounts() {
+        let ticket = OrderTicket::new(Pubkey::new_unique(), &live_escrow());
+        let ix = ticket.exchange_instruction(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+        );
+
+        assert_eq!(ix.accounts.len(), 14);
+        assert_eq!(ix.accounts[6].pubkey, ticket.escrow_pubkey);
+        match EscrowInstruction::unpack(&ix.data).unwrap() {
+            EscrowInstruction::Exchange {
+                min_acceptable_amount,
+                max_payment,
+                expected_vault_amount,
+                create_missing_accounts,
+            } => {
+                assert_eq!(min_acceptable_amount, 100);
+                assert_eq!(max_payment, Some(50));
+                assert_eq!(expected_vault_amount, None);
+                assert!(!create_missing_accounts);
+            }
+            _ => panic!("expected Exchange"),
+        }
+    }
+}
@@ -1,65 +1,484 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable,
     entrypoint::ProgramResult,
     msg,
     program::{invoke, invoke_signed},
-    program_error::ProgramError,
+    program_error::{PrintProgramError, ProgramError},
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };  // default solana imports
 
-use spl_token::state::Account as TokenAccount;  // solana token imports
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};  // solana token imports, Token-2022 shares their layouts
 
-use crate::{error::EscrowError, instruction::EscrowInstruction, state::Escrow};
+#[cfg(feature = "arbitration")]
+use crate::instruction::ArbiterDecision;
+use crate::{
+    error::EscrowError,
+    event::{EscrowEvent, EscrowExchanged, EscrowInitialized},
+    instruction::{EscrowInstruction, MAX_INSTRUCTION_DATA_LEN},
+    state::{
+        config_address, front_run_safe, legacy_vault_authority, program_data_address,
+        upgrade_authority, vault_authority, vault_authority_with_bump, Config, DutchAuction,
+        Escrow, EscrowStatus, Price, RoundingMode, Side, CONFIG_SEED,
+        DEFAULT_MAX_EXPIRY_HORIZON_SECS, ESCROW_VERSION, LEGACY_VAULT_AUTHORITY_SEED,
+        MAX_BASKET_LEN, MAX_FEE_BASIS_POINTS, VAULT_AUTHORITY_SEED,
+    },
+    token,
+};
+
+/// What an escrow is made with besides its accounts. Each init instruction sets what it
+/// offers and leaves the rest at the defaults: a sell with no arbiter, price, expiry, start,
+/// minimum fill, fee or allowed taker, rounding in the maker's favour
+#[derive(Default)]
+struct InitParams {
+    amount: u64,
+    offered_amount: u64,
+    arbiter: Option<Pubkey>,
+    price: Option<Price>,
+    side: Side,
+    expiry_unix_timestamp: i64,
+    start_unix_timestamp: i64,
+    min_fill_amount: u64,
+    fee: Option<(u16, Pubkey)>,
+    allowed_taker: Option<Pubkey>,
+    rounding: RoundingMode,
+}
+
+/// How a taker takes an escrow besides the accounts. Each exchange instruction sets what it
+/// pins down and leaves the rest at the defaults: the whole offer of a single token, with no
+/// payment cap, vault, co-signature or auction quote to check
+#[derive(Default)]
+struct ExchangeParams<'a, 'b> {
+    amount_expected_by_taker: u64,
+    max_payment: Option<u64>,
+    expected_vault_amount: Option<u64>,
+    fill_amount: Option<u64>,
+    initializer_must_sign: bool,
+    basket_accounts: &'b [AccountInfo<'a>],
+    auction_quote: Option<(u64, u64)>,
+}
+
+/// Where a taker's payment comes from and goes to
+struct PaymentAccounts<'a, 'b> {
+    taker: &'b AccountInfo<'a>,
+    takers_sending_token_account: &'b AccountInfo<'a>,
+    initializers_token_to_receive_account: &'b AccountInfo<'a>,
+    fee_account: &'b AccountInfo<'a>,
+}
 
 // look at instruction.rs first
 // two types of instructions -> InitEscrow, and Exchange
 // InitEscrow has the requested accounts listed, and those are passed as accounts
 pub struct Processor;
 impl Processor {
+    /// Runs one instruction, logging an escrow error by name before it is returned as a bare code
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult {
+        let result = Self::process_instruction(program_id, accounts, instruction_data);
+        if let Err(error) = &result {
+            error.print::<EscrowError>();
+        }
+        result
+    }
+
+    fn process_instruction(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        if instruction_data.len() > MAX_INSTRUCTION_DATA_LEN { // don't spend compute on oversized payloads
+            return Err(EscrowError::InstructionTooLarge.into());
+        }
+        #[cfg(feature = "debug-accounts")]
+        let instruction_data = crate::debug_accounts::check(program_id, accounts, instruction_data)?;
         let instruction = EscrowInstruction::unpack(instruction_data)?; // either an instruction or failure
 
         match instruction {
-            EscrowInstruction::InitEscrow { amount } => {
+            EscrowInstruction::InitEscrow {
+                amount,
+                offered_amount,
+                side,
+                expiry_unix_timestamp,
+                start_unix_timestamp,
+                min_fill_amount,
+                rounding,
+            } => {
                 msg!("Instruction: InitEscrow");
-                Self::process_init_escrow(accounts, amount, program_id) // amount is unpacked by instruction.rs
+                // amounts are unpacked by instruction.rs
+                Self::process_init_escrow(
+                    accounts,
+                    InitParams {
+                        amount,
+                        offered_amount,
+                        side,
+                        expiry_unix_timestamp,
+                        start_unix_timestamp,
+                        min_fill_amount,
+                        rounding,
+                        ..InitParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::InitEscrowMulti {
+                amount,
+                offered_amount,
+                sources,
+            } => {
+                msg!("Instruction: InitEscrowMulti");
+                Self::process_init_escrow_multi(
+                    accounts,
+                    amount,
+                    offered_amount,
+                    sources,
+                    program_id,
+                )
+            }
+            EscrowInstruction::InitEscrowAtPrice {
+                offered_amount,
+                numerator,
+                denominator,
+            } => {
+                msg!("Instruction: InitEscrowAtPrice");
+                let price = Price {
+                    numerator,
+                    denominator,
+                };
+                // the stored amount is only a quote for the initial offer, Exchange reprices the live vault
+                let amount = price.payment_for(offered_amount)?;
+                Self::process_init_escrow(
+                    accounts,
+                    InitParams {
+                        amount,
+                        offered_amount,
+                        price: Some(price),
+                        ..InitParams::default()
+                    },
+                    program_id,
+                )
             }
-            EscrowInstruction::Exchange { amount } => {
+            EscrowInstruction::Exchange {
+                min_acceptable_amount,
+                max_payment,
+                expected_vault_amount,
+                create_missing_accounts,
+            } => {
                 msg!("Instruction: Exchange");
-                Self::process_exchange(accounts, amount, program_id)
+                // the flag only unpacks with the feature
+                #[cfg(not(feature = "create-receive-account"))]
+                let _ = create_missing_accounts;
+                #[cfg(feature = "create-receive-account")]
+                let accounts = if create_missing_accounts {
+                    Self::create_missing_receive_accounts(accounts)?
+                } else {
+                    accounts
+                };
+                Self::process_exchange(
+                    accounts,
+                    ExchangeParams {
+                        amount_expected_by_taker: min_acceptable_amount,
+                        max_payment,
+                        expected_vault_amount,
+                        ..ExchangeParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::CosignedExchange {
+                amount,
+                max_payment,
+            } => {
+                msg!("Instruction: CosignedExchange");
+                Self::process_exchange(
+                    accounts,
+                    ExchangeParams {
+                        amount_expected_by_taker: amount,
+                        max_payment,
+                        initializer_must_sign: true,
+                        ..ExchangeParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::PartialExchange {
+                amount,
+                fill_amount,
+                max_payment,
+            } => {
+                msg!("Instruction: PartialExchange");
+                Self::process_exchange(
+                    accounts,
+                    ExchangeParams {
+                        amount_expected_by_taker: amount,
+                        max_payment,
+                        fill_amount: Some(fill_amount),
+                        ..ExchangeParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::InitEscrowWithFee {
+                amount,
+                offered_amount,
+                fee_basis_points,
+                fee_account,
+                rounding,
+            } => {
+                msg!("Instruction: InitEscrowWithFee");
+                Self::process_init_escrow(
+                    accounts,
+                    InitParams {
+                        amount,
+                        offered_amount,
+                        fee: Some((fee_basis_points, fee_account)),
+                        rounding,
+                        ..InitParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::MigrateEscrow => {
+                msg!("Instruction: MigrateEscrow");
+                Self::process_migrate_escrow(accounts, program_id)
+            }
+            EscrowInstruction::InitEscrowNative {
+                amount,
+                offered_amount,
+            } => {
+                msg!("Instruction: InitEscrowNative");
+                Self::process_init_escrow_native(accounts, amount, offered_amount, program_id)
+            }
+            EscrowInstruction::UpdateExpectedAmount { new_amount } => {
+                msg!("Instruction: UpdateExpectedAmount");
+                Self::process_update_expected_amount(accounts, new_amount, program_id)
+            }
+            EscrowInstruction::InitEscrowPrivate {
+                amount,
+                offered_amount,
+                allowed_taker,
+            } => {
+                msg!("Instruction: InitEscrowPrivate");
+                Self::process_init_escrow(
+                    accounts,
+                    InitParams {
+                        amount,
+                        offered_amount,
+                        allowed_taker: Some(allowed_taker),
+                        ..InitParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::ReclaimExpired => {
+                msg!("Instruction: ReclaimExpired");
+                Self::process_reclaim_expired(accounts, program_id)
+            }
+            EscrowInstruction::InitConfig {
+                unpause_delay,
+                max_expiry_horizon_secs,
+            } => {
+                msg!("Instruction: InitConfig");
+                Self::process_init_config(
+                    accounts,
+                    unpause_delay,
+                    max_expiry_horizon_secs,
+                    program_id,
+                )
+            }
+            EscrowInstruction::SetPaused { paused } => {
+                msg!("Instruction: SetPaused");
+                Self::process_set_paused(accounts, paused, program_id)
+            }
+            EscrowInstruction::InitBasketEscrow { amount, basket_len } => {
+                msg!("Instruction: InitBasketEscrow");
+                Self::process_init_basket_escrow(accounts, amount, basket_len, program_id)
+            }
+            EscrowInstruction::DepositMore {
+                additional,
+                expected_amount,
+            } => {
+                msg!("Instruction: DepositMore");
+                Self::process_deposit_more(accounts, additional, expected_amount, program_id)
+            }
+            EscrowInstruction::InitDutchAuction {
+                offered_amount,
+                start_amount,
+                end_amount,
+                start_unix_timestamp,
+                end_unix_timestamp,
+            } => {
+                msg!("Instruction: InitDutchAuction");
+                let auction = DutchAuction {
+                    start_amount,
+                    end_amount,
+                    start_unix_timestamp,
+                    end_unix_timestamp,
+                };
+                Self::process_init_dutch_auction(accounts, offered_amount, auction, program_id)
+            }
+            EscrowInstruction::ExchangeDutchAuction {
+                amount,
+                quoted_payment,
+                tolerance,
+            } => {
+                msg!("Instruction: ExchangeDutchAuction");
+                Self::process_exchange(
+                    accounts,
+                    ExchangeParams {
+                        amount_expected_by_taker: amount,
+                        auction_quote: Some((quoted_payment, tolerance)),
+                        ..ExchangeParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::ExchangeBasket {
+                amount,
+                basket_len,
+                max_payment,
+            } => {
+                msg!("Instruction: ExchangeBasket");
+                if accounts.len() != 14 + 3 * basket_len as usize {
+                    return Err(EscrowError::WrongAccountCount.into());
+                }
+                let (accounts, basket_accounts) = accounts.split_at(14);
+                Self::process_exchange(
+                    accounts,
+                    ExchangeParams {
+                        amount_expected_by_taker: amount,
+                        max_payment,
+                        basket_accounts,
+                        ..ExchangeParams::default()
+                    },
+                    program_id,
+                )
+            }
+            EscrowInstruction::TransferInitializer {
+                new_initializer,
+                new_token_to_receive_account,
+            } => {
+                msg!("Instruction: TransferInitializer");
+                Self::process_transfer_initializer(
+                    accounts,
+                    new_initializer,
+                    new_token_to_receive_account,
+                    program_id,
+                )
+            }
+            EscrowInstruction::RefundTaker { amount } => {
+                msg!("Instruction: RefundTaker");
+                Self::process_refund_taker(accounts, amount, program_id)
+            }
+            EscrowInstruction::SetReceiveAccount => {
+                msg!("Instruction: SetReceiveAccount");
+                Self::process_set_receive_account(accounts, program_id)
+            }
+            EscrowInstruction::Withdraw { amount } => {
+                msg!("Instruction: Withdraw");
+                Self::process_withdraw(accounts, amount, program_id)
+            }
+            #[cfg(feature = "create-receive-account")]
+            EscrowInstruction::ExchangeIntoNewAccount {
+                amount,
+                max_payment,
+            } => {
+                msg!("Instruction: ExchangeIntoNewAccount");
+                Self::process_exchange_into_new_account(accounts, amount, max_payment, program_id)
+            }
+            #[cfg(feature = "create-receive-account")]
+            EscrowInstruction::SetReceiveAta => {
+                msg!("Instruction: SetReceiveAta");
+                Self::process_set_receive_ata(accounts, program_id)
+            }
+            #[cfg(feature = "arbitration")]
+            EscrowInstruction::InitEscrowWithArbiter {
+                amount,
+                offered_amount,
+                arbiter,
+            } => {
+                msg!("Instruction: InitEscrowWithArbiter");
+                Self::process_init_escrow(
+                    accounts,
+                    InitParams {
+                        amount,
+                        offered_amount,
+                        arbiter: Some(arbiter),
+                        ..InitParams::default()
+                    },
+                    program_id,
+                )
+            }
+            #[cfg(feature = "arbitration")]
+            EscrowInstruction::Arbitrate { decision } => {
+                msg!("Instruction: Arbitrate");
+                Self::process_arbitrate(accounts, decision, program_id)
             }
         }
     }
 
     fn process_init_escrow(
         accounts: &[AccountInfo],
-        amount: u64,
+        params: InitParams,
         program_id: &Pubkey,
     ) -> ProgramResult {
+        let InitParams {
+            amount,
+            offered_amount,
+            arbiter,
+            price,
+            side,
+            expiry_unix_timestamp,
+            start_unix_timestamp,
+            min_fill_amount,
+            fee,
+            allowed_taker,
+            rounding,
+        } = params;
+        Self::check_not_paused(accounts.get(6), program_id)?;   // nothing new while paused
+        Self::check_expiry_horizon(accounts.get(6), expiry_unix_timestamp, program_id)?;
         let account_info_iter = &mut accounts.iter();   // iterable
         let initializer = next_account_info(account_info_iter)?;    // first account
 
         if !initializer.is_signer { // must be the signer
             return Err(ProgramError::MissingRequiredSignature);
         }
+        if amount == 0 { // a taker could fill it by sending nothing
+            return Err(EscrowError::InvalidAmount.into());
+        }
+        if let Some((fee_basis_points, _)) = fee {
+            if fee_basis_points > MAX_FEE_BASIS_POINTS { // can't skim more than the whole payment
+                return Err(EscrowError::InvalidFeeBps.into());
+            }
+        }
 
         let temp_token_account = next_account_info(account_info_iter)?; // this is the one whose ownership will be transferred
                                                                         // to escrow's pda_account
+        let temp_token_account_info = Self::load_token_account(temp_token_account)?;
+        if temp_token_account_info.amount == 0 { // nothing to trade
+            return Err(EscrowError::InvalidAmount.into());
+        }
+        if temp_token_account_info.amount < offered_amount { // the offer is what's stored, not whatever the account holds
+            return Err(EscrowError::InsufficientDeposit.into());
+        }
+
         let token_to_receive_account = next_account_info(account_info_iter)?;   // alice's Y token account
-        if token_to_receive_account.owner != spl_token::id() { // should be owned by the token program
-                                                                // note that this difference from "token account owner attribute"
-                                                                // who is Alice
+        if !token::is_token_program(token_to_receive_account.owner) { // should be owned by the token program
+                                                                      // note that this difference from "token account owner attribute"
+                                                                      // who is Alice
             return Err(ProgramError::IncorrectProgramId);
         }
+        let expected_mint = token::unpack_account(&token_to_receive_account.data.borrow())?.mint;
 
         let escrow_account = next_account_info(account_info_iter)?; // state account
-        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        // rent comes from the sysvar account rather than `Rent::get()`, which the off-chain
+        // syscall stubs don't implement; a wrong or garbled account here gets its own error
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info).map_err(|_| EscrowError::RentUnavailable)?;
 
         if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {  // state account must be rent exempt -> why ?
             return Err(EscrowError::NotRentExempt.into());
@@ -70,26 +489,53 @@ impl Processor {
             return Err(ProgramError::AccountAlreadyInitialized);
         }
 
+        escrow_info.version = ESCROW_VERSION;
         escrow_info.is_initialized = true;
         escrow_info.initializer_pubkey = *initializer.key;
         escrow_info.temp_token_account_pubkey = *temp_token_account.key;
         escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
         escrow_info.expected_amount = amount;
+        escrow_info.offered_amount = offered_amount;
+        escrow_info.arbiter = arbiter;
+        escrow_info.price = price;
+        escrow_info.side = side;
+        escrow_info.expiry_unix_timestamp = expiry_unix_timestamp;
+        escrow_info.start_unix_timestamp = start_unix_timestamp;
+        escrow_info.min_fill_amount = min_fill_amount;
+        escrow_info.rounding = rounding;
+        escrow_info.expected_mint = expected_mint;
+        escrow_info.deposited_mint = temp_token_account_info.mint;
+        let (fee_basis_points, fee_account) = fee.unwrap_or((0, *token_to_receive_account.key));
+        escrow_info.fee_basis_points = fee_basis_points;
+        escrow_info.fee_account = fee_account;
+        escrow_info.allowed_taker = allowed_taker.unwrap_or_default(); // the zero key lets anyone take it
+        escrow_info.status = EscrowStatus::Active;
 
-        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;  // store it at the address
-        let (pda, _nonce) = Pubkey::find_program_address(&[b"escrow"], program_id); // PDA is owned by this program
+        let (pda, bump) = vault_authority(program_id, escrow_account.key); // PDA is owned by this program, one per escrow
+        escrow_info.bump = bump;
 
         let token_program = next_account_info(account_info_iter)?;  // token program
+        Self::check_token_program(token_program)?;
+        // both sides of the trade go through the one program the escrow stores
+        if temp_token_account.owner != token_program.key
+            || token_to_receive_account.owner != token_program.key
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        escrow_info.token_program = *token_program.key;
         // use instruction to tell token program to change owner of temp_token_account
         // from Alice to pda_account
-        let owner_change_ix = spl_token::instruction::set_authority(    // see spl_token API for params
+        let owner_change_ix = token::retarget(
+            spl_token::instruction::set_authority(    // see spl_token API for params
+                &spl_token::id(),
+                temp_token_account.key,
+                Some(&pda),
+                spl_token::instruction::AuthorityType::AccountOwner,
+                initializer.key,
+                &[initializer.key],
+            )?,
             token_program.key,
-            temp_token_account.key,
-            Some(&pda),
-            spl_token::instruction::AuthorityType::AccountOwner,
-            initializer.key,
-            &[&initializer.key],
-        )?;
+        );
 
         msg!("Calling the token program to transfer token account ownership...");
         invoke(
@@ -101,14 +547,312 @@ impl Processor {
             ],
         )?;
 
-        Ok(())  // Ok() => return an empty Ok => () is an empty tuple
+        // a CPI that returns Ok without moving the authority would leave an escrow nobody can take
+        let temp_token_account_info = Self::load_token_account(temp_token_account)?;
+        if temp_token_account_info.owner != pda {
+            return Err(EscrowError::AuthorityTransferFailed.into());
+        }
+
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;  // only store it at the address once the PDA owns the temp account
+
+        EscrowEvent::Initialized(EscrowInitialized {
+            escrow: *escrow_account.key,
+            initializer: *initializer.key,
+            temp_account: *temp_token_account.key,
+            expected_amount: amount,
+            offered_amount,
+        })
+        .emit()  // Ok() => return an empty Ok => () is an empty tuple
     }
 
-    fn process_exchange(
+    fn process_init_escrow_native(
         accounts: &[AccountInfo],
-        amount_expected_by_taker: u64,
+        amount: u64,
+        offered_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::check_not_paused(accounts.get(4), program_id)?;
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        if !token::is_token_program(token_to_receive_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let expected_mint = token::unpack_account(&token_to_receive_account.data.borrow())?.mint;
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        if escrow_account.owner != program_id { // only an account the program owns can pay the lamports out
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info).map_err(|_| EscrowError::RentUnavailable)?;
+
+        // the offer is whatever sits above the rent-exempt minimum, so the account outlives every fill
+        let rent_exempt_minimum = rent.minimum_balance(escrow_account.data_len());
+        let deposit = escrow_account
+            .lamports()
+            .checked_sub(rent_exempt_minimum)
+            .ok_or(EscrowError::NotRentExempt)?;
+        if deposit < offered_amount {
+            return Err(EscrowError::InsufficientDeposit.into());
+        }
+
+        let mut escrow_info = Escrow::unpack_unchecked(&escrow_account.data.borrow())?;
+        if escrow_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        escrow_info.version = ESCROW_VERSION;
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *escrow_account.key; // the escrow account is its own vault
+        escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
+        escrow_info.expected_amount = amount;
+        escrow_info.offered_amount = offered_amount;
+        escrow_info.expected_mint = expected_mint;
+        escrow_info.deposited_mint = spl_token::native_mint::id();
+        escrow_info.fee_account = *token_to_receive_account.key;
+        escrow_info.bump = vault_authority(program_id, escrow_account.key).1;
+        escrow_info.deposited_is_native = true;
+        escrow_info.token_program = *token_to_receive_account.owner; // only the payment moves tokens
+        escrow_info.status = EscrowStatus::Active;
+
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        EscrowEvent::Initialized(EscrowInitialized {
+            escrow: *escrow_account.key,
+            initializer: *initializer.key,
+            temp_account: *escrow_account.key,
+            expected_amount: amount,
+            offered_amount,
+        })
+        .emit()
+    }
+
+    fn process_init_escrow_multi(
+        accounts: &[AccountInfo],
+        amount: u64,
+        offered_amount: u64,
+        sources: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if accounts.len() != 8 + sources as usize {
+            return Err(EscrowError::WrongAccountCount.into());
+        }
+        let (init_accounts, extra_accounts) = accounts.split_at(7);
+        let (mint_account, source_accounts) = extra_accounts.split_at(1);
+        let mint_account = &mint_account[0];
+        let initializer = &init_accounts[0];
+        let temp_token_account = &init_accounts[1];
+        let token_program = &init_accounts[5];
+        Self::check_token_program(token_program)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // a source listed twice would be counted twice but could only be drained once
+        let temp_token_account_info = Self::load_token_account(temp_token_account)?;
+        let mint = Self::load_mint(mint_account, &temp_token_account_info.mint)?;
+        let mut total = temp_token_account_info.amount;
+        for (i, source) in source_accounts.iter().enumerate() {
+            if source.key == temp_token_account.key
+                || source_accounts[i + 1..].iter().any(|other| other.key == source.key)
+            {
+                return Err(EscrowError::DuplicateAccount.into());
+            }
+            total = total
+                .checked_add(Self::load_token_account(source)?.amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+        }
+        if total != offered_amount {
+            return Err(EscrowError::SourceTotalMismatch.into());
+        }
+
+        // move everything into the temp account, so Exchange pays the taker out of a single vault
+        for source in source_accounts {
+            let transfer_ix = token::retarget(
+                spl_token::instruction::transfer_checked(
+                    &spl_token::id(),
+                    source.key,
+                    mint_account.key,
+                    temp_token_account.key,
+                    initializer.key,
+                    &[initializer.key],
+                    Self::load_token_account(source)?.amount,
+                    mint.decimals,
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to move a source into the temp account...");
+            invoke(
+                &transfer_ix,
+                &[
+                    source.clone(),
+                    mint_account.clone(),
+                    temp_token_account.clone(),
+                    initializer.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
+        Self::process_init_escrow(
+            init_accounts,
+            InitParams {
+                amount,
+                offered_amount,
+                ..InitParams::default()
+            },
+            program_id,
+        )
+    }
+
+    /// InitEscrow for the temp token account, then each basket vault is handed to the same PDA.
+    /// The whole of every account is on offer, the temp token account's balance included
+    fn process_init_basket_escrow(
+        accounts: &[AccountInfo],
+        amount: u64,
+        basket_len: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if basket_len as usize > MAX_BASKET_LEN {
+            return Err(EscrowError::TooManyAccounts.into());
+        }
+        if accounts.len() != 7 + basket_len as usize {
+            return Err(EscrowError::WrongAccountCount.into());
+        }
+        let (init_accounts, basket_accounts) = accounts.split_at(7);
+        let initializer = &init_accounts[0];
+        let temp_token_account = &init_accounts[1];
+        let escrow_account = &init_accounts[3];
+        let token_program = &init_accounts[5];
+
+        let offered_amount = Self::load_token_account(temp_token_account)?.amount;
+        Self::process_init_escrow(
+            init_accounts,
+            InitParams {
+                amount,
+                offered_amount,
+                ..InitParams::default()
+            },
+            program_id,
+        )?;
+
+        let mut escrow_info = Self::load_escrow(escrow_account)?;
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        for (i, vault) in basket_accounts.iter().enumerate() {
+            if vault.key == temp_token_account.key
+                || basket_accounts[i + 1..].iter().any(|other| other.key == vault.key)
+            {
+                return Err(EscrowError::DuplicateAccount.into());
+            }
+            // every vault goes through the one token program the escrow stores
+            if vault.owner != token_program.key {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if Self::load_token_account(vault)?.amount == 0 { // adds nothing to the bundle
+                return Err(EscrowError::InvalidAmount.into());
+            }
+
+            let owner_change_ix = token::retarget(
+                spl_token::instruction::set_authority(
+                    &spl_token::id(),
+                    vault.key,
+                    Some(&pda),
+                    spl_token::instruction::AuthorityType::AccountOwner,
+                    initializer.key,
+                    &[initializer.key],
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to transfer a basket vault's ownership...");
+            invoke(
+                &owner_change_ix,
+                &[vault.clone(), initializer.clone(), token_program.clone()],
+            )?;
+            if Self::load_token_account(vault)?.owner != pda {
+                return Err(EscrowError::AuthorityTransferFailed.into());
+            }
+            escrow_info.basket[i] = *vault.key;
+        }
+        escrow_info.basket_len = basket_len;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    /// InitEscrow quoting the auction's opening price, then the auction is stored beside it
+    fn process_init_dutch_auction(
+        accounts: &[AccountInfo],
+        offered_amount: u64,
+        auction: DutchAuction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !auction.is_valid() { // a price that rises, hits zero, or never moves isn't an auction
+            return Err(EscrowError::InvalidPrice.into());
+        }
+        Self::process_init_escrow(
+            accounts,
+            InitParams {
+                amount: auction.start_amount,
+                offered_amount,
+                ..InitParams::default()
+            },
+            program_id,
+        )?;
+
+        let escrow_account = accounts.get(3).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let mut escrow_info = Self::load_escrow(escrow_account)?;
+        escrow_info.auction = Some(auction);
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    fn process_exchange<'a>(
+        accounts: &[AccountInfo<'a>],
+        params: ExchangeParams<'a, '_>,
         program_id: &Pubkey,
     ) -> ProgramResult {    // if escrow is inited, here's how to take trade
+        let ExchangeParams {
+            amount_expected_by_taker,
+            max_payment,
+            expected_vault_amount,
+            fill_amount,
+            initializer_must_sign,
+            basket_accounts,
+            auction_quote,
+        } = params;
+        Self::check_not_paused(accounts.get(13), program_id)?;
+        // a native escrow has no token vault, its lamports are paid straight out of the state account
+        if let Some(escrow_account) = accounts.get(6) {
+            // checked before its bytes are read at all, the escrow is rewritten or closed below
+            if escrow_account.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if !escrow_account.is_writable {
+                return Err(EscrowError::AccountNotWritable.into());
+            }
+            if Escrow::deposited_is_native_from_bytes(&escrow_account.data.borrow()) == Ok(true) {
+                if !basket_accounts.is_empty() { // lamports come alone, never in a basket
+                    return Err(EscrowError::WrongAccountCount.into());
+                }
+                if auction_quote.is_some() { // nor at auction
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                return Self::process_native_exchange(
+                    accounts,
+                    amount_expected_by_taker,
+                    max_payment,
+                    expected_vault_amount,
+                    fill_amount,
+                    initializer_must_sign,
+                );
+            }
+        }
+
         let account_info_iter = &mut accounts.iter();
         let taker = next_account_info(account_info_iter)?;  // taker / signer
 
@@ -122,32 +866,83 @@ impl Processor {
 
         let pdas_temp_token_account = next_account_info(account_info_iter)?;    // this is the PDA account created for Alice's X tokens
                                                                                 // not sure why it needs to be passed -> should be stored in state no?
-        let pdas_temp_token_account_info =
-            TokenAccount::unpack(&pdas_temp_token_account.data.borrow())?;
-        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
-
-        if amount_expected_by_taker != pdas_temp_token_account_info.amount {    // ensure no front running
-            return Err(EscrowError::ExpectedAmountMismatch.into());
+        let pdas_temp_token_account_info = Self::load_token_account(pdas_temp_token_account)?;
+        if !pdas_temp_token_account.is_writable { // the vault is drained, and closed once empty
+            return Err(EscrowError::AccountNotWritable.into());
         }
 
         let initializers_main_account = next_account_info(account_info_iter)?;  // Alice's account for SOL?
+        if initializer_must_sign && !initializers_main_account.is_signer { // Alice can veto a co-signed trade up to the last moment
+            return Err(ProgramError::MissingRequiredSignature);
+        }
         let initializers_token_to_receive_account = next_account_info(account_info_iter)?;  // Alice's Y token account
         let escrow_account = next_account_info(account_info_iter)?; // state account
+        let token_program = next_account_info(account_info_iter)?;
+        Self::check_token_program(token_program)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let fee_account = next_account_info(account_info_iter)?;
+        let expected_mint_account = next_account_info(account_info_iter)?;  // mint of the token Bob pays in
+        let deposited_mint_account = next_account_info(account_info_iter)?; // mint of the token in the vault
 
-        let escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        let mut escrow_info = Self::load_escrow(escrow_account)?;
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        // rebuilt from the stored bump rather than searched for again
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+        // a stale offer stays in the vault, where only an arbiter can still settle it
+        if escrow_info.is_expired(clock.unix_timestamp) {
+            return Err(EscrowError::EscrowExpired.into());
+        }
+        if !escrow_info.has_started(clock.unix_timestamp) { // scheduled offers aren't live yet
+            return Err(EscrowError::EscrowNotStarted.into());
+        }
+        if !escrow_info.is_allowed_taker(taker.key) { // a private escrow only trades with its counterparty
+            return Err(EscrowError::UnauthorizedTaker.into());
+        }
+        let signer_seeds: &[&[u8]] =
+            &[VAULT_AUTHORITY_SEED, escrow_account.key.as_ref(), &[escrow_info.bump]];
 
         // i don't know why so many checks below are needed -> if Bob passes state address
         // it should be his responsibility to check, not the program's (Ctrl F for "Bob can")
         // maybe front running prevention by Alice re-writing state?
 
-        // weirdly we haven't checked that Bob is indeed sending the token that Alice has expected
-            // mint of the token?
         if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {  // lol why ask in line 123 then
             return Err(ProgramError::InvalidAccountData);
         }
+        if pdas_temp_token_account_info.owner != pda { // a vault the PDA can't sign for belongs to some other escrow
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        // a basket goes to the taker whole or not at all, so every vault has to be named
+        if basket_accounts.len() != 3 * escrow_info.basket_vaults().len() {
+            return Err(EscrowError::WrongAccountCount.into());
+        }
+        if !basket_accounts.is_empty() && fill_amount.is_some() {
+            return Err(EscrowError::InvalidFillAmount.into());
+        }
+        let mut basket = Vec::with_capacity(escrow_info.basket_vaults().len());
+        let legs = basket_accounts.chunks_exact(3);
+        for (vault_key, leg) in escrow_info.basket_vaults().iter().zip(legs) {
+            let (vault, takers_account, mint_account) = (&leg[0], &leg[1], &leg[2]);
+            if vault.key != vault_key {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if !vault.is_writable {
+                return Err(EscrowError::AccountNotWritable.into());
+            }
+            let vault_info = Self::load_token_account(vault)?;
+            if vault_info.owner != pda {
+                return Err(EscrowError::InvalidAccountOwner.into());
+            }
+            Self::assert_token_account_mint(takers_account, &vault_info.mint)?;
+            let mint = Self::load_mint(mint_account, &vault_info.mint)?;
+            basket.push((vault, takers_account, mint_account, mint.decimals, vault_info.amount));
+        }
 
         if escrow_info.initializer_pubkey != *initializers_main_account.key {   // assert Alice trade to finish
-            return Err(ProgramError::InvalidAccountData);
+            return Err(EscrowError::NotInitializer.into());
         }
 
         if escrow_info.initializer_token_to_receive_account_pubkey  // Alice's Y token account passed by Bob must == state
@@ -155,81 +950,1842 @@ impl Processor {
         {
             return Err(ProgramError::InvalidAccountData);
         }
+        // a frozen account would only fail the payment CPI, name it before anything moves
+        if token::is_token_program(initializers_token_to_receive_account.owner)
+            && token::unpack_account_unchecked(&initializers_token_to_receive_account.data.borrow())?
+                .is_frozen()
+        {
+            return Err(EscrowError::AccountFrozen.into());
+        }
 
-        let token_program = next_account_info(account_info_iter)?;
+        // a priced escrow trades whatever the vault holds now, so the taker states that balance
+        // and nothing is left over to hand back
+        if let Some(price) = escrow_info.price {
+            escrow_info.offered_amount = pdas_temp_token_account_info.amount;
+            escrow_info.expected_amount = price.payment_for(escrow_info.offered_amount)?;
+        }
+        // an auction asks whatever its price has fallen to, which the taker could only guess
+        // at when signing, so they quote it with some leeway
+        match (escrow_info.auction, auction_quote) {
+            (Some(auction), Some((quoted_payment, tolerance))) => {
+                let payment = auction.payment_at(clock.unix_timestamp)?;
+                if payment.abs_diff(quoted_payment) > tolerance {
+                    return Err(EscrowError::AuctionPriceMoved.into());
+                }
+                escrow_info.expected_amount = payment;
+            }
+            (None, None) => {}
+            // an auction only goes whole and through its own instruction, which nothing else takes
+            _ => return Err(EscrowError::InvalidInstruction.into()),
+        }
 
-        // transfer from Bob (context) to Alice
-        let transfer_to_initializer_ix = spl_token::instruction::transfer(
-            token_program.key,
+        // only compared once the escrow is known to be live, so a closed escrow's empty vault
+        // reports as uninitialized rather than as a mismatch
+        if !front_run_safe(&escrow_info, amount_expected_by_taker) {    // ensure no front running
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        // the offer alone doesn't pin the vault, a maker can still top it up or drain a surplus
+        if let Some(quoted) = expected_vault_amount {
+            if quoted != pdas_temp_token_account_info.amount {
+                return Err(EscrowError::VaultAmountChanged.into());
+            }
+        }
+        let surplus = pdas_temp_token_account_info  // anything deposited on top of the offer goes back to Alice
+            .amount
+            .checked_sub(escrow_info.offered_amount)
+            .ok_or(EscrowError::InsufficientDeposit)?;
+        let fill = fill_amount.unwrap_or(escrow_info.offered_amount);   // Exchange takes the whole offer
+        let payment = escrow_info.payment_for_fill(fill)?;
+        // the taker saw a price when signing, and the maker may have repriced since
+        if let Some(max_payment) = max_payment {
+            if payment > max_payment {
+                return Err(EscrowError::ExpectedAmountMismatch.into());
+            }
+        }
+        // a part leaves the rest open for the next taker, the whole offer settles the escrow
+        escrow_info.transition(if fill < escrow_info.offered_amount {
+            EscrowStatus::PartiallyFilled
+        } else {
+            EscrowStatus::Completed
+        })?;
+        let fee = escrow_info.fee_for(payment)?;    // comes out of the payment, Alice gets the rest
+        if escrow_info.fee_account != *fee_account.key {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+
+        // no account may be both a source and a destination, or a transfer would be self-referential
+        let mut transfer_accounts = vec![
             takers_sending_token_account.key,
+            takers_token_to_receive_account.key,
+            pdas_temp_token_account.key,
             initializers_token_to_receive_account.key,
-            taker.key,
-            &[&taker.key],
-            escrow_info.expected_amount,
-        )?;
-        msg!("Calling the token program to transfer tokens to the escrow's initializer...");
-        invoke(
-            &transfer_to_initializer_ix,
-            &[
-                takers_sending_token_account.clone(),
-                initializers_token_to_receive_account.clone(),
-                taker.clone(),
-                token_program.clone(),
-            ],
+        ];
+        for leg in basket_accounts.chunks_exact(3) {
+            transfer_accounts.extend([leg[0].key, leg[1].key]);
+        }
+        for (i, key) in transfer_accounts.iter().enumerate() {
+            if transfer_accounts[i + 1..].contains(key) {
+                return Err(EscrowError::DuplicateAccount.into());
+            }
+        }
+
+        // Bob pays in the token Alice asked for and is paid in the one she deposited; the token
+        // program would refuse either transfer anyway, but only with an opaque error
+        Self::assert_token_account_mint(takers_sending_token_account, &escrow_info.expected_mint)?;
+        Self::assert_token_account_mint(
+            takers_token_to_receive_account,
+            &escrow_info.deposited_mint,
         )?;
+        let takers_sending_token_account_info = Self::load_token_account(takers_sending_token_account)?;
+        if takers_sending_token_account_info.amount < payment {
+            return Err(EscrowError::InsufficientTakerFunds.into());
+        }
+        // checked transfers make the token program hold every account to the mint and its decimals too
+        let expected_mint = Self::load_mint(expected_mint_account, &escrow_info.expected_mint)?;
+        let deposited_mint = Self::load_mint(deposited_mint_account, &escrow_info.deposited_mint)?;
 
-        let pda_account = next_account_info(account_info_iter)?;
+        // settled in state before any CPI, so neither a call nested under the transfers nor a
+        // later instruction in the same transaction can take this fill a second time
+        let partial = fill < escrow_info.offered_amount;
+        if partial {
+            // a partial fill leaves the rest of the offer, and its price, for the next taker
+            escrow_info.offered_amount = escrow_info
+                .offered_amount
+                .checked_sub(fill)
+                .ok_or(EscrowError::AmountOverflow)?;
+            escrow_info.expected_amount = escrow_info
+                .expected_amount
+                .checked_sub(payment)
+                .ok_or(EscrowError::AmountOverflow)?;
+            // an older layout can't be rewritten in place
+            if escrow_account.data_len() != Escrow::LEN {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            escrow_info.pack_into_slice(&mut escrow_account.data.borrow_mut());
+        } else {
+            Escrow::mark_uninitialized(&mut escrow_account.data.borrow_mut())?;
+        }
+
+        Self::pay_initializer(
+            token_program,
+            &PaymentAccounts {
+                taker,
+                takers_sending_token_account,
+                initializers_token_to_receive_account,
+                fee_account,
+            },
+            expected_mint_account,
+            expected_mint.decimals,
+            payment,
+            fee,
+        )?;
 
         // transfer Alice's escrowed money to Bob (owned by PDA so it needs to be signed by the program)
-        let transfer_to_taker_ix = spl_token::instruction::transfer(
+        let transfer_to_taker_ix = token::retarget(
+            spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                pdas_temp_token_account.key,
+                deposited_mint_account.key,
+                takers_token_to_receive_account.key,
+                &pda,
+                &[&pda],
+                fill,
+                deposited_mint.decimals,
+            )?,
             token_program.key,
-            pdas_temp_token_account.key,
-            takers_token_to_receive_account.key,
-            &pda,
-            &[&pda],
-            pdas_temp_token_account_info.amount,
-        )?;
+        );
         msg!("Calling the token program to transfer tokens to the taker...");
         invoke_signed(
             &transfer_to_taker_ix,
             &[
                 pdas_temp_token_account.clone(),
+                deposited_mint_account.clone(),
                 takers_token_to_receive_account.clone(),
                 pda_account.clone(),
                 token_program.clone(),
             ],
-            &[&[&b"escrow"[..], &[nonce]]],
+            &[signer_seeds],
         )?;
+        Self::emit_exchanged(escrow_account, taker, &escrow_info, fill, payment)?;
+        if partial {
+            return Ok(());
+        }
 
-        // then close the PDA account, again via invoke_signed
-        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
-            token_program.key,
-            pdas_temp_token_account.key,
-            initializers_main_account.key,
+        for (vault, takers_account, mint_account, decimals, vault_amount) in basket {
+            let transfer_to_taker_ix = token::retarget(
+                spl_token::instruction::transfer_checked(
+                    &spl_token::id(),
+                    vault.key,
+                    mint_account.key,
+                    takers_account.key,
+                    &pda,
+                    &[&pda],
+                    vault_amount,
+                    decimals,
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to transfer a basket vault to the taker...");
+            invoke_signed(
+                &transfer_to_taker_ix,
+                &[
+                    vault.clone(),
+                    mint_account.clone(),
+                    takers_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[signer_seeds],
+            )?;
+            Self::release_vault(
+                token_program,
+                vault,
+                initializers_main_account,
+                pda_account,
+                &pda,
+                signer_seeds,
+                0,
+            )?;
+        }
+
+        Self::release_vault(
+            token_program,
+            pdas_temp_token_account,
+            initializers_main_account,
+            pda_account,
             &pda,
-            &[&pda],
+            signer_seeds,
+            surplus,
         )?;
-        msg!("Calling the token program to close pda's temp account...");
-        invoke_signed(
-            &close_pdas_temp_acc_ix,
-            &[
-                pdas_temp_token_account.clone(),
-                initializers_main_account.clone(),
-                pda_account.clone(),
-                token_program.clone(),
-            ],
-            &[&[&b"escrow"[..], &[nonce]]],
+        Self::close_escrow_account(escrow_account, initializers_main_account)
+    }
+
+    /// Exchange for an escrow made by InitEscrowNative, the offered lamports go to whatever
+    /// account the taker names where the token vault would be
+    fn process_native_exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        max_payment: Option<u64>,
+        expected_vault_amount: Option<u64>,
+        fill_amount: Option<u64>,
+        initializer_must_sign: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let taker = next_account_info(account_info_iter)?;
+
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let takers_sending_token_account = next_account_info(account_info_iter)?;
+        let takers_lamport_account = next_account_info(account_info_iter)?;
+        let vault = next_account_info(account_info_iter)?;
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        if initializer_must_sign && !initializers_main_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        Self::check_token_program(token_program)?;
+        let _pda_account = next_account_info(account_info_iter)?; // nothing for the PDA to sign for
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let fee_account = next_account_info(account_info_iter)?;
+        let expected_mint_account = next_account_info(account_info_iter)?;
+        let native_mint_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::load_escrow(escrow_account)?;
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        if escrow_info.is_expired(clock.unix_timestamp) {
+            return Err(EscrowError::EscrowExpired.into());
+        }
+        if !escrow_info.has_started(clock.unix_timestamp) {
+            return Err(EscrowError::EscrowNotStarted.into());
+        }
+        if !escrow_info.is_allowed_taker(taker.key) { // a private escrow only trades with its counterparty
+            return Err(EscrowError::UnauthorizedTaker.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *vault.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_info.initializer_pubkey != *initializers_main_account.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        if escrow_info.initializer_token_to_receive_account_pubkey
+            != *initializers_token_to_receive_account.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !front_run_safe(&escrow_info, amount_expected_by_taker) {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        // the lamports above rent beyond the offer are the initializer's, so the offer is the vault
+        if let Some(quoted) = expected_vault_amount {
+            if quoted != escrow_info.offered_amount {
+                return Err(EscrowError::VaultAmountChanged.into());
+            }
+        }
+        let fill = fill_amount.unwrap_or(escrow_info.offered_amount);
+        let payment = escrow_info.payment_for_fill(fill)?;
+        if let Some(max_payment) = max_payment {
+            if payment > max_payment {
+                return Err(EscrowError::ExpectedAmountMismatch.into());
+            }
+        }
+        // a part leaves the rest open for the next taker, the whole offer settles the escrow
+        escrow_info.transition(if fill < escrow_info.offered_amount {
+            EscrowStatus::PartiallyFilled
+        } else {
+            EscrowStatus::Completed
+        })?;
+        let fee = escrow_info.fee_for(payment)?;
+        if escrow_info.fee_account != *fee_account.key {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+
+        let transfer_accounts = [
+            takers_sending_token_account.key,
+            takers_lamport_account.key,
+            escrow_account.key,
+            initializers_token_to_receive_account.key,
+        ];
+        for (i, key) in transfer_accounts.iter().enumerate() {
+            if transfer_accounts[i + 1..].contains(key) {
+                return Err(EscrowError::DuplicateAccount.into());
+            }
+        }
+
+        Self::assert_token_account_mint(takers_sending_token_account, &escrow_info.expected_mint)?;
+        let takers_sending_token_account_info = Self::load_token_account(takers_sending_token_account)?;
+        if takers_sending_token_account_info.amount < payment {
+            return Err(EscrowError::InsufficientTakerFunds.into());
+        }
+        // the offer leaves as lamports, so the native mint is only named to keep the accounts in step
+        if *native_mint_account.key != escrow_info.deposited_mint {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        let expected_mint = Self::load_mint(expected_mint_account, &escrow_info.expected_mint)?;
+
+        // settled in state before the payment CPI, as for a token vault
+        let partial = fill < escrow_info.offered_amount;
+        if partial {
+            escrow_info.offered_amount = escrow_info
+                .offered_amount
+                .checked_sub(fill)
+                .ok_or(EscrowError::AmountOverflow)?;
+            escrow_info.expected_amount = escrow_info
+                .expected_amount
+                .checked_sub(payment)
+                .ok_or(EscrowError::AmountOverflow)?;
+            // an older layout can't be rewritten in place
+            if escrow_account.data_len() != Escrow::LEN {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            escrow_info.pack_into_slice(&mut escrow_account.data.borrow_mut());
+        } else {
+            Escrow::mark_uninitialized(&mut escrow_account.data.borrow_mut())?;
+        }
+
+        Self::pay_initializer(
+            token_program,
+            &PaymentAccounts {
+                taker,
+                takers_sending_token_account,
+                initializers_token_to_receive_account,
+                fee_account,
+            },
+            expected_mint_account,
+            expected_mint.decimals,
+            payment,
+            fee,
         )?;
 
-        // close the state account
-        msg!("Closing the escrow account...");
-        **initializers_main_account.lamports.borrow_mut() = initializers_main_account
+        // the program owns the escrow account, so it can debit it without anyone signing
+        msg!("Paying the taker out of the escrow account...");
+        **escrow_account.lamports.borrow_mut() = escrow_account
             .lamports()
-            .checked_add(escrow_account.lamports())
+            .checked_sub(fill)
+            .ok_or(EscrowError::InsufficientDeposit)?;
+        **takers_lamport_account.lamports.borrow_mut() = takers_lamport_account
+            .lamports()
+            .checked_add(fill)
             .ok_or(EscrowError::AmountOverflow)?;
-        **escrow_account.lamports.borrow_mut() = 0;
-        *escrow_account.data.borrow_mut() = &mut [];
+        Self::emit_exchanged(escrow_account, taker, &escrow_info, fill, payment)?;
+        if partial {
+            return Ok(());
+        }
 
-        Ok(())
+        // rent and any lamports left above the offer go back with the account
+        Self::close_escrow_account(escrow_account, initializers_main_account)
+    }
+
+    /// For an Exchange that asks for it, creates the taker's and the initializer's receiving
+    /// accounts as their owners' associated accounts where they don't exist yet, the taker
+    /// paying. Hands back the accounts Exchange itself takes
+    #[cfg(feature = "create-receive-account")]
+    fn create_missing_receive_accounts<'b, 'a>(
+        accounts: &'b [AccountInfo<'a>],
+    ) -> Result<&'b [AccountInfo<'a>], ProgramError> {
+        if accounts.len() != 16 {
+            return Err(EscrowError::WrongAccountCount.into());
+        }
+        let (exchange_accounts, extra_accounts) = accounts.split_at(14);
+        let taker = &exchange_accounts[0];
+        let token_program = &exchange_accounts[7];
+        Self::check_token_program(token_program)?;
+        let associated_token_program = &extra_accounts[0];
+        let system_program = &extra_accounts[1];
+        if *associated_token_program.key != token::associated_token::id()
+            || *system_program.key != solana_program::system_program::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // each receiving account with the wallet it belongs to and the mint it holds;
+        // Exchange checks all of them against the escrow afterwards
+        let receiving = [
+            (&exchange_accounts[2], taker, &exchange_accounts[12]),
+            (&exchange_accounts[5], &exchange_accounts[4], &exchange_accounts[11]),
+        ];
+        for (account, wallet, mint) in receiving {
+            if account.lamports() != 0 { // already there
+                continue;
+            }
+            // only an owner's canonical account can be made for them
+            if *account.key != token::associated_address(wallet.key, mint.key, token_program.key) {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            let create_ix = token::create_associated_account(
+                taker.key,
+                wallet.key,
+                mint.key,
+                token_program.key,
+            );
+            msg!("Calling the associated token program to create a receiving account...");
+            invoke(
+                &create_ix,
+                &[
+                    taker.clone(),
+                    account.clone(),
+                    wallet.clone(),
+                    mint.clone(),
+                    system_program.clone(),
+                    token_program.clone(),
+                    associated_token_program.clone(),
+                ],
+            )?;
+        }
+        Ok(exchange_accounts)
+    }
+
+    #[cfg(feature = "create-receive-account")]
+    fn process_exchange_into_new_account(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        max_payment: Option<u64>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if accounts.len() != 16 {
+            return Err(EscrowError::WrongAccountCount.into());
+        }
+        let (exchange_accounts, extra_accounts) = accounts.split_at(14);
+        let taker = &exchange_accounts[0];
+        let takers_token_to_receive_account = &exchange_accounts[2];
+        let token_program = &exchange_accounts[7];
+        Self::check_token_program(token_program)?;
+        let mint = &exchange_accounts[12]; // the offered token's, which Exchange checks against the escrow
+        let system_program = &extra_accounts[0];
+        let rent_info = &extra_accounts[1];
+
+        if !takers_token_to_receive_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if takers_token_to_receive_account.lamports() != 0 { // something already lives at this address
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let rent = &Rent::from_account_info(rent_info).map_err(|_| EscrowError::RentUnavailable)?;
+
+        let create_ix = solana_program::system_instruction::create_account(
+            taker.key,
+            takers_token_to_receive_account.key,
+            rent.minimum_balance(TokenAccount::LEN),
+            TokenAccount::LEN as u64,
+            token_program.key,
+        );
+        msg!("Calling the system program to create the taker's receiving account...");
+        invoke(
+            &create_ix,
+            &[
+                taker.clone(),
+                takers_token_to_receive_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        if takers_token_to_receive_account.data_len() != TokenAccount::LEN
+            || takers_token_to_receive_account.owner != token_program.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let init_account_ix = token::retarget(
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                takers_token_to_receive_account.key,
+                mint.key,
+                taker.key,
+            )?,
+            token_program.key,
+        );
+        msg!("Calling the token program to initialize the taker's receiving account...");
+        invoke(
+            &init_account_ix,
+            &[
+                takers_token_to_receive_account.clone(),
+                mint.clone(),
+                taker.clone(),
+                rent_info.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        Self::process_exchange(
+            exchange_accounts,
+            ExchangeParams {
+                amount_expected_by_taker,
+                max_payment,
+                ..ExchangeParams::default()
+            },
+            program_id,
+        )
+    }
+
+    #[cfg(feature = "arbitration")]
+    fn process_arbitrate(
+        accounts: &[AccountInfo],
+        decision: ArbiterDecision,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let arbiter = next_account_info(account_info_iter)?;
+
+        if !arbiter.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account_info = Self::load_token_account(pdas_temp_token_account)?;
+
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::load_escrow(escrow_account)?;
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        let signer_seeds: &[&[u8]] =
+            &[VAULT_AUTHORITY_SEED, escrow_account.key.as_ref(), &[escrow_info.bump]];
+
+        // an escrow made without an arbiter can't be force-settled by anyone
+        if escrow_info.arbiter != Some(*arbiter.key) {
+            return Err(EscrowError::NotArbiter.into());
+        }
+        escrow_info.transition(match decision {
+            ArbiterDecision::Release => EscrowStatus::Completed,
+            ArbiterDecision::Refund => EscrowStatus::Cancelled,
+        })?;
+        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if pdas_temp_token_account_info.owner != pda {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        if escrow_info.initializer_pubkey != *initializers_main_account.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+
+        let token_program = next_account_info(account_info_iter)?;
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+
+        let remaining = match decision {
+            ArbiterDecision::Release => {
+                let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+                if takers_token_to_receive_account.key == pdas_temp_token_account.key {
+                    return Err(EscrowError::DuplicateAccount.into());
+                }
+                let surplus = pdas_temp_token_account_info
+                    .amount
+                    .checked_sub(escrow_info.offered_amount)
+                    .ok_or(EscrowError::InsufficientDeposit)?;
+
+                let mint_account = next_account_info(account_info_iter)?;
+                let mint = Self::load_mint(mint_account, &escrow_info.deposited_mint)?;
+
+                let transfer_to_taker_ix = token::retarget(
+                    spl_token::instruction::transfer_checked(
+                        &spl_token::id(),
+                        pdas_temp_token_account.key,
+                        mint_account.key,
+                        takers_token_to_receive_account.key,
+                        &pda,
+                        &[&pda],
+                        escrow_info.offered_amount,
+                        mint.decimals,
+                    )?,
+                    token_program.key,
+                );
+                msg!("Calling the token program to release tokens to the taker...");
+                invoke_signed(
+                    &transfer_to_taker_ix,
+                    &[
+                        pdas_temp_token_account.clone(),
+                        mint_account.clone(),
+                        takers_token_to_receive_account.clone(),
+                        pda_account.clone(),
+                        token_program.clone(),
+                    ],
+                    &[signer_seeds],
+                )?;
+                surplus
+            }
+            ArbiterDecision::Refund => pdas_temp_token_account_info.amount,
+        };
+
+        Self::release_vault(
+            token_program,
+            pdas_temp_token_account,
+            initializers_main_account,
+            pda_account,
+            &pda,
+            signer_seeds,
+            remaining,
+        )?;
+        Self::close_escrow_account(escrow_account, initializers_main_account)
+    }
+
+    fn process_migrate_escrow(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let old_escrow_account = next_account_info(account_info_iter)?;
+        let new_escrow_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        Self::check_token_program(token_program)?;
+        let old_pda_account = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info).map_err(|_| EscrowError::RentUnavailable)?;
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+
+        if old_escrow_account.owner != program_id || new_escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if old_escrow_account.key == new_escrow_account.key {
+            return Err(EscrowError::DuplicateAccount.into());
+        }
+        let mut escrow_info = Self::load_escrow_of_any_version(old_escrow_account)?;
+        // only an account in an older layout has anything to migrate
+        if escrow_info.version == ESCROW_VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key
+            || escrow_info.initializer_token_to_receive_account_pubkey
+                != *token_to_receive_account.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the original program kept every vault under the one PDA, seeded with nothing else
+        let (old_pda, old_bump) = legacy_vault_authority(program_id);
+        if *old_pda_account.key != old_pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+
+        if !rent.is_exempt(new_escrow_account.lamports(), new_escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if Escrow::unpack_unchecked(&new_escrow_account.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        // the original Exchange handed over the whole vault, so that is the offer, and neither
+        // mint was stored; the vault and the receiving account still name them
+        let vault = Self::load_token_account(pdas_temp_token_account)?;
+        if vault.amount == 0 {
+            return Err(EscrowError::InvalidAmount.into());
+        }
+        escrow_info.offered_amount = vault.amount;
+        escrow_info.deposited_mint = vault.mint;
+        escrow_info.expected_mint = Self::load_token_account(token_to_receive_account)?.mint;
+        // without a fee the fee account only stands in for the receiving one
+        escrow_info.fee_account = *token_to_receive_account.key;
+
+        // the vault authority is seeded with the escrow account, so it moves with the state
+        let (new_pda, bump) = vault_authority(program_id, new_escrow_account.key);
+        let owner_change_ix = token::retarget(
+            spl_token::instruction::set_authority(
+                &spl_token::id(),
+                pdas_temp_token_account.key,
+                Some(&new_pda),
+                spl_token::instruction::AuthorityType::AccountOwner,
+                &old_pda,
+                &[&old_pda],
+            )?,
+            token_program.key,
+        );
+        msg!("Calling the token program to hand the vault to the migrated escrow's PDA...");
+        invoke_signed(
+            &owner_change_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                old_pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[LEGACY_VAULT_AUTHORITY_SEED, &[old_bump]]],
+        )?;
+
+        escrow_info.version = ESCROW_VERSION;
+        escrow_info.bump = bump;
+        Escrow::pack(escrow_info, &mut new_escrow_account.data.borrow_mut())?;
+
+        Self::close_escrow_account(old_escrow_account, initializer)
+    }
+
+    fn process_update_expected_amount(
+        accounts: &[AccountInfo],
+        new_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        if new_amount == 0 { // the offer would go for nothing
+            return Err(EscrowError::InvalidAmount.into());
+        }
+
+        // written back in place, so only the current layout will do
+        let mut escrow_info = Escrow::from_account_info(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        // a priced escrow works its expected amount out of the vault at every Exchange, and an
+        // auction out of the clock
+        if escrow_info.price.is_some() || escrow_info.auction.is_some() {
+            return Err(EscrowError::InvalidPrice.into());
+        }
+        if !escrow_info.status.is_open() { // nothing left for a new price to apply to
+            return Err(EscrowError::InvalidStateTransition.into());
+        }
+
+        // Exchange loads the state afresh, so the next taker pays this amount
+        escrow_info.expected_amount = new_amount;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    /// Moves `additional` more of the offered token into the vault and grows the offer to match,
+    /// optionally repricing it in the same go
+    fn process_deposit_more(
+        accounts: &[AccountInfo],
+        additional: u64,
+        expected_amount: Option<u64>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if additional == 0 || expected_amount == Some(0) {
+            return Err(EscrowError::InvalidAmount.into());
+        }
+
+        let source_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let deposited_mint_account = next_account_info(account_info_iter)?;
+
+        // written back in place, so only the current layout will do
+        let mut escrow_info = Escrow::from_account_info(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        if !escrow_info.status.is_open() {
+            return Err(EscrowError::InvalidStateTransition.into());
+        }
+        // lamports sit in the escrow account itself, there is no vault to top up
+        if escrow_info.deposited_is_native {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        // a priced escrow works its expected amount out of the vault at every Exchange, and an
+        // auction out of the clock
+        if expected_amount.is_some()
+            && (escrow_info.price.is_some() || escrow_info.auction.is_some())
+        {
+            return Err(EscrowError::InvalidPrice.into());
+        }
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        if Self::load_token_account(pdas_temp_token_account)?.owner != pda {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        Self::assert_token_account_mint(source_account, &escrow_info.deposited_mint)?;
+        let deposited_mint = Self::load_mint(deposited_mint_account, &escrow_info.deposited_mint)?;
+
+        let transfer_ix = token::retarget(
+            spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                source_account.key,
+                deposited_mint_account.key,
+                pdas_temp_token_account.key,
+                initializer.key,
+                &[initializer.key],
+                additional,
+                deposited_mint.decimals,
+            )?,
+            token_program.key,
+        );
+        msg!("Calling the token program to top up the temp account...");
+        invoke(
+            &transfer_ix,
+            &[
+                source_account.clone(),
+                deposited_mint_account.clone(),
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        // Exchange pays out the stored offer and hands any surplus back, so the offer has to grow
+        escrow_info.offered_amount = escrow_info
+            .offered_amount
+            .checked_add(additional)
+            .ok_or(EscrowError::AmountOverflow)?;
+        if let Some(expected_amount) = expected_amount {
+            escrow_info.expected_amount = expected_amount;
+        }
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    /// Moves `amount` of the offer out of the vault back to the initializer and shrinks the
+    /// offer to match, leaving the rest of it open
+    fn process_withdraw(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if amount == 0 {
+            return Err(EscrowError::InvalidAmount.into());
+        }
+
+        let initializers_token_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let deposited_mint_account = next_account_info(account_info_iter)?;
+
+        // written back in place, so only the current layout will do
+        let mut escrow_info = Escrow::from_account_info(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        if !escrow_info.status.is_open() {
+            return Err(EscrowError::InvalidStateTransition.into());
+        }
+        // lamports sit in the escrow account itself, there is no vault to draw on
+        if escrow_info.deposited_is_native {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        // an empty offer could never be taken, the whole of it comes back by reclaiming
+        if amount >= escrow_info.offered_amount {
+            return Err(EscrowError::InsufficientDeposit.into());
+        }
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Self::load_token_account(pdas_temp_token_account)?.owner != pda {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        Self::assert_token_account_mint(initializers_token_account, &escrow_info.deposited_mint)?;
+        let deposited_mint = Self::load_mint(deposited_mint_account, &escrow_info.deposited_mint)?;
+
+        // settled in state before the CPI, as Exchange does
+        escrow_info.offered_amount -= amount;
+        escrow_info.pack_into_slice(&mut escrow_account.data.borrow_mut());
+
+        let transfer_ix = token::retarget(
+            spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                pdas_temp_token_account.key,
+                deposited_mint_account.key,
+                initializers_token_account.key,
+                &pda,
+                &[&pda],
+                amount,
+                deposited_mint.decimals,
+            )?,
+            token_program.key,
+        );
+        msg!("Calling the token program to return part of the offer to the initializer...");
+        invoke_signed(
+            &transfer_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                deposited_mint_account.clone(),
+                initializers_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[VAULT_AUTHORITY_SEED, escrow_account.key.as_ref(), &[escrow_info.bump]]],
+        )
+    }
+
+    /// Hands the escrow to `new_initializer`, and its payment to `new_token_to_receive_account`
+    /// if one is given
+    fn process_transfer_initializer(
+        accounts: &[AccountInfo],
+        new_initializer: Pubkey,
+        new_token_to_receive_account: Option<Pubkey>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        // written back in place, so only the current layout will do
+        let mut escrow_info = Escrow::from_account_info(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        if !escrow_info.status.is_open() { // a closed escrow has nothing left to hand over
+            return Err(EscrowError::InvalidStateTransition.into());
+        }
+
+        // every exchange and reclaim reads both keys from state, so they now go to the new party
+        escrow_info.initializer_pubkey = new_initializer;
+        if let Some(new_token_to_receive_account) = new_token_to_receive_account {
+            // held to the same checks as SetReceiveAccount, or the payment could be stranded
+            let receive_account = next_account_info(account_info_iter)?;
+            if *receive_account.key != new_token_to_receive_account {
+                return Err(EscrowError::AccountMismatch.into());
+            }
+            Self::check_receive_account(receive_account, &escrow_info)?;
+            // without a fee the fee account only stands in for the receiving one
+            if escrow_info.fee_basis_points == 0 {
+                escrow_info.fee_account = new_token_to_receive_account;
+            }
+            escrow_info.initializer_token_to_receive_account_pubkey = new_token_to_receive_account;
+        }
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    /// Redirects the escrow's payment to another token account holding the mint it expects
+    fn process_set_receive_account(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let new_token_to_receive_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Escrow::from_account_info(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        if !escrow_info.status.is_open() {
+            return Err(EscrowError::InvalidStateTransition.into());
+        }
+        Self::check_receive_account(new_token_to_receive_account, &escrow_info)?;
+
+        // without a fee the fee account only stands in for the receiving one
+        if escrow_info.fee_basis_points == 0 {
+            escrow_info.fee_account = *new_token_to_receive_account.key;
+        }
+        escrow_info.initializer_token_to_receive_account_pubkey = *new_token_to_receive_account.key;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    /// Redirects the escrow's payment to the initializer's associated account for the mint it
+    /// expects, which the initializer pays to create if it doesn't exist yet
+    #[cfg(feature = "create-receive-account")]
+    fn process_set_receive_ata(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let associated_account = next_account_info(account_info_iter)?;
+        let mint_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let associated_token_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Escrow::from_account_info(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        if !escrow_info.status.is_open() {
+            return Err(EscrowError::InvalidStateTransition.into());
+        }
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        if *mint_account.key != escrow_info.expected_mint {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        if *associated_account.key != escrow_info.expected_associated_account(initializer.key) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if *associated_token_program.key != token::associated_token::id()
+            || *system_program.key != solana_program::system_program::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if associated_account.lamports() == 0 { // closed, or never made
+            let create_ix = token::create_associated_account(
+                initializer.key,
+                initializer.key,
+                mint_account.key,
+                token_program.key,
+            );
+            msg!("Calling the associated token program to create the receiving account...");
+            invoke(
+                &create_ix,
+                &[
+                    initializer.clone(),
+                    associated_account.clone(),
+                    initializer.clone(),
+                    mint_account.clone(),
+                    system_program.clone(),
+                    token_program.clone(),
+                    associated_token_program.clone(),
+                ],
+            )?;
+        }
+        Self::check_receive_account(associated_account, &escrow_info)?;
+
+        // without a fee the fee account only stands in for the receiving one
+        if escrow_info.fee_basis_points == 0 {
+            escrow_info.fee_account = *associated_account.key;
+        }
+        escrow_info.initializer_token_to_receive_account_pubkey = *associated_account.key;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    /// Refuses an account the escrow's payment can't be sent to: one under another token
+    /// program than the escrow's, or holding another mint than the one it expects
+    fn check_receive_account(account: &AccountInfo, escrow: &Escrow) -> ProgramResult {
+        // Exchange transfers with the escrow's own token program, so the account has to be its
+        if *account.owner != escrow.token_program {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::assert_token_account_mint(account, &escrow.expected_mint)
+    }
+
+    /// Sends `amount` of what a taker left in a token account the escrow's PDA owns back to
+    /// them, on the word of the initializer or the config's admin
+    fn process_refund_taker(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let authority = next_account_info(account_info_iter)?;
+
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if amount == 0 {
+            return Err(EscrowError::InvalidAmount.into());
+        }
+
+        let holding_account = next_account_info(account_info_iter)?;
+        let takers_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let mint_account = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let taker = next_account_info(account_info_iter)?;
+
+        // the holding account can't say whose tokens it holds, so the taker vouches for them
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_info = Self::load_escrow(escrow_account)?;
+        if escrow_info.initializer_pubkey != *authority.key
+            && !Self::is_config_admin(config_account, authority.key, program_id)?
+        {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+        // the offer only ever leaves through an exchange or back to the initializer
+        if escrow_info.temp_token_account_pubkey == *holding_account.key
+            || escrow_info.basket_vaults().contains(holding_account.key)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let holding_account_info = Self::load_token_account(holding_account)?;
+        if holding_account_info.owner != pda {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        if holding_account_info.amount < amount {
+            return Err(EscrowError::NothingToRefund.into());
+        }
+        let takers_token_account_info = Self::load_token_account(takers_token_account)?;
+        if takers_token_account_info.mint != holding_account_info.mint {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        if takers_token_account_info.owner != *taker.key {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        let mint = Self::load_mint(mint_account, &holding_account_info.mint)?;
+
+        let refund_ix = token::retarget(
+            spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                holding_account.key,
+                mint_account.key,
+                takers_token_account.key,
+                &pda,
+                &[&pda],
+                amount,
+                mint.decimals,
+            )?,
+            token_program.key,
+        );
+        msg!("Calling the token program to refund the taker...");
+        invoke_signed(
+            &refund_ix,
+            &[
+                holding_account.clone(),
+                mint_account.clone(),
+                takers_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[VAULT_AUTHORITY_SEED, escrow_account.key.as_ref(), &[escrow_info.bump]]],
+        )
+    }
+
+    /// Winds up an escrow past its deadline for whoever sends it, the initializer gets
+    /// the whole vault back and every account's rent
+    fn process_reclaim_expired(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_account = next_account_info(account_info_iter)?; // gets the offered tokens
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let deposited_mint_account = next_account_info(account_info_iter)?;
+
+        if escrow_account.owner != program_id { // only the program's own state says who gets what
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut escrow_info = Self::load_escrow(escrow_account)?;
+        // an escrow without a deadline never expires, only a taker can close it
+        if !escrow_info.is_expired(clock.unix_timestamp) {
+            return Err(EscrowError::EscrowNotExpired.into());
+        }
+        escrow_info.transition(EscrowStatus::Cancelled)?;
+        if escrow_info.initializer_pubkey != *initializers_main_account.key {
+            return Err(EscrowError::NotInitializer.into());
+        }
+        // the offered lamports go back with the account
+        if escrow_info.deposited_is_native {
+            return Self::close_escrow_account(escrow_account, initializers_main_account);
+        }
+
+        Self::check_escrow_token_program(token_program, &escrow_info)?;
+        let pda = vault_authority_with_bump(program_id, escrow_account.key, escrow_info.bump)?;
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let pdas_temp_token_account_info = Self::load_token_account(pdas_temp_token_account)?;
+        if pdas_temp_token_account_info.owner != pda {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        // nobody signs, so the tokens may only go to an account the initializer holds
+        let initializers_token_account_info = Self::load_token_account(initializers_token_account)?;
+        if initializers_token_account_info.owner != escrow_info.initializer_pubkey {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+        if initializers_token_account_info.mint != escrow_info.deposited_mint {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        let deposited_mint = Self::load_mint(deposited_mint_account, &escrow_info.deposited_mint)?;
+        let signer_seeds: &[&[u8]] =
+            &[VAULT_AUTHORITY_SEED, escrow_account.key.as_ref(), &[escrow_info.bump]];
+
+        let vault_amount = pdas_temp_token_account_info.amount; // a surplus goes back too
+        if vault_amount > 0 {
+            let transfer_to_initializer_ix = token::retarget(
+                spl_token::instruction::transfer_checked(
+                    &spl_token::id(),
+                    pdas_temp_token_account.key,
+                    deposited_mint_account.key,
+                    initializers_token_account.key,
+                    &pda,
+                    &[&pda],
+                    vault_amount,
+                    deposited_mint.decimals,
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to return the offer to the initializer...");
+            invoke_signed(
+                &transfer_to_initializer_ix,
+                &[
+                    pdas_temp_token_account.clone(),
+                    deposited_mint_account.clone(),
+                    initializers_token_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[signer_seeds],
+            )?;
+        }
+
+        Self::release_vault(
+            token_program,
+            pdas_temp_token_account,
+            initializers_main_account,
+            pda_account,
+            &pda,
+            signer_seeds,
+            0,
+        )?;
+        Self::close_escrow_account(escrow_account, initializers_main_account)
+    }
+
+    /// Creates the config account at its PDA, with the signer as admin and nothing paused
+    fn process_init_config(
+        accounts: &[AccountInfo],
+        unpause_delay: i64,
+        max_expiry_horizon_secs: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if unpause_delay < 0 || max_expiry_horizon_secs <= 0 {
+            return Err(EscrowError::InvalidAmount.into());
+        }
+
+        let config_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info).map_err(|_| EscrowError::RentUnavailable)?;
+        let program_data = next_account_info(account_info_iter)?;
+
+        // the deployer's key, so no one can front-run the deploy and take the pause switch
+        if *program_data.key != program_data_address(program_id) {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+        if *program_data.owner != bpf_loader_upgradeable::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if upgrade_authority(&program_data.data.borrow()) != Some(*admin.key) {
+            return Err(EscrowError::NotAdmin.into());
+        }
+
+        let (config_key, bump) = config_address(program_id);
+        if *config_account.key != config_key {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+        if config_account.lamports() != 0 { // there is only ever the one config
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let create_ix = solana_program::system_instruction::create_account(
+            admin.key,
+            config_account.key,
+            rent.minimum_balance(Config::LEN),
+            Config::LEN as u64,
+            program_id,
+        );
+        msg!("Calling the system program to create the config account...");
+        invoke_signed(
+            &create_ix,
+            &[admin.clone(), config_account.clone(), system_program.clone()],
+            &[&[CONFIG_SEED, &[bump]]],
+        )?;
+        if config_account.data_len() != Config::LEN || config_account.owner != program_id {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let config = Config {
+            is_initialized: true,
+            admin: *admin.key,
+            paused: false,
+            unpause_delay,
+            pause_until: 0,
+            max_expiry_horizon_secs,
+        };
+        Config::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    fn process_set_paused(
+        accounts: &[AccountInfo],
+        paused: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config_account = next_account_info(account_info_iter)?;
+        if config_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *config_account.key != config_address(program_id).0 {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+        let mut config = Config::unpack(&config_account.data.borrow())?;
+        if config.admin != *admin.key {
+            return Err(EscrowError::NotAdmin.into());
+        }
+
+        // only a real restart starts the timelock, so repeating it can't push the end out
+        if config.paused && !paused {
+            let now = Clock::get()?.unix_timestamp;
+            config.pause_until = now
+                .checked_add(config.unpause_delay)
+                .ok_or(EscrowError::AmountOverflow)?;
+        }
+        config.paused = paused;
+        Config::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    /// Refuses to go on while the config account says the program is paused, or was unpaused
+    /// less than its `unpause_delay` ago. A config that InitConfig hasn't created yet pauses
+    /// nothing
+    fn check_not_paused(
+        config_account: Option<&AccountInfo>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let config = match Self::load_config(config_account, program_id)? {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        if config.paused {
+            return Err(EscrowError::ProgramPaused.into());
+        }
+        // the init instructions take no clock account, so ask the runtime, and only when it matters
+        if config.pause_until != 0 {
+            let now = Clock::get()?.unix_timestamp;
+            if now < config.pause_until {
+                return Err(EscrowError::StillPaused.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses an `expiry` further out than the config's `max_expiry_horizon_secs` from now,
+    /// or than `DEFAULT_MAX_EXPIRY_HORIZON_SECS` before InitConfig. `0` never expires
+    fn check_expiry_horizon(
+        config_account: Option<&AccountInfo>,
+        expiry: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if expiry == 0 {
+            return Ok(());
+        }
+        let horizon = Self::load_config(config_account, program_id)?
+            .map_or(DEFAULT_MAX_EXPIRY_HORIZON_SECS, |config| config.max_expiry_horizon_secs);
+        let now = Clock::get()?.unix_timestamp;
+        if expiry > now.saturating_add(horizon) {
+            return Err(EscrowError::ExpiryTooFar.into());
+        }
+        Ok(())
+    }
+
+    /// The config at `config_address`, or `None` until InitConfig creates it
+    fn load_config(
+        config_account: Option<&AccountInfo>,
+        program_id: &Pubkey,
+    ) -> Result<Option<Config>, ProgramError> {
+        let config_account = config_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *config_account.key != config_address(program_id).0 {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+        if config_account.owner != program_id || config_account.data_is_empty() {
+            return Ok(None);
+        }
+        let config = Config::unpack_unchecked(&config_account.data.borrow())?;
+        Ok(Some(config).filter(|config| config.is_initialized()))
+    }
+
+    /// Whether `key` is the admin of the config at `config_address`. Before InitConfig
+    /// creates it there is no admin at all
+    fn is_config_admin(
+        config_account: &AccountInfo,
+        key: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<bool, ProgramError> {
+        if *config_account.key != config_address(program_id).0 {
+            return Err(EscrowError::AccountMismatch.into());
+        }
+        if config_account.owner != program_id || config_account.data_is_empty() {
+            return Ok(false);
+        }
+        let config = Config::unpack_unchecked(&config_account.data.borrow())?;
+        Ok(config.is_initialized() && config.admin == *key)
+    }
+
+    /// Sends the taker's `payment` for a fill, `fee` of it to the fee account and the rest to
+    /// the initializer, checked against `mint` and its `decimals`
+    fn pay_initializer<'a>(
+        token_program: &AccountInfo<'a>,
+        accounts: &PaymentAccounts<'a, '_>,
+        mint: &AccountInfo<'a>,
+        decimals: u8,
+        payment: u64,
+        fee: u64,
+    ) -> ProgramResult {
+        let PaymentAccounts {
+            taker,
+            takers_sending_token_account,
+            initializers_token_to_receive_account,
+            fee_account,
+        } = *accounts;
+        if fee > 0 {
+            let transfer_fee_ix = token::retarget(
+                spl_token::instruction::transfer_checked(
+                    &spl_token::id(),
+                    takers_sending_token_account.key,
+                    mint.key,
+                    fee_account.key,
+                    taker.key,
+                    &[taker.key],
+                    fee,
+                    decimals,
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to transfer the fee...");
+            invoke(
+                &transfer_fee_ix,
+                &[
+                    takers_sending_token_account.clone(),
+                    mint.clone(),
+                    fee_account.clone(),
+                    taker.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
+        // transfer from Bob (context) to Alice
+        let transfer_to_initializer_ix = token::retarget(
+            spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                takers_sending_token_account.key,
+                mint.key,
+                initializers_token_to_receive_account.key,
+                taker.key,
+                &[taker.key],
+                payment.checked_sub(fee).ok_or(EscrowError::AmountOverflow)?,
+                decimals,
+            )?,
+            token_program.key,
+        );
+        msg!("Calling the token program to transfer tokens to the escrow's initializer...");
+        invoke(
+            &transfer_to_initializer_ix,
+            &[
+                takers_sending_token_account.clone(),
+                mint.clone(),
+                initializers_token_to_receive_account.clone(),
+                taker.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    /// Gives the PDA's temp account back to the initializer if `remaining` tokens are
+    /// still in it, otherwise closes it to them
+    fn release_vault<'a>(
+        token_program: &AccountInfo<'a>,
+        pdas_temp_token_account: &AccountInfo<'a>,
+        initializers_main_account: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        pda: &Pubkey,
+        signer_seeds: &[&[u8]],
+        remaining: u64,
+    ) -> ProgramResult {
+        if remaining > 0 {
+            // a token account can't be closed with a balance, so hand it back to Alice instead
+            let owner_change_ix = token::retarget(
+                spl_token::instruction::set_authority(
+                    &spl_token::id(),
+                    pdas_temp_token_account.key,
+                    Some(initializers_main_account.key),
+                    spl_token::instruction::AuthorityType::AccountOwner,
+                    pda,
+                    &[pda],
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to return pda's temp account to the initializer...");
+            invoke_signed(
+                &owner_change_ix,
+                &[
+                    pdas_temp_token_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[signer_seeds],
+            )
+        } else {
+            // then close the PDA account, again via invoke_signed
+            let close_pdas_temp_acc_ix = token::retarget(
+                spl_token::instruction::close_account(
+                    &spl_token::id(),
+                    pdas_temp_token_account.key,
+                    initializers_main_account.key,
+                    pda,
+                    &[pda],
+                )?,
+                token_program.key,
+            );
+            msg!("Calling the token program to close pda's temp account...");
+            invoke_signed(
+                &close_pdas_temp_acc_ix,
+                &[
+                    pdas_temp_token_account.clone(),
+                    initializers_main_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[signer_seeds],
+            )
+        }
+    }
+
+    /// Sends the escrow account's rent to the initializer and wipes its data
+    fn close_escrow_account(
+        escrow_account: &AccountInfo,
+        initializers_main_account: &AccountInfo,
+    ) -> ProgramResult {
+        // close the state account
+        msg!("Closing the escrow account...");
+        **initializers_main_account.lamports.borrow_mut() = initializers_main_account
+            .lamports()
+            .checked_add(escrow_account.lamports())
+            .ok_or(EscrowError::AmountOverflow)?;
+        **escrow_account.lamports.borrow_mut() = 0;
+        // 1.6 can neither realloc the account nor assign it back to the system program, so
+        // the stale state is wiped before the view is cut: the runtime keeps these bytes until
+        // it reaps the empty account, and refunding it within the transaction must not revive it
+        escrow_account.data.borrow_mut().fill(0);
+        *escrow_account.data.borrow_mut() = &mut [];
+
+        Ok(())
+    }
+
+    /// Logs a fill of `fill` for `payment`, for either kind of vault
+    fn emit_exchanged(
+        escrow_account: &AccountInfo,
+        taker: &AccountInfo,
+        escrow: &Escrow,
+        fill: u64,
+        payment: u64,
+    ) -> ProgramResult {
+        EscrowEvent::Exchanged(EscrowExchanged {
+            escrow: *escrow_account.key,
+            taker: *taker.key,
+            initializer: escrow.initializer_pubkey,
+            amount: fill,
+            payment,
+        })
+        .emit()
+    }
+
+    /// Unpacks an escrow state account, naming one that is closed or was never initialized
+    fn load_escrow(info: &AccountInfo) -> Result<Escrow, ProgramError> {
+        let escrow = Self::load_escrow_of_any_version(info)?;
+        // a v0 escrow lacks the fields every instruction but MigrateEscrow relies on
+        if escrow.version != ESCROW_VERSION {
+            return Err(EscrowError::UnsupportedVersion.into());
+        }
+        Ok(escrow)
+    }
+
+    /// Same as `load_escrow`, but also reads an escrow of the original layout
+    fn load_escrow_of_any_version(info: &AccountInfo) -> Result<Escrow, ProgramError> {
+        if info.data_is_empty() { // already closed, e.g. by an earlier Exchange in the same transaction
+            return Err(EscrowError::EscrowNotInitialized.into());
+        }
+        let escrow = Escrow::unpack_unchecked(&info.data.borrow())?;
+        if !escrow.is_initialized() {
+            return Err(EscrowError::EscrowNotInitialized.into());
+        }
+        Escrow::check_discriminator(&info.data.borrow())?;
+        Ok(escrow)
+    }
+
+    /// Unpacks the mint an escrow stored as `expected`, refusing any other account in its slot
+    fn load_mint(info: &AccountInfo, expected: &Pubkey) -> Result<Mint, ProgramError> {
+        if info.key != expected {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        if !token::is_token_program(info.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        token::unpack_mint(&info.data.borrow())
+    }
+
+    /// Refuses a token account holding some other mint than the one the escrow names
+    fn assert_token_account_mint(account: &AccountInfo, expected_mint: &Pubkey) -> ProgramResult {
+        if Self::load_token_account(account)?.mint != *expected_mint {
+            return Err(EscrowError::MintMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Refuses a token program account that is neither spl-token nor Token-2022, before any CPI goes to it
+    fn check_token_program(info: &AccountInfo) -> ProgramResult {
+        if !token::is_token_program(info.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+        Ok(())
+    }
+
+    /// Refuses a token program account other than the one the escrow was made with
+    fn check_escrow_token_program(info: &AccountInfo, escrow: &Escrow) -> ProgramResult {
+        if *info.key != escrow.token_program {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+        Ok(())
+    }
+
+    /// Unpacks a token account, refusing anything the token program doesn't own and
+    /// anything that isn't a live, initialized account
+    fn load_token_account(info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+        if !token::is_token_program(info.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_account = token::unpack_account_unchecked(&info.data.borrow())?;
+        match token_account.state {
+            AccountState::Initialized => Ok(token_account),
+            AccountState::Uninitialized => Err(ProgramError::UninitializedAccount),
+            AccountState::Frozen => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::clock::Epoch;
+
+    fn load(data: &mut [u8], owner: &Pubkey) -> Result<TokenAccount, ProgramError> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            data,
+            owner,
+            false,
+            Epoch::default(),
+        );
+        Processor::load_token_account(&info)
+    }
+
+    fn packed(state: AccountState) -> Vec<u8> {
+        let token_account = TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+            state,
+            ..TokenAccount::default()
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(token_account, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn process_rejects_empty_instruction_data() {
+        assert_eq!(
+            Processor::process(&Pubkey::new_unique(), &[], &[]),
+            Err(EscrowError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn process_rejects_oversized_instruction_data() {
+        let mut data = vec![1];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.resize(MAX_INSTRUCTION_DATA_LEN + 1, 0);
+
+        assert_eq!(
+            Processor::process(&Pubkey::new_unique(), &[], &data),
+            Err(EscrowError::InstructionTooLarge.into())
+        );
+    }
+
+    #[test]
+    fn close_escrow_account_wipes_state_and_moves_lamports() {
+        let (escrow_key, initializer_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (program_id, system_program) = (Pubkey::new_unique(), Pubkey::default());
+        let (mut escrow_lamports, mut initializer_lamports) = (10, 5);
+        let mut data = vec![1; Escrow::LEN];
+        {
+            let escrow = AccountInfo::new(
+                &escrow_key,
+                false,
+                true,
+                &mut escrow_lamports,
+                &mut data,
+                &program_id,
+                false,
+                Epoch::default(),
+            );
+            let initializer = AccountInfo::new(
+                &initializer_key,
+                true,
+                true,
+                &mut initializer_lamports,
+                &mut [],
+                &system_program,
+                false,
+                Epoch::default(),
+            );
+            Processor::close_escrow_account(&escrow, &initializer).unwrap();
+            assert_eq!(escrow.data_len(), 0);
+        }
+        assert_eq!((escrow_lamports, initializer_lamports), (0, 15));
+        // what the runtime still holds for the account until it is reaped
+        assert!(data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn load_token_account_accepts_initialized_account() {
+        let mut data = packed(AccountState::Initialized);
+
+        assert_eq!(load(&mut data, &spl_token::id()).unwrap().amount, 42);
+    }
+
+    #[test]
+    fn load_token_account_rejects_unusable_accounts() {
+        let mut data = vec![0; TokenAccount::LEN];
+        assert_eq!(
+            load(&mut data, &spl_token::id()),
+            Err(ProgramError::UninitializedAccount)
+        );
+
+        let mut data = packed(AccountState::Frozen);
+        assert_eq!(
+            load(&mut data, &spl_token::id()),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut data = packed(AccountState::Initialized);
+        assert_eq!(
+            load(&mut data, &Pubkey::new_unique()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        let mut data = vec![0; TokenAccount::LEN - 1];
+        assert_eq!(
+            load(&mut data, &spl_token::id()),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn assert_token_account_mint_refuses_another_mint() {
+        let mint = Pubkey::new_unique();
+        let token_account = TokenAccount {
+            mint,
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(token_account, &mut data).unwrap();
+        let key = Pubkey::new_unique();
+        let owner = spl_token::id();
+        let mut lamports = 0;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            Epoch::default(),
+        );
+
+        assert_eq!(Processor::assert_token_account_mint(&info, &mint), Ok(()));
+        assert_eq!(
+            Processor::assert_token_account_mint(&info, &Pubkey::new_unique()),
+            Err(EscrowError::MintMismatch.into())
+        );
     }
 }
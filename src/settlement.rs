@@ -0,0 +1,91 @@
+use crate::{error::EscrowError, state::Escrow};
+
+/// Where a taker's payment for the whole of an escrow goes, for a client to show before
+/// anyone signs. `net_to_maker` and the two fees always add up to `gross`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettlementBreakdown {
+    /// What the taker pays for the whole offer
+    pub gross: u64,
+    /// The part of it the escrow's fee account takes
+    pub protocol_fee: u64,
+    /// The part of it a referrer takes; escrows have no referrer, so always `0`
+    pub referrer_fee: u64,
+    /// What is left for the initializer
+    pub net_to_maker: u64,
+}
+
+/// Splits the payment for the whole of `escrow` the way Exchange does, with the same checked
+/// math and rounding, so a preview never differs from the settlement
+pub fn settlement_breakdown(escrow: &Escrow) -> Result<SettlementBreakdown, EscrowError> {
+    let gross = escrow.payment_for_fill(escrow.offered_amount)?;
+    let protocol_fee = escrow.fee_for(gross)?;
+    let referrer_fee = 0;
+    let net_to_maker = gross
+        .checked_sub(protocol_fee)
+        .and_then(|rest| rest.checked_sub(referrer_fee))
+        .ok_or(EscrowError::AmountOverflow)?;
+    Ok(SettlementBreakdown {
+        gross,
+        protocol_fee,
+        referrer_fee,
+        net_to_maker,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::program_pack::Pack;
+
+    use super::*;
+    use crate::state::{RoundingMode, MAX_FEE_BASIS_POINTS};
+
+    fn escrow(offered_amount: u64, expected_amount: u64, fee_basis_points: u16) -> Escrow {
+        let mut escrow = Escrow::unpack_unchecked(&[0; Escrow::LEN]).unwrap();
+        escrow.offered_amount = offered_amount;
+        escrow.expected_amount = expected_amount;
+        escrow.fee_basis_points = fee_basis_points;
+        escrow
+    }
+
+    #[test]
+    fn without_a_fee_the_maker_gets_everything() {
+        assert_eq!(
+            settlement_breakdown(&escrow(100, 50, 0)),
+            Ok(SettlementBreakdown {
+                gross: 50,
+                protocol_fee: 0,
+                referrer_fee: 0,
+                net_to_maker: 50,
+            })
+        );
+    }
+
+    #[test]
+    fn the_highest_fee_takes_the_whole_payment() {
+        assert_eq!(
+            settlement_breakdown(&escrow(100, 50, MAX_FEE_BASIS_POINTS)),
+            Ok(SettlementBreakdown {
+                gross: 50,
+                protocol_fee: 50,
+                referrer_fee: 0,
+                net_to_maker: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn follows_the_fee_rounding() {
+        let mut escrow = escrow(100, 333, 30);
+        assert_eq!(settlement_breakdown(&escrow).unwrap().net_to_maker, 333);
+        escrow.rounding = RoundingMode::FloorToTaker;
+        assert_eq!(settlement_breakdown(&escrow).unwrap().net_to_maker, 332);
+    }
+
+    #[test]
+    fn overflow_is_an_error_not_a_wrong_preview() {
+        assert_eq!(
+            settlement_breakdown(&escrow(100, u64::MAX, MAX_FEE_BASIS_POINTS)),
+            Err(EscrowError::AmountOverflow)
+        );
+    }
+}
@@ -1,17 +1,899 @@
 use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use std::{convert::TryFrom, fmt};
 
+use crate::error::EscrowError;
+#[cfg(feature = "create-receive-account")]
+use crate::token;
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct Escrow {
+    /// Which layout the account was read from, see `ESCROW_VERSION`
+    pub version: u8,
     pub is_initialized: bool,
     pub initializer_pubkey: Pubkey,
     pub temp_token_account_pubkey: Pubkey,
     pub initializer_token_to_receive_account_pubkey: Pubkey,
     pub expected_amount: u64,
+    pub offered_amount: u64,
+    /// Who may force-settle the escrow, if anyone. Part of the layout whether or not the
+    /// `arbitration` feature is built, so every client reads the same account size
+    pub arbiter: Option<Pubkey>,
+    /// If set, Exchange ignores the stored amounts and trades the whole vault at this price
+    pub price: Option<Price>,
+    /// How front-ends label the order, the program itself never reads it
+    pub side: Side,
+    /// The vault authority's bump seed, found once at init so Exchange doesn't search for it again
+    pub bump: u8,
+    /// Unix time after which Exchange refuses the escrow, `0` for never
+    pub expiry_unix_timestamp: i64,
+    /// The mint of the token the initializer wants, the taker must pay in it
+    pub expected_mint: Pubkey,
+    /// The mint of the token held in the temp token account
+    pub deposited_mint: Pubkey,
+    /// The share of each payment skimmed off for the fee account, in hundredths of a percent
+    pub fee_basis_points: u16,
+    /// Where the fee goes, the initializer's receive account when the escrow charges none
+    pub fee_account: Pubkey,
+    /// The offer is lamports held in the escrow account itself rather than tokens in a temp
+    /// token account, which is then the escrow account's own key
+    pub deposited_is_native: bool,
+    /// The token program every CPI for this escrow goes to, spl-token or Token-2022
+    pub token_program: Pubkey,
+    /// The only key that may take the escrow, the zero key for anyone
+    pub allowed_taker: Pubkey,
+    /// Where the escrow is in its life, moved on only through `transition`
+    pub status: EscrowStatus,
+    /// How many of `basket` hold part of the offer, `0` for an escrow of a single token
+    pub basket_len: u8,
+    /// Further vaults of a basket escrow, each handed to the taker whole on top of the temp
+    /// token account. Only the first `basket_len` are used, the rest are the zero key
+    pub basket: [Pubkey; MAX_BASKET_LEN],
+    /// Unix time before which Exchange refuses the escrow, `0` to take it at once
+    pub start_unix_timestamp: i64,
+    /// If set, the escrow is sold by Dutch auction and its expected amount is whatever the
+    /// auction asks at the time of the exchange
+    pub auction: Option<DutchAuction>,
+    /// The least a partial fill may take, `0` for no least. A fill that takes all that is left
+    /// is always allowed, however small
+    pub min_fill_amount: u64,
+    /// Which party a partial fill's share of the price and the fee are rounded in favour of
+    pub rounding: RoundingMode,
+}
+
+/// Whether the maker is selling the offered token or buying the expected one with it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    #[default]
+    Sell,
+    Buy,
+}
+
+impl Side {
+    pub fn from_u8(byte: u8) -> Result<Self, EscrowError> {
+        match byte {
+            0 => Ok(Side::Sell),
+            1 => Ok(Side::Buy),
+            _ => Err(EscrowError::InvalidSide),
+        }
+    }
+}
+
+/// Who a rounding remainder falls to when the price is split for a partial fill or the fee is
+/// taken out of a payment. A priced escrow's payment always rounds up, as its price does
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// The maker keeps the remainders: a partial fill's share rounds up and the fee down
+    #[default]
+    FloorToMaker,
+    /// The taker keeps the remainders: a partial fill's share rounds down, and the maker gives
+    /// up the fee's by rounding it up
+    FloorToTaker,
+}
+
+impl RoundingMode {
+    pub fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(RoundingMode::FloorToMaker),
+            1 => Some(RoundingMode::FloorToTaker),
+            _ => None,
+        }
+    }
+}
+
+/// Where an escrow is in its life. Only an open one, `Active` or `PartiallyFilled`, can be
+/// filled or wound up, so whatever settles an escrow can't settle it twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscrowStatus {
+    Active,
+    PartiallyFilled,
+    Completed,
+    Cancelled,
+}
+
+impl EscrowStatus {
+    pub fn from_u8(byte: u8) -> Result<Self, ProgramError> {
+        match byte {
+            0 => Ok(EscrowStatus::Active),
+            1 => Ok(EscrowStatus::PartiallyFilled),
+            2 => Ok(EscrowStatus::Completed),
+            3 => Ok(EscrowStatus::Cancelled),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Whether a taker may still take from the escrow
+    pub fn is_open(self) -> bool {
+        matches!(self, EscrowStatus::Active | EscrowStatus::PartiallyFilled)
+    }
+}
+
+/// Payment per offered token, as a fraction of the expected token's base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Price {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Price {
+    /// What the taker pays for `amount` offered tokens, `ceil(amount * numerator / denominator)`
+    /// so rounding never favours the taker
+    pub fn payment_for(&self, amount: u64) -> Result<u64, EscrowError> {
+        if self.numerator == 0 || self.denominator == 0 {
+            return Err(EscrowError::InvalidPrice);
+        }
+        // a product of two u64s always fits in a u128
+        let payment = (amount as u128 * self.numerator as u128).div_ceil(self.denominator as u128);
+        u64::try_from(payment).map_err(|_| EscrowError::AmountOverflow)
+    }
+}
+
+/// A price that falls in a straight line from `start_amount` at `start_unix_timestamp` to
+/// `end_amount` at `end_unix_timestamp`, holding at either end outside that window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DutchAuction {
+    pub start_amount: u64,
+    pub end_amount: u64,
+    pub start_unix_timestamp: i64,
+    pub end_unix_timestamp: i64,
+}
+
+impl DutchAuction {
+    /// Whether the price only ever falls, to something above zero, over a window that isn't empty
+    pub fn is_valid(&self) -> bool {
+        self.end_amount > 0
+            && self.end_amount <= self.start_amount
+            && self.start_unix_timestamp < self.end_unix_timestamp
+    }
+
+    /// What the whole offer costs at `unix_timestamp`. The part of the drop still to come is
+    /// rounded up, so the price never falls early
+    pub fn payment_at(&self, unix_timestamp: i64) -> Result<u64, EscrowError> {
+        if !self.is_valid() {
+            return Err(EscrowError::InvalidPrice);
+        }
+        if unix_timestamp <= self.start_unix_timestamp {
+            return Ok(self.start_amount);
+        }
+        if unix_timestamp >= self.end_unix_timestamp {
+            return Ok(self.end_amount);
+        }
+        // widened, a window can span more than an i64 holds
+        let window = (self.end_unix_timestamp as i128 - self.start_unix_timestamp as i128) as u128;
+        let remaining = (self.end_unix_timestamp as i128 - unix_timestamp as i128) as u128;
+        let drop = (self.start_amount - self.end_amount) as u128;
+        // no more than drop, since remaining is below window
+        let still_to_fall = (drop * remaining).div_ceil(window);
+        Ok(self.end_amount + still_to_fall as u64)
+    }
+}
+
+/// The layout this program writes, `ESCROW_DISCRIMINATOR` and a version byte then every
+/// field below it.
+/// Version `0` is the original program's account, `V0_LEN` long with neither: the initialized
+/// flag, the three account keys and `expected_amount`, nothing else. Its vault belongs to
+/// `legacy_vault_authority`, and it stores no offered amount or mints, so it can't be taken
+/// as it is; `MigrateEscrow` moves it to this layout.
+pub const ESCROW_VERSION: u8 = 1;
+
+/// The first 8 bytes of every escrow account in the current layout, the first 8 bytes of
+/// `sha256("account:Escrow")`. Any other account of the same length, of a type a fork adds
+/// or of another program, fails `unpack` with `InvalidAccountDiscriminator`
+pub const ESCROW_DISCRIMINATOR: [u8; 8] = [31, 213, 123, 187, 186, 22, 218, 155];
+
+/// The most vaults a basket escrow holds besides its temp token account
+pub const MAX_BASKET_LEN: usize = 3;
+
+/// A whole payment in basis points, the most `fee_basis_points` can be
+pub const MAX_FEE_BASIS_POINTS: u16 = 10_000;
+
+/// Seed prefix of every escrow's vault authority, see `vault_authority`. Every derivation and
+/// every `invoke_signed` uses it, so a fork moving to its own namespace only changes it here
+pub const VAULT_AUTHORITY_SEED: &[u8] = b"escrow";
+
+/// The PDA that owns an escrow's temp token account, and its bump. It is seeded with the
+/// escrow state account, so each escrow has its own authority that can't sign for another's vault,
+/// and one initializer runs as many escrows side by side as they make state accounts for.
+pub fn vault_authority(program_id: &Pubkey, escrow_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_AUTHORITY_SEED, escrow_account.as_ref()], program_id)
+}
+
+/// Seed of the one vault authority the original program used, see `legacy_vault_authority`
+pub const LEGACY_VAULT_AUTHORITY_SEED: &[u8] = b"escrow";
+
+/// The PDA that owns the vault of every v0 escrow, and its bump. The original program seeded
+/// it with nothing but `LEGACY_VAULT_AUTHORITY_SEED`, so it signs for all of them at once;
+/// only `MigrateEscrow` still signs with it, to hand a vault to its escrow's own authority
+pub fn legacy_vault_authority(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LEGACY_VAULT_AUTHORITY_SEED], program_id)
+}
+
+/// Seed of the program's one config account, see `config_address`
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// How far out an escrow's expiry may be set while InitConfig hasn't fixed another horizon,
+/// ten years in seconds
+pub const DEFAULT_MAX_EXPIRY_HORIZON_SECS: i64 = 10 * 365 * 24 * 60 * 60;
+
+/// The PDA holding the program's `Config`, and its bump. Until InitConfig creates it the
+/// program runs unpaused
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Where the upgradeable loader keeps `program_id`'s ProgramData account, whose upgrade
+/// authority is the only signer InitConfig takes as admin
+pub fn program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// The upgrade authority in a ProgramData account's data, read straight off the loader's
+/// bincode layout: a `u32` tag of 3, the deploy slot, then an optional key. `None` if the
+/// program is immutable or `data` is some other loader account
+pub fn upgrade_authority(data: &[u8]) -> Option<Pubkey> {
+    if data.len() < PROGRAM_DATA_METADATA_LEN {
+        return None;
+    }
+    let src = array_ref![data, 0, PROGRAM_DATA_METADATA_LEN];
+    let (tag, _slot, has_authority, authority) = array_refs![src, 4, 8, 1, 32];
+    if u32::from_le_bytes(*tag) != 3 || has_authority[0] != 1 {
+        return None;
+    }
+    Some(Pubkey::new_from_array(*authority))
+}
+
+/// The ProgramData fields ahead of the program's bytes, with an upgrade authority set
+pub const PROGRAM_DATA_METADATA_LEN: usize = 45;
+
+/// The vault authority rebuilt from a stored bump, a single hash where `vault_authority`
+/// may try several. Fails with `InvalidSeeds` if the bump puts the key on the curve.
+pub fn vault_authority_with_bump(
+    program_id: &Pubkey,
+    escrow_account: &Pubkey,
+    bump: u8,
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[VAULT_AUTHORITY_SEED, escrow_account.as_ref(), &[bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)
+}
+
+/// Whether a taker who will take no less than `min_acceptable_amount` of the escrow's
+/// offer would get at least that by sending Exchange now.
+///
+/// Without this check a maker who sees a taker's Exchange in flight could close
+/// the escrow and re-initialize it on the same accounts offering less, landing
+/// first so the taker pays the full price for fewer tokens than they were shown.
+/// Exchange makes the taker state the least they accept and rejects the trade
+/// with `ExpectedAmountMismatch` when this returns false. An offer that grew in the
+/// meantime, topped up or repriced to the live vault, still goes through; clients can
+/// call it on a freshly fetched escrow before signing.
+pub fn front_run_safe(escrow: &Escrow, min_acceptable_amount: u64) -> bool {
+    escrow.offered_amount >= min_acceptable_amount
+}
+
+impl Escrow {
+    /// Reads a live escrow from an account `program_id` owns, for clients and for programs
+    /// calling this one. Only the current layout is read, an older one has to be migrated
+    /// first; a closed or never initialized escrow fails with `EscrowNotInitialized`
+    pub fn from_account_info(
+        info: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        if info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if info.data_is_empty() {
+            return Err(EscrowError::EscrowNotInitialized.into());
+        }
+        if info.data_len() != Escrow::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let escrow = Escrow::unpack_unchecked(&info.data.borrow())?;
+        if !escrow.is_initialized() {
+            return Err(EscrowError::EscrowNotInitialized.into());
+        }
+        Escrow::check_discriminator(&info.data.borrow())?;
+        Ok(escrow)
+    }
+
+    /// Clears the initialized flag of account data of either layout, leaving every other byte
+    /// as it was, so whatever reads the escrow next finds it settled before it is closed
+    pub fn mark_uninitialized(dst: &mut [u8]) -> Result<(), ProgramError> {
+        let offset = match dst.len() {
+            Escrow::LEN => IS_INITIALIZED_OFFSET,
+            Escrow::V0_LEN => 0,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        dst[offset] = 0;
+        Ok(())
+    }
+
+    /// Whether the deadline has passed at `unix_timestamp`; the deadline itself is still in time
+    pub fn is_expired(&self, unix_timestamp: i64) -> bool {
+        self.expiry_unix_timestamp != 0 && unix_timestamp > self.expiry_unix_timestamp
+    }
+
+    /// Whether the escrow may be taken yet at `unix_timestamp`; the start time itself is in time
+    pub fn has_started(&self, unix_timestamp: i64) -> bool {
+        self.start_unix_timestamp == 0 || unix_timestamp >= self.start_unix_timestamp
+    }
+
+    /// Moves the escrow on to `next`. Only an open escrow moves, and never back to `Active`
+    pub fn transition(&mut self, next: EscrowStatus) -> Result<(), EscrowError> {
+        if !self.status.is_open() || next == EscrowStatus::Active {
+            return Err(EscrowError::InvalidStateTransition);
+        }
+        self.status = next;
+        Ok(())
+    }
+
+    /// The basket's vaults in use, empty for an escrow of a single token
+    pub fn basket_vaults(&self) -> &[Pubkey] {
+        &self.basket[..(self.basket_len as usize).min(MAX_BASKET_LEN)]
+    }
+
+    /// Whether `taker` may take the escrow, always true unless it names an allowed taker
+    pub fn is_allowed_taker(&self, taker: &Pubkey) -> bool {
+        self.allowed_taker == Pubkey::default() || self.allowed_taker == *taker
+    }
+
+    /// What a taker pays for `fill` of the offered tokens. A whole fill pays the expected amount,
+    /// a part pays its share of it rounded up, so splitting an order never costs the initializer
+    pub fn payment_for_fill(&self, fill: u64) -> Result<u64, EscrowError> {
+        if fill == 0 || fill > self.offered_amount {
+            return Err(EscrowError::InvalidFillAmount);
+        }
+        if fill == self.offered_amount {
+            return Ok(self.expected_amount);
+        }
+        // dust fills would only churn the escrow, but the last of it may go however small
+        if fill < self.min_fill_amount {
+            return Err(EscrowError::FillTooSmall);
+        }
+        if let Some(price) = self.price {
+            return price.payment_for(fill);
+        }
+        // below expected_amount, since fill is below offered_amount
+        let scaled = fill as u128 * self.expected_amount as u128;
+        let payment = match self.rounding {
+            RoundingMode::FloorToMaker => scaled.div_ceil(self.offered_amount as u128),
+            RoundingMode::FloorToTaker => scaled / self.offered_amount as u128,
+        };
+        u64::try_from(payment).map_err(|_| EscrowError::AmountOverflow)
+    }
+
+    /// The part of `payment` that goes to the fee account, rounded as `rounding` says
+    pub fn fee_for(&self, payment: u64) -> Result<u64, EscrowError> {
+        let scaled = payment
+            .checked_mul(self.fee_basis_points as u64)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Ok(match self.rounding {
+            RoundingMode::FloorToMaker => scaled / MAX_FEE_BASIS_POINTS as u64,
+            RoundingMode::FloorToTaker => scaled.div_ceil(MAX_FEE_BASIS_POINTS as u64),
+        })
+    }
+
+    /// `owner`'s associated account for the offered tokens, where a taker receives them
+    #[cfg(feature = "create-receive-account")]
+    pub fn offered_associated_account(&self, owner: &Pubkey) -> Pubkey {
+        token::associated_address(owner, &self.deposited_mint, &self.token_program)
+    }
+
+    /// `owner`'s associated account for the expected tokens, where an initializer is paid
+    #[cfg(feature = "create-receive-account")]
+    pub fn expected_associated_account(&self, owner: &Pubkey) -> Pubkey {
+        token::associated_address(owner, &self.expected_mint, &self.token_program)
+    }
+}
+
+impl Escrow {
+    /// The names of the fields that differ between `self` and `other`, in layout order
+    pub fn diff(&self, other: &Escrow) -> Vec<&'static str> {
+        let fields = [
+            ("version", self.version != other.version),
+            ("is_initialized", self.is_initialized != other.is_initialized),
+            ("initializer_pubkey", self.initializer_pubkey != other.initializer_pubkey),
+            (
+                "temp_token_account_pubkey",
+                self.temp_token_account_pubkey != other.temp_token_account_pubkey,
+            ),
+            (
+                "initializer_token_to_receive_account_pubkey",
+                self.initializer_token_to_receive_account_pubkey
+                    != other.initializer_token_to_receive_account_pubkey,
+            ),
+            ("expected_amount", self.expected_amount != other.expected_amount),
+            ("offered_amount", self.offered_amount != other.offered_amount),
+            ("arbiter", self.arbiter != other.arbiter),
+            ("price", self.price != other.price),
+            ("side", self.side != other.side),
+            ("bump", self.bump != other.bump),
+            (
+                "expiry_unix_timestamp",
+                self.expiry_unix_timestamp != other.expiry_unix_timestamp,
+            ),
+            ("expected_mint", self.expected_mint != other.expected_mint),
+            ("deposited_mint", self.deposited_mint != other.deposited_mint),
+            ("fee_basis_points", self.fee_basis_points != other.fee_basis_points),
+            ("fee_account", self.fee_account != other.fee_account),
+            (
+                "deposited_is_native",
+                self.deposited_is_native != other.deposited_is_native,
+            ),
+            ("token_program", self.token_program != other.token_program),
+            ("allowed_taker", self.allowed_taker != other.allowed_taker),
+            ("status", self.status != other.status),
+            ("basket_len", self.basket_len != other.basket_len),
+            ("basket", self.basket != other.basket),
+            (
+                "start_unix_timestamp",
+                self.start_unix_timestamp != other.start_unix_timestamp,
+            ),
+            ("auction", self.auction != other.auction),
+            ("min_fill_amount", self.min_fill_amount != other.min_fill_amount),
+            ("rounding", self.rounding != other.rounding),
+        ];
+        fields
+            .iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+/// One field a line, the optional ones only when set, for reading state off-chain
+impl fmt::Display for Escrow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "escrow v{}, {:?}", self.version, self.status)?;
+        writeln!(f, "initializer: {}", self.initializer_pubkey)?;
+        writeln!(f, "temp token account: {}", self.temp_token_account_pubkey)?;
+        writeln!(
+            f,
+            "receive account: {}",
+            self.initializer_token_to_receive_account_pubkey
+        )?;
+        writeln!(f, "offered: {} of {}", self.offered_amount, self.deposited_mint)?;
+        writeln!(f, "expected: {} of {}", self.expected_amount, self.expected_mint)?;
+        writeln!(f, "side: {:?}", self.side)?;
+        writeln!(f, "token program: {}", self.token_program)?;
+        writeln!(f, "fee: {} bp to {}", self.fee_basis_points, self.fee_account)?;
+        if self.deposited_is_native {
+            writeln!(f, "offer held as lamports")?;
+        }
+        if let Some(arbiter) = self.arbiter {
+            writeln!(f, "arbiter: {}", arbiter)?;
+        }
+        if let Some(price) = self.price {
+            writeln!(f, "price: {}/{}", price.numerator, price.denominator)?;
+        }
+        if let Some(auction) = self.auction {
+            writeln!(
+                f,
+                "auction: {} at {} falling to {} at {}",
+                auction.start_amount,
+                auction.start_unix_timestamp,
+                auction.end_amount,
+                auction.end_unix_timestamp
+            )?;
+        }
+        if self.allowed_taker != Pubkey::default() {
+            writeln!(f, "allowed taker: {}", self.allowed_taker)?;
+        }
+        for vault in self.basket_vaults() {
+            writeln!(f, "basket vault: {}", vault)?;
+        }
+        if self.start_unix_timestamp != 0 {
+            writeln!(f, "starts: {}", self.start_unix_timestamp)?;
+        }
+        if self.expiry_unix_timestamp != 0 {
+            writeln!(f, "expires: {}", self.expiry_unix_timestamp)?;
+        }
+        if self.min_fill_amount != 0 {
+            writeln!(f, "min fill: {}", self.min_fill_amount)?;
+        }
+        if self.rounding != RoundingMode::default() {
+            writeln!(f, "rounding: {:?}", self.rounding)?;
+        }
+        write!(f, "bump: {}", self.bump)
+    }
+}
+
+/// Renders escrow account data for client tooling. Any layout `unpack_unchecked` reads will
+/// do, a never initialized account included
+pub fn summarize(data: &[u8]) -> Result<String, ProgramError> {
+    Ok(Escrow::unpack_unchecked(data)?.to_string())
+}
+
+// byte offsets of each field in the packed layout, see `Pack` below
+const DISCRIMINATOR_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 8;
+const IS_INITIALIZED_OFFSET: usize = 9;
+const INITIALIZER_PUBKEY_OFFSET: usize = 10;
+const TEMP_TOKEN_ACCOUNT_PUBKEY_OFFSET: usize = 42;
+const INITIALIZER_TOKEN_TO_RECEIVE_ACCOUNT_PUBKEY_OFFSET: usize = 74;
+const EXPECTED_AMOUNT_OFFSET: usize = 106;
+const OFFERED_AMOUNT_OFFSET: usize = 114;
+const ARBITER_OFFSET: usize = 122;
+const PRICE_OFFSET: usize = 155;
+const SIDE_OFFSET: usize = 172;
+const BUMP_OFFSET: usize = 173;
+const EXPIRY_UNIX_TIMESTAMP_OFFSET: usize = 174;
+const EXPECTED_MINT_OFFSET: usize = 182;
+const DEPOSITED_MINT_OFFSET: usize = 214;
+const FEE_BASIS_POINTS_OFFSET: usize = 246;
+const FEE_ACCOUNT_OFFSET: usize = 248;
+const DEPOSITED_IS_NATIVE_OFFSET: usize = 280;
+const TOKEN_PROGRAM_OFFSET: usize = 281;
+const ALLOWED_TAKER_OFFSET: usize = 313;
+const STATUS_OFFSET: usize = 345;
+const BASKET_LEN_OFFSET: usize = 346;
+const BASKET_OFFSET: usize = 347;
+const START_UNIX_TIMESTAMP_OFFSET: usize = 443;
+const AUCTION_OFFSET: usize = 451;
+const MIN_FILL_AMOUNT_OFFSET: usize = 484;
+const ROUNDING_OFFSET: usize = 492;
+
+/// Single-field reads straight from packed account data, so indexers scanning many
+/// accounts don't have to unpack every field of each one. They read the current layout
+/// only, discriminator and all; an older account has to go through `unpack` or be
+/// migrated first.
+impl Escrow {
+    /// Length of a v0 account, the original program's, which ends at `expected_amount` and
+    /// has no discriminator or version byte
+    pub const V0_LEN: usize = 105;
+
+    fn packed(src: &[u8]) -> Result<&[u8; Escrow::LEN], ProgramError> {
+        if src.len() != Escrow::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_discriminator(src)?;
+        check_version(src[VERSION_OFFSET])?;
+        Ok(array_ref![src, 0, Escrow::LEN])
+    }
+
+    /// Refuses account data of the current layout that doesn't open with
+    /// `ESCROW_DISCRIMINATOR`. A v0 account was written before it and passes, its
+    /// length already tells it apart
+    pub fn check_discriminator(src: &[u8]) -> Result<(), ProgramError> {
+        if src.len() == Escrow::LEN
+            && src[DISCRIMINATOR_OFFSET..VERSION_OFFSET] != ESCROW_DISCRIMINATOR
+        {
+            return Err(EscrowError::InvalidAccountDiscriminator.into());
+        }
+        Ok(())
+    }
+
+    pub fn version_from_bytes(src: &[u8]) -> Result<u8, ProgramError> {
+        Ok(Self::packed(src)?[VERSION_OFFSET])
+    }
+
+    pub fn is_initialized_from_bytes(src: &[u8]) -> Result<bool, ProgramError> {
+        match Self::packed(src)?[IS_INITIALIZED_OFFSET] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn initializer_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            INITIALIZER_PUBKEY_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn temp_token_account_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            TEMP_TOKEN_ACCOUNT_PUBKEY_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn initializer_token_to_receive_account_from_bytes(
+        src: &[u8],
+    ) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            INITIALIZER_TOKEN_TO_RECEIVE_ACCOUNT_PUBKEY_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn expected_amount_from_bytes(src: &[u8]) -> Result<u64, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(u64::from_le_bytes(*array_ref![
+            src,
+            EXPECTED_AMOUNT_OFFSET,
+            8
+        ]))
+    }
+
+    pub fn offered_amount_from_bytes(src: &[u8]) -> Result<u64, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(u64::from_le_bytes(*array_ref![
+            src,
+            OFFERED_AMOUNT_OFFSET,
+            8
+        ]))
+    }
+
+    pub fn arbiter_from_bytes(src: &[u8]) -> Result<Option<Pubkey>, ProgramError> {
+        let src = Self::packed(src)?;
+        unpack_option_key(array_ref![src, ARBITER_OFFSET, 33])
+    }
+
+    pub fn price_from_bytes(src: &[u8]) -> Result<Option<Price>, ProgramError> {
+        let src = Self::packed(src)?;
+        unpack_option_price(array_ref![src, PRICE_OFFSET, 17])
+    }
+
+    pub fn side_from_bytes(src: &[u8]) -> Result<Side, ProgramError> {
+        Ok(Side::from_u8(Self::packed(src)?[SIDE_OFFSET])?)
+    }
+
+    pub fn bump_from_bytes(src: &[u8]) -> Result<u8, ProgramError> {
+        Ok(Self::packed(src)?[BUMP_OFFSET])
+    }
+
+    pub fn expiry_unix_timestamp_from_bytes(src: &[u8]) -> Result<i64, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(i64::from_le_bytes(*array_ref![
+            src,
+            EXPIRY_UNIX_TIMESTAMP_OFFSET,
+            8
+        ]))
+    }
+
+    pub fn expected_mint_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            EXPECTED_MINT_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn deposited_mint_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            DEPOSITED_MINT_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn fee_basis_points_from_bytes(src: &[u8]) -> Result<u16, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(u16::from_le_bytes(*array_ref![
+            src,
+            FEE_BASIS_POINTS_OFFSET,
+            2
+        ]))
+    }
+
+    pub fn fee_account_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            FEE_ACCOUNT_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn deposited_is_native_from_bytes(src: &[u8]) -> Result<bool, ProgramError> {
+        match Self::packed(src)?[DEPOSITED_IS_NATIVE_OFFSET] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn token_program_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            TOKEN_PROGRAM_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn allowed_taker_from_bytes(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(Pubkey::new_from_array(*array_ref![
+            src,
+            ALLOWED_TAKER_OFFSET,
+            32
+        ]))
+    }
+
+    pub fn status_from_bytes(src: &[u8]) -> Result<EscrowStatus, ProgramError> {
+        EscrowStatus::from_u8(Self::packed(src)?[STATUS_OFFSET])
+    }
+
+    pub fn basket_len_from_bytes(src: &[u8]) -> Result<u8, ProgramError> {
+        check_basket_len(Self::packed(src)?[BASKET_LEN_OFFSET])
+    }
+
+    pub fn basket_from_bytes(src: &[u8]) -> Result<[Pubkey; MAX_BASKET_LEN], ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(unpack_basket(array_ref![src, BASKET_OFFSET, 32 * MAX_BASKET_LEN]))
+    }
+
+    pub fn start_unix_timestamp_from_bytes(src: &[u8]) -> Result<i64, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(i64::from_le_bytes(*array_ref![
+            src,
+            START_UNIX_TIMESTAMP_OFFSET,
+            8
+        ]))
+    }
+
+    pub fn auction_from_bytes(src: &[u8]) -> Result<Option<DutchAuction>, ProgramError> {
+        let src = Self::packed(src)?;
+        unpack_option_auction(array_ref![src, AUCTION_OFFSET, 33])
+    }
+
+    pub fn min_fill_amount_from_bytes(src: &[u8]) -> Result<u64, ProgramError> {
+        let src = Self::packed(src)?;
+        Ok(u64::from_le_bytes(*array_ref![
+            src,
+            MIN_FILL_AMOUNT_OFFSET,
+            8
+        ]))
+    }
+
+    pub fn rounding_from_bytes(src: &[u8]) -> Result<RoundingMode, ProgramError> {
+        RoundingMode::from_u8(Self::packed(src)?[ROUNDING_OFFSET])
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+}
+
+// an account that was never written still has a zero version byte
+fn check_version(version: u8) -> Result<u8, ProgramError> {
+    if version > ESCROW_VERSION {
+        return Err(EscrowError::UnsupportedVersion.into());
+    }
+    Ok(version)
+}
+
+// a basket can't name more vaults than it has room for
+fn check_basket_len(basket_len: u8) -> Result<u8, ProgramError> {
+    if basket_len as usize > MAX_BASKET_LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(basket_len)
+}
+
+fn unpack_basket(src: &[u8; 32 * MAX_BASKET_LEN]) -> [Pubkey; MAX_BASKET_LEN] {
+    let mut basket = [Pubkey::default(); MAX_BASKET_LEN];
+    for (vault, key) in basket.iter_mut().zip(src.chunks_exact(32)) {
+        *vault = Pubkey::new(key);
+    }
+    basket
+}
+
+// an optional key is a 0/1 tag byte followed by the key, zeroed when absent
+fn unpack_option_key(src: &[u8; 33]) -> Result<Option<Pubkey>, ProgramError> {
+    let (tag, key) = array_refs![src, 1, 32];
+    match tag {
+        [0] => Ok(None),
+        [1] => Ok(Some(Pubkey::new_from_array(*key))),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// an optional price is a 0/1 tag byte followed by numerator and denominator, zeroed when absent
+fn unpack_option_price(src: &[u8; 17]) -> Result<Option<Price>, ProgramError> {
+    let (tag, numerator, denominator) = array_refs![src, 1, 8, 8];
+    match tag {
+        [0] => Ok(None),
+        [1] => Ok(Some(Price {
+            numerator: u64::from_le_bytes(*numerator),
+            denominator: u64::from_le_bytes(*denominator),
+        })),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// an optional auction is a 0/1 tag byte followed by its amounts and window, zeroed when absent
+fn unpack_option_auction(src: &[u8; 33]) -> Result<Option<DutchAuction>, ProgramError> {
+    let (tag, start_amount, end_amount, start_unix_timestamp, end_unix_timestamp) =
+        array_refs![src, 1, 8, 8, 8, 8];
+    match tag {
+        [0] => Ok(None),
+        [1] => Ok(Some(DutchAuction {
+            start_amount: u64::from_le_bytes(*start_amount),
+            end_amount: u64::from_le_bytes(*end_amount),
+            start_unix_timestamp: i64::from_le_bytes(*start_unix_timestamp),
+            end_unix_timestamp: i64::from_le_bytes(*end_unix_timestamp),
+        })),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn pack_option_auction(src: &Option<DutchAuction>, dst: &mut [u8; 33]) {
+    let (tag, start_amount, end_amount, start_unix_timestamp, end_unix_timestamp) =
+        mut_array_refs![dst, 1, 8, 8, 8, 8];
+    match src {
+        Some(auction) => {
+            tag[0] = 1;
+            *start_amount = auction.start_amount.to_le_bytes();
+            *end_amount = auction.end_amount.to_le_bytes();
+            *start_unix_timestamp = auction.start_unix_timestamp.to_le_bytes();
+            *end_unix_timestamp = auction.end_unix_timestamp.to_le_bytes();
+        }
+        None => {
+            tag[0] = 0;
+            *start_amount = [0; 8];
+            *end_amount = [0; 8];
+            *start_unix_timestamp = [0; 8];
+            *end_unix_timestamp = [0; 8];
+        }
+    }
+}
+
+fn pack_option_price(src: &Option<Price>, dst: &mut [u8; 17]) {
+    let (tag, numerator, denominator) = mut_array_refs![dst, 1, 8, 8];
+    match src {
+        Some(price) => {
+            tag[0] = 1;
+            *numerator = price.numerator.to_le_bytes();
+            *denominator = price.denominator.to_le_bytes();
+        }
+        None => {
+            tag[0] = 0;
+            *numerator = [0; 8];
+            *denominator = [0; 8];
+        }
+    }
+}
+
+fn pack_option_key(src: &Option<Pubkey>, dst: &mut [u8; 33]) {
+    let (tag, key) = mut_array_refs![dst, 1, 32];
+    match src {
+        Some(pubkey) => {
+            tag[0] = 1;
+            key.copy_from_slice(pubkey.as_ref());
+        }
+        None => {
+            tag[0] = 0;
+            *key = [0; 32];
+        }
+    }
 }
 
 impl Sealed for Escrow {}
@@ -23,16 +905,70 @@ impl IsInitialized for Escrow {
 }
 
 impl Pack for Escrow {
-    const LEN: usize = 105;
+    const LEN: usize = 493;
+
+    // as `Pack::unpack`, but an account of the current layout must carry the discriminator
+    fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let escrow = Self::unpack_unchecked(input)?;
+        if !escrow.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Self::check_discriminator(input)?;
+        Ok(escrow)
+    }
+
+    // a v0 account is read as if padded out to this one, with a zero version byte in front
+    // and the fields it lacks zeroed except the token program, which was always spl-token;
+    // a zero status is `Active`. The current layout's discriminator isn't checked, so a
+    // fresh zeroed account reads as uninitialized
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        let mut current = [0; Escrow::LEN];
+        if input.len() == Escrow::V0_LEN {
+            current[TOKEN_PROGRAM_OFFSET..ALLOWED_TAKER_OFFSET]
+                .copy_from_slice(spl_token::id().as_ref());
+            current[IS_INITIALIZED_OFFSET..][..Escrow::V0_LEN].copy_from_slice(input);
+        } else if input.len() == Escrow::LEN {
+            current.copy_from_slice(input);
+        } else {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::unpack_from_slice(&current)
+    }
+
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Escrow::LEN];
         let (
+            _discriminator,
+            version,
             is_initialized,
             initializer_pubkey,
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
-        ) = array_refs![src, 1, 32, 32, 32, 8];
+            offered_amount,
+            arbiter,
+            price,
+            side,
+            bump,
+            expiry_unix_timestamp,
+            expected_mint,
+            deposited_mint,
+            fee_basis_points,
+            fee_account,
+            deposited_is_native,
+            token_program,
+            allowed_taker,
+            status,
+            basket_len,
+            basket,
+            start_unix_timestamp,
+            auction,
+            min_fill_amount,
+            rounding,
+        ) = array_refs![
+            src, 8, 1, 1, 32, 32, 32, 8, 8, 33, 17, 1, 1, 8, 32, 32, 2, 32, 1, 32, 32, 1, 1,
+            32 * MAX_BASKET_LEN, 8, 33, 8, 1
+        ];
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
@@ -40,6 +976,7 @@ impl Pack for Escrow {
         };
 
         Ok(Escrow {
+            version: check_version(version[0])?,
             is_initialized,
             initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
             temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
@@ -47,32 +984,976 @@ impl Pack for Escrow {
                 *initializer_token_to_receive_account_pubkey,
             ),
             expected_amount: u64::from_le_bytes(*expected_amount),
+            offered_amount: u64::from_le_bytes(*offered_amount),
+            arbiter: unpack_option_key(arbiter)?,
+            price: unpack_option_price(price)?,
+            side: Side::from_u8(side[0])?,
+            bump: bump[0],
+            expiry_unix_timestamp: i64::from_le_bytes(*expiry_unix_timestamp),
+            expected_mint: Pubkey::new_from_array(*expected_mint),
+            deposited_mint: Pubkey::new_from_array(*deposited_mint),
+            fee_basis_points: u16::from_le_bytes(*fee_basis_points),
+            fee_account: Pubkey::new_from_array(*fee_account),
+            deposited_is_native: match deposited_is_native {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            token_program: Pubkey::new_from_array(*token_program),
+            allowed_taker: Pubkey::new_from_array(*allowed_taker),
+            status: EscrowStatus::from_u8(status[0])?,
+            basket_len: check_basket_len(basket_len[0])?,
+            basket: unpack_basket(basket),
+            start_unix_timestamp: i64::from_le_bytes(*start_unix_timestamp),
+            auction: unpack_option_auction(auction)?,
+            min_fill_amount: u64::from_le_bytes(*min_fill_amount),
+            rounding: RoundingMode::from_u8(rounding[0]).ok_or(ProgramError::InvalidAccountData)?,
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, Escrow::LEN];
         let (
+            discriminator_dst,
+            version_dst,
             is_initialized_dst,
             initializer_pubkey_dst,
             temp_token_account_pubkey_dst,
             initializer_token_to_receive_account_pubkey_dst,
             expected_amount_dst,
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8];
+            offered_amount_dst,
+            arbiter_dst,
+            price_dst,
+            side_dst,
+            bump_dst,
+            expiry_unix_timestamp_dst,
+            expected_mint_dst,
+            deposited_mint_dst,
+            fee_basis_points_dst,
+            fee_account_dst,
+            deposited_is_native_dst,
+            token_program_dst,
+            allowed_taker_dst,
+            status_dst,
+            basket_len_dst,
+            basket_dst,
+            start_unix_timestamp_dst,
+            auction_dst,
+            min_fill_amount_dst,
+            rounding_dst,
+        ) = mut_array_refs![
+            dst, 8, 1, 1, 32, 32, 32, 8, 8, 33, 17, 1, 1, 8, 32, 32, 2, 32, 1, 32, 32, 1, 1,
+            32 * MAX_BASKET_LEN, 8, 33, 8, 1
+        ];
 
         let Escrow {
+            version,
             is_initialized,
             initializer_pubkey,
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
+            offered_amount,
+            arbiter,
+            price,
+            side,
+            bump,
+            expiry_unix_timestamp,
+            expected_mint,
+            deposited_mint,
+            fee_basis_points,
+            fee_account,
+            deposited_is_native,
+            token_program,
+            allowed_taker,
+            status,
+            basket_len,
+            basket,
+            start_unix_timestamp,
+            auction,
+            min_fill_amount,
+            rounding,
         } = self;
 
+        *discriminator_dst = ESCROW_DISCRIMINATOR;
+        version_dst[0] = *version;
         is_initialized_dst[0] = *is_initialized as u8;
         initializer_pubkey_dst.copy_from_slice(initializer_pubkey.as_ref());
         temp_token_account_pubkey_dst.copy_from_slice(temp_token_account_pubkey.as_ref());
         initializer_token_to_receive_account_pubkey_dst
             .copy_from_slice(initializer_token_to_receive_account_pubkey.as_ref());
         *expected_amount_dst = expected_amount.to_le_bytes();
+        *offered_amount_dst = offered_amount.to_le_bytes();
+        pack_option_key(arbiter, arbiter_dst);
+        pack_option_price(price, price_dst);
+        side_dst[0] = *side as u8;
+        bump_dst[0] = *bump;
+        *expiry_unix_timestamp_dst = expiry_unix_timestamp.to_le_bytes();
+        expected_mint_dst.copy_from_slice(expected_mint.as_ref());
+        deposited_mint_dst.copy_from_slice(deposited_mint.as_ref());
+        *fee_basis_points_dst = fee_basis_points.to_le_bytes();
+        fee_account_dst.copy_from_slice(fee_account.as_ref());
+        deposited_is_native_dst[0] = *deposited_is_native as u8;
+        token_program_dst.copy_from_slice(token_program.as_ref());
+        allowed_taker_dst.copy_from_slice(allowed_taker.as_ref());
+        status_dst[0] = *status as u8;
+        basket_len_dst[0] = *basket_len;
+        for (dst, vault) in basket_dst.chunks_exact_mut(32).zip(basket.iter()) {
+            dst.copy_from_slice(vault.as_ref());
+        }
+        *start_unix_timestamp_dst = start_unix_timestamp.to_le_bytes();
+        pack_option_auction(auction, auction_dst);
+        *min_fill_amount_dst = min_fill_amount.to_le_bytes();
+        rounding_dst[0] = *rounding as u8;
+    }
+}
+
+/// Program-wide settings, held at `config_address`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Config {
+    pub is_initialized: bool,
+    /// The only key that may change the config
+    pub admin: Pubkey,
+    /// While set, no escrow can be made or taken
+    pub paused: bool,
+    /// Seconds an unpause waits before escrows can be made or taken again. Fixed by
+    /// InitConfig, so the admin can't shorten it later
+    pub unpause_delay: i64,
+    /// Unix time the last unpause takes effect at, `0` if the program was never unpaused
+    pub pause_until: i64,
+    /// Seconds past the time of init that an escrow's expiry may be set to at most
+    pub max_expiry_horizon_secs: i64,
+}
+
+impl Sealed for Config {}
+
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Config {
+    const LEN: usize = 58;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Config::LEN];
+        let (is_initialized, admin, paused, unpause_delay, pause_until, max_expiry_horizon_secs) =
+            array_refs![src, 1, 32, 1, 8, 8, 8];
+        let read_bool = |byte: &[u8; 1]| match byte {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Config {
+            is_initialized: read_bool(is_initialized)?,
+            admin: Pubkey::new_from_array(*admin),
+            paused: read_bool(paused)?,
+            unpause_delay: i64::from_le_bytes(*unpause_delay),
+            pause_until: i64::from_le_bytes(*pause_until),
+            max_expiry_horizon_secs: i64::from_le_bytes(*max_expiry_horizon_secs),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Config::LEN];
+        let (
+            is_initialized_dst,
+            admin_dst,
+            paused_dst,
+            unpause_delay_dst,
+            pause_until_dst,
+            max_expiry_horizon_secs_dst,
+        ) = mut_array_refs![dst, 1, 32, 1, 8, 8, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        admin_dst.copy_from_slice(self.admin.as_ref());
+        paused_dst[0] = self.paused as u8;
+        *unpause_delay_dst = self.unpause_delay.to_le_bytes();
+        *pause_until_dst = self.pause_until.to_le_bytes();
+        *max_expiry_horizon_secs_dst = self.max_expiry_horizon_secs.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "create-receive-account")]
+    use std::str::FromStr;
+
+    fn packed_escrow() -> (Escrow, Vec<u8>) {
+        let escrow = Escrow {
+            version: ESCROW_VERSION,
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new_from_array([1; 32]),
+            temp_token_account_pubkey: Pubkey::new_from_array([2; 32]),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array([3; 32]),
+            expected_amount: 0x0102_0304_0506_0708,
+            offered_amount: 0x1112_1314_1516_1718,
+            arbiter: Some(Pubkey::new_from_array([4; 32])),
+            price: Some(Price {
+                numerator: 0x2122_2324_2526_2728,
+                denominator: 0x3132_3334_3536_3738,
+            }),
+            side: Side::Buy,
+            bump: 0xfe,
+            expiry_unix_timestamp: -0x4142_4344_4546_4748,
+            expected_mint: Pubkey::new_from_array([5; 32]),
+            deposited_mint: Pubkey::new_from_array([6; 32]),
+            fee_basis_points: 0x5152,
+            fee_account: Pubkey::new_from_array([7; 32]),
+            deposited_is_native: true,
+            token_program: Pubkey::new_from_array([8; 32]),
+            allowed_taker: Pubkey::new_from_array([9; 32]),
+            status: EscrowStatus::PartiallyFilled,
+            basket_len: 2,
+            basket: [
+                Pubkey::new_from_array([10; 32]),
+                Pubkey::new_from_array([11; 32]),
+                Pubkey::new_from_array([12; 32]),
+            ],
+            start_unix_timestamp: 0x6162_6364_6566_6768,
+            auction: Some(DutchAuction {
+                start_amount: 0x7172_7374_7576_7778,
+                end_amount: 0x0102_0304_0506_0708,
+                start_unix_timestamp: 0x1112_1314_1516_1718,
+                end_unix_timestamp: 0x2122_2324_2526_2728,
+            }),
+            min_fill_amount: 0x4142_4344_4546_4748,
+            rounding: RoundingMode::FloorToTaker,
+        };
+        let mut data = vec![0; Escrow::LEN];
+        Escrow::pack(escrow, &mut data).unwrap();
+        (Escrow::unpack(&data).unwrap(), data)
+    }
+
+    /// Pins the account layout; a failure here means escrows already on chain would
+    /// no longer read back.
+    #[test]
+    fn packed_layout_is_stable() {
+        let (_, data) = packed_escrow();
+        let expected = [
+            &ESCROW_DISCRIMINATOR[..],
+            &[1],
+            &[1],
+            &[1; 32],
+            &[2; 32],
+            &[3; 32],
+            &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01],
+            &[0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11],
+            &[1],
+            &[4; 32],
+            &[1],
+            &[0x28, 0x27, 0x26, 0x25, 0x24, 0x23, 0x22, 0x21],
+            &[0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31],
+            &[1],
+            &[0xfe],
+            &[0xb8, 0xb8, 0xb9, 0xba, 0xbb, 0xbc, 0xbd, 0xbe],
+            &[5; 32],
+            &[6; 32],
+            &[0x52, 0x51],
+            &[7; 32],
+            &[1],
+            &[8; 32],
+            &[9; 32],
+            &[1],
+            &[2],
+            &[10; 32],
+            &[11; 32],
+            &[12; 32],
+            &[0x68, 0x67, 0x66, 0x65, 0x64, 0x63, 0x62, 0x61],
+            &[1],
+            &[0x78, 0x77, 0x76, 0x75, 0x74, 0x73, 0x72, 0x71],
+            &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01],
+            &[0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11],
+            &[0x28, 0x27, 0x26, 0x25, 0x24, 0x23, 0x22, 0x21],
+            &[0x48, 0x47, 0x46, 0x45, 0x44, 0x43, 0x42, 0x41],
+            &[1],
+        ]
+        .concat();
+        assert_eq!(data, expected);
+
+        // an absent option packs as its tag and zeroes, leaving every other field in place
+        let mut empty = vec![0; Escrow::LEN];
+        Escrow {
+            arbiter: None,
+            price: None,
+            ..Escrow::unpack_unchecked(&data).unwrap()
+        }
+        .pack_into_slice(&mut empty);
+        assert!(empty[ARBITER_OFFSET..SIDE_OFFSET].iter().all(|byte| *byte == 0));
+        assert_eq!(&empty[..ARBITER_OFFSET], &data[..ARBITER_OFFSET]);
+        assert_eq!(&empty[SIDE_OFFSET..], &data[SIDE_OFFSET..]);
+    }
+
+    #[test]
+    fn unpack_reads_the_original_layout_and_rejects_unknown_versions() {
+        let (escrow, mut data) = packed_escrow();
+
+        // a v0 account is the original program's fields, with no discriminator or version byte
+        let v0 = Escrow::unpack(&data[IS_INITIALIZED_OFFSET..][..Escrow::V0_LEN]).unwrap();
+        assert_eq!(v0.version, 0);
+        assert_eq!(
+            escrow.diff(&v0),
+            [
+                "version",
+                "offered_amount",
+                "arbiter",
+                "price",
+                "side",
+                "bump",
+                "expiry_unix_timestamp",
+                "expected_mint",
+                "deposited_mint",
+                "fee_basis_points",
+                "fee_account",
+                "deposited_is_native",
+                "token_program",
+                "allowed_taker",
+                "status",
+                "basket_len",
+                "basket",
+                "start_unix_timestamp",
+                "auction",
+                "min_fill_amount",
+                "rounding"
+            ]
+        );
+        assert_eq!(v0.expected_amount, escrow.expected_amount);
+        assert_eq!(v0.token_program, spl_token::id());
+        assert_eq!(v0.status, EscrowStatus::Active);
+        assert!(v0.basket_vaults().is_empty());
+
+        // no other length is an escrow, whatever it starts with
+        for len in [Escrow::V0_LEN - 1, Escrow::V0_LEN + 1, Escrow::LEN - 1] {
+            assert_eq!(
+                Escrow::unpack(&data[..len]),
+                Err(ProgramError::InvalidAccountData),
+                "{} bytes",
+                len
+            );
+        }
+
+        data[VERSION_OFFSET] = ESCROW_VERSION + 1;
+        assert_eq!(
+            Escrow::unpack(&data),
+            Err(EscrowError::UnsupportedVersion.into())
+        );
+        assert_eq!(
+            Escrow::offered_amount_from_bytes(&data),
+            Err(EscrowError::UnsupportedVersion.into())
+        );
+
+        // a blank account has no version yet and unpacks as uninitialized
+        let blank = Escrow::unpack_unchecked(&[0; Escrow::LEN]).unwrap();
+        assert_eq!(blank.version, 0);
+        assert!(!blank.is_initialized);
+    }
+
+    #[test]
+    fn front_run_safe_requires_at_least_the_minimum() {
+        let (escrow, _) = packed_escrow();
+
+        assert!(front_run_safe(&escrow, escrow.offered_amount));
+        assert!(front_run_safe(&escrow, escrow.offered_amount - 1));
+        assert!(front_run_safe(&escrow, 0));
+        assert!(!front_run_safe(&escrow, escrow.offered_amount + 1));
+    }
+
+    #[test]
+    fn zero_expiry_never_expires() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.expiry_unix_timestamp = 0;
+        assert!(!escrow.is_expired(i64::MAX));
+
+        escrow.expiry_unix_timestamp = 1_000;
+        assert!(!escrow.is_expired(999));
+        assert!(!escrow.is_expired(1_000));
+        assert!(escrow.is_expired(1_001));
+    }
+
+    #[test]
+    fn zero_start_is_taken_at_once() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.start_unix_timestamp = 0;
+        assert!(escrow.has_started(i64::MIN));
+
+        escrow.start_unix_timestamp = 1_000;
+        assert!(!escrow.has_started(999));
+        assert!(escrow.has_started(1_000));
+        assert!(escrow.has_started(1_001));
+    }
+
+    #[cfg(feature = "create-receive-account")]
+    #[test]
+    fn associated_accounts_follow_the_escrow_mints_and_token_program() {
+        let (mut escrow, _) = packed_escrow();
+        let owner = Pubkey::new_from_array([1; 32]);
+        escrow.deposited_mint = Pubkey::new_from_array([2; 32]);
+        escrow.expected_mint = Pubkey::new_from_array([3; 32]);
+        escrow.token_program = spl_token::id();
+        let known = |address: &str| Pubkey::from_str(address).unwrap();
+
+        assert_eq!(
+            escrow.offered_associated_account(&owner),
+            known("CsYkfSfTUTWwnoeRkGchtai5kkYz2SC33kKJwA99wVr3")
+        );
+        assert_eq!(
+            escrow.expected_associated_account(&owner),
+            known("HwD4QpS4bsutLbWZWhbmFUZfkXC5Au1DbkzYEzjDgps8")
+        );
+
+        escrow.token_program = token::token_2022::id();
+        assert_eq!(
+            escrow.offered_associated_account(&owner),
+            known("DyaUQ3JTcmWApDibKtBvxLBhUPjvA4KEM99t45qz3bfh")
+        );
+
+        // a native escrow offers wrapped SOL
+        escrow.deposited_mint = spl_token::native_mint::id();
+        escrow.token_program = spl_token::id();
+        assert_eq!(
+            escrow.offered_associated_account(&owner),
+            known("7i4VVk55NzhtekVjPg7EZzoSGznZYixPyd5cCeDxi7rW")
+        );
+    }
+
+    #[test]
+    fn partial_fill_pays_its_share_rounded_up() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.rounding = RoundingMode::FloorToMaker;
+        escrow.price = None;
+        escrow.min_fill_amount = 0;
+        escrow.offered_amount = 3;
+        escrow.expected_amount = 10;
+
+        assert_eq!(escrow.payment_for_fill(1), Ok(4));
+        assert_eq!(escrow.payment_for_fill(2), Ok(7));
+        assert_eq!(escrow.payment_for_fill(3), Ok(10));
+        assert_eq!(escrow.payment_for_fill(0), Err(EscrowError::InvalidFillAmount));
+        assert_eq!(escrow.payment_for_fill(4), Err(EscrowError::InvalidFillAmount));
+
+        escrow.price = Some(Price {
+            numerator: 7,
+            denominator: 2,
+        });
+        assert_eq!(escrow.payment_for_fill(1), Ok(4));
+    }
+
+    #[test]
+    fn rounding_mode_decides_who_keeps_the_remainder() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.price = None;
+        escrow.min_fill_amount = 0;
+        escrow.offered_amount = 3;
+        escrow.expected_amount = 10;
+        escrow.fee_basis_points = 30;
+
+        escrow.rounding = RoundingMode::FloorToMaker;
+        assert_eq!(escrow.payment_for_fill(1), Ok(4));
+        assert_eq!(escrow.fee_for(333), Ok(0));
+
+        escrow.rounding = RoundingMode::FloorToTaker;
+        assert_eq!(escrow.payment_for_fill(1), Ok(3));
+        assert_eq!(escrow.payment_for_fill(2), Ok(6));
+        assert_eq!(escrow.payment_for_fill(3), Ok(10));
+        assert_eq!(escrow.fee_for(333), Ok(1));
+        assert_eq!(escrow.fee_for(1_000), Ok(3));
+        assert_eq!(escrow.fee_for(0), Ok(0));
+    }
+
+    #[test]
+    fn rounding_round_trips_and_rejects_unknown_bytes() {
+        let (mut escrow, mut data) = packed_escrow();
+        assert_eq!(escrow.rounding, RoundingMode::FloorToTaker);
+
+        escrow.rounding = RoundingMode::FloorToMaker;
+        Escrow::pack(escrow, &mut data).unwrap();
+        assert_eq!(Escrow::unpack(&data).unwrap().rounding, RoundingMode::FloorToMaker);
+
+        data[ROUNDING_OFFSET] = 2;
+        assert_eq!(Escrow::unpack(&data).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(
+            Escrow::rounding_from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn partial_fill_below_the_minimum_only_goes_if_it_is_the_last() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.price = None;
+        escrow.offered_amount = 10;
+        escrow.expected_amount = 20;
+        escrow.min_fill_amount = 4;
+
+        assert_eq!(escrow.payment_for_fill(3), Err(EscrowError::FillTooSmall));
+        assert_eq!(escrow.payment_for_fill(4), Ok(8));
+        escrow.offered_amount = 3;
+        escrow.expected_amount = 6;
+        assert_eq!(escrow.payment_for_fill(3), Ok(6));
+        assert_eq!(escrow.payment_for_fill(2), Err(EscrowError::FillTooSmall));
+    }
+
+    #[test]
+    fn fee_rounds_down_and_reports_overflow() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.rounding = RoundingMode::FloorToMaker;
+        escrow.fee_basis_points = 30;
+        assert_eq!(escrow.fee_for(1_000), Ok(3));
+        assert_eq!(escrow.fee_for(333), Ok(0));
+
+        escrow.fee_basis_points = MAX_FEE_BASIS_POINTS;
+        assert_eq!(escrow.fee_for(1_000), Ok(1_000));
+        assert_eq!(escrow.fee_for(u64::MAX), Err(EscrowError::AmountOverflow));
+
+        escrow.fee_basis_points = 0;
+        assert_eq!(escrow.fee_for(u64::MAX), Ok(0));
+    }
+
+    #[test]
+    fn price_rounds_payment_up() {
+        let third = Price {
+            numerator: 1,
+            denominator: 3,
+        };
+        assert_eq!(third.payment_for(0), Ok(0));
+        assert_eq!(third.payment_for(3), Ok(1));
+        assert_eq!(third.payment_for(4), Ok(2));
+        assert_eq!(third.payment_for(u64::MAX), Ok(u64::MAX / 3));
+
+        let ratio = Price {
+            numerator: 7,
+            denominator: 2,
+        };
+        assert_eq!(ratio.payment_for(5), Ok(18));
+    }
+
+    #[test]
+    fn price_rejects_overflow_and_zero_terms() {
+        let double = Price {
+            numerator: 2,
+            denominator: 1,
+        };
+        assert_eq!(double.payment_for(u64::MAX / 2), Ok(u64::MAX - 1));
+        assert_eq!(
+            double.payment_for(u64::MAX / 2 + 1),
+            Err(EscrowError::AmountOverflow)
+        );
+
+        let free = Price {
+            numerator: 0,
+            denominator: 1,
+        };
+        assert_eq!(free.payment_for(10), Err(EscrowError::InvalidPrice));
+        let undefined = Price {
+            numerator: 1,
+            denominator: 0,
+        };
+        assert_eq!(undefined.payment_for(10), Err(EscrowError::InvalidPrice));
+    }
+
+    #[test]
+    fn auction_price_falls_over_its_window() {
+        let auction = DutchAuction {
+            start_amount: 100,
+            end_amount: 40,
+            start_unix_timestamp: 1_000,
+            end_unix_timestamp: 1_060,
+        };
+
+        // held at either end outside the window
+        assert_eq!(auction.payment_at(i64::MIN), Ok(100));
+        assert_eq!(auction.payment_at(1_000), Ok(100));
+        assert_eq!(auction.payment_at(1_060), Ok(40));
+        assert_eq!(auction.payment_at(i64::MAX), Ok(40));
+
+        let mut last = 100;
+        for now in 1_001..1_060 {
+            let payment = auction.payment_at(now).unwrap();
+            assert!(payment <= last && payment > 40);
+            last = payment;
+        }
+        assert_eq!(auction.payment_at(1_030), Ok(70));
+        // the drop still to come rounds up, 1 of 60 seconds left is 1 of 60 tokens
+        assert_eq!(auction.payment_at(1_059), Ok(41));
+
+        let widest = DutchAuction {
+            start_unix_timestamp: i64::MIN,
+            end_unix_timestamp: i64::MAX,
+            ..auction
+        };
+        assert_eq!(widest.payment_at(0), Ok(70));
+
+        for invalid in [
+            DutchAuction {
+                end_amount: 0,
+                ..auction
+            },
+            DutchAuction {
+                end_amount: 101,
+                ..auction
+            },
+            DutchAuction {
+                end_unix_timestamp: 1_000,
+                ..auction
+            },
+        ] {
+            assert!(!invalid.is_valid());
+            assert_eq!(invalid.payment_at(1_030), Err(EscrowError::InvalidPrice));
+        }
+    }
+
+    #[test]
+    fn len_matches_field_layout() {
+        // one term per field, widths as packed; update with every field added to Escrow
+        const PUBKEY: usize = 32;
+        const U64: usize = 8;
+        const I64: usize = 8;
+        const U16: usize = 2;
+        let expected = ESCROW_DISCRIMINATOR.len()
+            + 1 // version
+            + 1 // is_initialized
+            + PUBKEY // initializer_pubkey
+            + PUBKEY // temp_token_account_pubkey
+            + PUBKEY // initializer_token_to_receive_account_pubkey
+            + U64 // expected_amount
+            + U64 // offered_amount
+            + (1 + PUBKEY) // arbiter
+            + (1 + U64 + U64) // price
+            + 1 // side
+            + 1 // bump
+            + I64 // expiry_unix_timestamp
+            + PUBKEY // expected_mint
+            + PUBKEY // deposited_mint
+            + U16 // fee_basis_points
+            + PUBKEY // fee_account
+            + 1 // deposited_is_native
+            + PUBKEY // token_program
+            + PUBKEY // allowed_taker
+            + 1 // status
+            + 1 // basket_len
+            + PUBKEY * MAX_BASKET_LEN // basket
+            + I64 // start_unix_timestamp
+            + (1 + U64 + U64 + I64 + I64) // auction
+            + U64 // min_fill_amount
+            + 1; // rounding
+
+        assert_eq!(Escrow::LEN, expected);
+        assert_eq!(ROUNDING_OFFSET + 1, expected);
+        assert_eq!(VERSION_OFFSET, ESCROW_DISCRIMINATOR.len());
+        // the original layout is every field up to expected_amount, after the version byte
+        assert_eq!(Escrow::V0_LEN, OFFERED_AMOUNT_OFFSET - IS_INITIALIZED_OFFSET);
+        assert_eq!(Escrow::V0_LEN, 1 + PUBKEY * 3 + U64);
+    }
+
+    #[test]
+    fn discriminator_is_written_and_checked() {
+        let (escrow, mut data) = packed_escrow();
+        assert_eq!(
+            ESCROW_DISCRIMINATOR,
+            solana_program::hash::hash(b"account:Escrow").to_bytes()[..8]
+        );
+        assert_eq!(data[..VERSION_OFFSET], ESCROW_DISCRIMINATOR);
+        assert_eq!(Escrow::unpack(&data).as_ref(), Ok(&escrow));
+
+        // the same bytes under any other discriminator are some other account
+        data[DISCRIMINATOR_OFFSET] ^= 1;
+        assert_eq!(
+            Escrow::unpack(&data),
+            Err(EscrowError::InvalidAccountDiscriminator.into())
+        );
+        assert_eq!(
+            Escrow::offered_amount_from_bytes(&data),
+            Err(EscrowError::InvalidAccountDiscriminator.into())
+        );
+        // unchecked, as init reads a fresh account
+        assert_eq!(Escrow::unpack_unchecked(&data), Ok(escrow));
+        assert!(!Escrow::unpack_unchecked(&[0; Escrow::LEN]).unwrap().is_initialized);
+    }
+
+    #[test]
+    fn mark_uninitialized_clears_only_the_flag_in_either_layout() {
+        let (escrow, mut data) = packed_escrow();
+        Escrow::mark_uninitialized(&mut data).unwrap();
+        let settled = Escrow::unpack_unchecked(&data).unwrap();
+        assert_eq!(escrow.diff(&settled), ["is_initialized"]);
+        assert_eq!(Escrow::unpack(&data), Err(ProgramError::UninitializedAccount));
+
+        let mut v0 = [0; Escrow::V0_LEN];
+        v0[0] = 1;
+        assert!(Escrow::unpack(&v0).unwrap().is_initialized);
+        Escrow::mark_uninitialized(&mut v0).unwrap();
+        assert_eq!(v0, [0; Escrow::V0_LEN]);
+        assert_eq!(
+            Escrow::mark_uninitialized(&mut [1; Escrow::LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn diff_names_changed_fields() {
+        let (escrow, data) = packed_escrow();
+        let mut changed = Escrow::unpack(&data).unwrap();
+        assert_eq!(escrow, changed);
+        assert!(escrow.diff(&changed).is_empty());
+
+        changed.expected_amount += 1;
+        changed.arbiter = None;
+        changed.side = Side::Sell;
+
+        assert_ne!(escrow, changed);
+        assert_eq!(escrow.diff(&changed), ["expected_amount", "arbiter", "side"]);
+    }
+
+    #[test]
+    fn summarize_names_every_key_that_is_set() {
+        let (escrow, data) = packed_escrow();
+        let summary = summarize(&data).unwrap();
+        assert_eq!(summary, escrow.to_string());
+
+        for key in [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11] {
+            let key = Pubkey::new_from_array([key; 32]).to_string();
+            assert!(summary.contains(&key), "{} missing from\n{}", key, summary);
+        }
+        // past basket_len, so not a vault of the escrow
+        assert!(!summary.contains(&Pubkey::new_from_array([12; 32]).to_string()));
+        let heading = format!("escrow v{}, PartiallyFilled\n", ESCROW_VERSION);
+        assert!(summary.starts_with(&heading));
+        assert!(summary.contains(&format!("expected: {} of ", 0x0102_0304_0506_0708u64)));
+
+        assert_eq!(summarize(&data[1..]), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn field_reads_match_unpack() {
+        let (escrow, data) = packed_escrow();
+
+        assert_eq!(Escrow::version_from_bytes(&data).unwrap(), escrow.version);
+        assert!(Escrow::is_initialized_from_bytes(&data).unwrap());
+        assert_eq!(
+            Escrow::initializer_from_bytes(&data).unwrap(),
+            escrow.initializer_pubkey
+        );
+        assert_eq!(
+            Escrow::temp_token_account_from_bytes(&data).unwrap(),
+            escrow.temp_token_account_pubkey
+        );
+        assert_eq!(
+            Escrow::initializer_token_to_receive_account_from_bytes(&data).unwrap(),
+            escrow.initializer_token_to_receive_account_pubkey
+        );
+        assert_eq!(
+            Escrow::expected_amount_from_bytes(&data).unwrap(),
+            escrow.expected_amount
+        );
+        assert_eq!(
+            Escrow::offered_amount_from_bytes(&data).unwrap(),
+            escrow.offered_amount
+        );
+        assert_eq!(Escrow::arbiter_from_bytes(&data).unwrap(), escrow.arbiter);
+        assert_eq!(Escrow::price_from_bytes(&data).unwrap(), escrow.price);
+        assert_eq!(Escrow::side_from_bytes(&data).unwrap(), escrow.side);
+        assert_eq!(Escrow::bump_from_bytes(&data).unwrap(), escrow.bump);
+        assert_eq!(
+            Escrow::expiry_unix_timestamp_from_bytes(&data).unwrap(),
+            escrow.expiry_unix_timestamp
+        );
+        assert_eq!(
+            Escrow::expected_mint_from_bytes(&data).unwrap(),
+            escrow.expected_mint
+        );
+        assert_eq!(
+            Escrow::deposited_mint_from_bytes(&data).unwrap(),
+            escrow.deposited_mint
+        );
+        assert_eq!(
+            Escrow::fee_basis_points_from_bytes(&data).unwrap(),
+            escrow.fee_basis_points
+        );
+        assert_eq!(
+            Escrow::fee_account_from_bytes(&data).unwrap(),
+            escrow.fee_account
+        );
+        assert_eq!(
+            Escrow::deposited_is_native_from_bytes(&data).unwrap(),
+            escrow.deposited_is_native
+        );
+        assert_eq!(
+            Escrow::token_program_from_bytes(&data).unwrap(),
+            escrow.token_program
+        );
+        assert_eq!(
+            Escrow::allowed_taker_from_bytes(&data).unwrap(),
+            escrow.allowed_taker
+        );
+        assert_eq!(Escrow::status_from_bytes(&data).unwrap(), escrow.status);
+        assert_eq!(Escrow::basket_len_from_bytes(&data).unwrap(), escrow.basket_len);
+        assert_eq!(Escrow::basket_from_bytes(&data).unwrap(), escrow.basket);
+        assert_eq!(
+            Escrow::start_unix_timestamp_from_bytes(&data).unwrap(),
+            escrow.start_unix_timestamp
+        );
+        assert_eq!(Escrow::auction_from_bytes(&data).unwrap(), escrow.auction);
+        assert_eq!(
+            Escrow::min_fill_amount_from_bytes(&data).unwrap(),
+            escrow.min_fill_amount
+        );
+        assert_eq!(Escrow::rounding_from_bytes(&data).unwrap(), escrow.rounding);
+        assert_eq!(escrow.basket_vaults(), &escrow.basket[..2]);
+    }
+
+    #[test]
+    fn side_round_trips_and_rejects_unknown_bytes() {
+        let (mut escrow, mut data) = packed_escrow();
+        assert_eq!(escrow.side, Side::Buy);
+
+        escrow.side = Side::Sell;
+        Escrow::pack(escrow, &mut data).unwrap();
+        assert_eq!(Escrow::unpack(&data).unwrap().side, Side::Sell);
+
+        data[SIDE_OFFSET] = 2;
+        assert_eq!(
+            Escrow::unpack(&data).err(),
+            Some(EscrowError::InvalidSide.into())
+        );
+        assert_eq!(
+            Escrow::side_from_bytes(&data),
+            Err(EscrowError::InvalidSide.into())
+        );
+    }
+
+    #[test]
+    fn field_reads_reject_bad_data() {
+        let (_, mut data) = packed_escrow();
+
+        assert_eq!(
+            Escrow::expected_amount_from_bytes(&data[1..]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        data[IS_INITIALIZED_OFFSET] = 2;
+        assert_eq!(
+            Escrow::is_initialized_from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        data[ARBITER_OFFSET] = 2;
+        assert_eq!(
+            Escrow::arbiter_from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        data[STATUS_OFFSET] = 4;
+        assert_eq!(
+            Escrow::status_from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        data[BASKET_LEN_OFFSET] = MAX_BASKET_LEN as u8 + 1;
+        assert_eq!(
+            Escrow::basket_len_from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        data[AUCTION_OFFSET] = 2;
+        assert_eq!(
+            Escrow::auction_from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn only_an_open_escrow_moves_on() {
+        let (mut escrow, _) = packed_escrow();
+        escrow.status = EscrowStatus::Active;
+        assert_eq!(escrow.transition(EscrowStatus::PartiallyFilled), Ok(()));
+        assert_eq!(escrow.transition(EscrowStatus::PartiallyFilled), Ok(()));
+        assert_eq!(
+            escrow.transition(EscrowStatus::Active),
+            Err(EscrowError::InvalidStateTransition)
+        );
+        assert_eq!(escrow.transition(EscrowStatus::Completed), Ok(()));
+
+        // nothing moves a settled escrow, not even to the same state
+        for settled in [EscrowStatus::Completed, EscrowStatus::Cancelled] {
+            for next in [
+                EscrowStatus::PartiallyFilled,
+                EscrowStatus::Completed,
+                EscrowStatus::Cancelled,
+            ] {
+                escrow.status = settled;
+                assert_eq!(escrow.transition(next), Err(EscrowError::InvalidStateTransition));
+                assert_eq!(escrow.status, settled);
+            }
+        }
+    }
+
+    #[test]
+    fn from_account_info_checks_owner_length_and_state() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let read = |data: &mut [u8], owner: &Pubkey| {
+            let mut lamports = 0;
+            let info = AccountInfo::new(&key, false, false, &mut lamports, data, owner, false, 0);
+            Escrow::from_account_info(&info, &program_id)
+        };
+
+        let (escrow, mut data) = packed_escrow();
+        assert_eq!(read(&mut data, &program_id), Ok(escrow));
+        assert_eq!(
+            read(&mut data, &Pubkey::new_unique()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        // the original layout unpacks, but has to be migrated before it is read here
+        assert_eq!(
+            read(&mut data[IS_INITIALIZED_OFFSET..][..Escrow::V0_LEN], &program_id),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            read(&mut [], &program_id),
+            Err(EscrowError::EscrowNotInitialized.into())
+        );
+
+        data[DISCRIMINATOR_OFFSET] ^= 1;
+        assert_eq!(
+            read(&mut data, &program_id),
+            Err(EscrowError::InvalidAccountDiscriminator.into())
+        );
+        data[IS_INITIALIZED_OFFSET] = 0;
+        assert_eq!(
+            read(&mut data, &program_id),
+            Err(EscrowError::EscrowNotInitialized.into())
+        );
+        assert_eq!(
+            read(&mut vec![0; Escrow::LEN], &program_id),
+            Err(EscrowError::EscrowNotInitialized.into())
+        );
+    }
+
+    #[test]
+    fn config_layout_is_stable() {
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_from_array([1; 32]),
+            paused: true,
+            unpause_delay: 0x0102_0304_0506_0708,
+            pause_until: -2,
+            max_expiry_horizon_secs: 0x1112_1314_1516_1718,
+        };
+        let mut data = vec![0; Config::LEN];
+        Config::pack(config, &mut data).unwrap();
+        assert_eq!(
+            data,
+            [
+                &[1][..],
+                &[1; 32],
+                &[1],
+                &[8, 7, 6, 5, 4, 3, 2, 1],
+                &[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+                &[0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11],
+            ]
+            .concat()
+        );
+        let unpacked = Config::unpack(&data).unwrap();
+        assert_eq!(unpacked.admin, Pubkey::new_from_array([1; 32]));
+        assert_eq!(unpacked.pause_until, -2);
+
+        data[33] = 2;
+        assert_eq!(Config::unpack(&data), Err(ProgramError::InvalidAccountData));
     }
 }
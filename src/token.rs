@@ -0,0 +1,276 @@
+#[cfg(feature = "create-receive-account")]
+use solana_program::instruction::AccountMeta;
+use solana_program::{
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use crate::error::EscrowError;
+
+/// The Token-2022 program. It keeps spl-token's encoding for every instruction this
+/// program sends and spl-token's layout for the front of every account, appending
+/// its extensions after them, so the spl-token crate serves for both.
+pub mod token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+/// The associated-token-account program, which creates the canonical token account of
+/// a wallet and mint at an address derived from both
+#[cfg(feature = "create-receive-account")]
+pub mod associated_token {
+    solana_program::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+}
+
+// the byte Token-2022 writes right after an account's spl-token length, once it has extensions
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+// Token-2022 extension types that make a transfer deliver less than it sends or run someone
+// else's program inside it: a mint's transfer fee and transfer hook, and the per-account state
+// Token-2022 requires alongside each
+const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const EXTENSION_TRANSFER_FEE_AMOUNT: u16 = 2;
+const EXTENSION_TRANSFER_HOOK: u16 = 14;
+const EXTENSION_TRANSFER_HOOK_ACCOUNT: u16 = 15;
+
+/// Whether an escrow can hold and move tokens through `program_id`
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == token_2022::id()
+}
+
+/// Points an instruction built by spl-token at `token_program`. The builders refuse any
+/// program id but spl-token's own, so build with that and retarget the result.
+pub fn retarget(mut instruction: Instruction, token_program: &Pubkey) -> Instruction {
+    instruction.program_id = *token_program;
+    instruction
+}
+
+/// The address of `wallet`'s associated account for `mint` under `token_program`
+#[cfg(feature = "create-receive-account")]
+pub fn associated_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &associated_token::id(),
+    )
+    .0
+}
+
+/// Creates `wallet`'s associated account for `mint` under `token_program`, paid for by `payer`.
+/// The program fails it if the account already exists
+#[cfg(feature = "create-receive-account")]
+pub fn create_associated_account(
+    payer: &Pubkey,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: associated_token::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(associated_address(wallet, mint, token_program), false),
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(solana_program::system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+        data: vec![0], // Create
+    }
+}
+
+/// Unpacks an initialized token account, ignoring any Token-2022 extensions after it but
+/// those of a transfer fee or transfer hook, which it refuses
+pub fn unpack_account(data: &[u8]) -> Result<TokenAccount, ProgramError> {
+    TokenAccount::unpack(base::<TokenAccount>(data, ACCOUNT_TYPE_ACCOUNT)?)
+}
+
+/// Same as `unpack_account`, but also reads an account that was never initialized
+pub fn unpack_account_unchecked(data: &[u8]) -> Result<TokenAccount, ProgramError> {
+    TokenAccount::unpack_unchecked(base::<TokenAccount>(data, ACCOUNT_TYPE_ACCOUNT)?)
+}
+
+/// Unpacks an initialized mint, ignoring any Token-2022 extensions after it but a transfer
+/// fee or transfer hook, which it refuses
+pub fn unpack_mint(data: &[u8]) -> Result<Mint, ProgramError> {
+    Mint::unpack(base::<Mint>(data, ACCOUNT_TYPE_MINT)?)
+}
+
+// an extended mint is padded out to an account's length before its type byte, so the
+// type byte sits at the same offset for both and tells them apart
+fn base<T: Pack + IsInitialized>(data: &[u8], account_type: u8) -> Result<&[u8], ProgramError> {
+    if data.len() == T::LEN {
+        return Ok(data);
+    }
+    match data.get(TokenAccount::LEN) {
+        Some(byte) if *byte == account_type => {
+            check_extensions(&data[TokenAccount::LEN + 1..])?;
+            Ok(&data[..T::LEN])
+        }
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// walks the type-length-value entries after the type byte; a zero type marks the unused
+// space Token-2022 leaves at the end
+fn check_extensions(mut tlv: &[u8]) -> Result<(), ProgramError> {
+    while tlv.len() >= 4 {
+        let extension_type = u16::from_le_bytes([tlv[0], tlv[1]]);
+        let length = u16::from_le_bytes([tlv[2], tlv[3]]) as usize;
+        if extension_type == 0 {
+            break;
+        }
+        let rest = tlv.get(4 + length..).ok_or(ProgramError::InvalidAccountData)?;
+        if let EXTENSION_TRANSFER_FEE_CONFIG
+        | EXTENSION_TRANSFER_FEE_AMOUNT
+        | EXTENSION_TRANSFER_HOOK
+        | EXTENSION_TRANSFER_HOOK_ACCOUNT = extension_type
+        {
+            return Err(EscrowError::UnsupportedTokenExtension.into());
+        }
+        tlv = rest;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::program_option::COption;
+    use spl_token::state::AccountState;
+
+    fn extended(base: Vec<u8>, account_type: u8) -> Vec<u8> {
+        with_extension(base, account_type, 3, &[0xaa; 32]) // a mint close authority, say
+    }
+
+    fn with_extension(
+        mut base: Vec<u8>,
+        account_type: u8,
+        extension: u16,
+        value: &[u8],
+    ) -> Vec<u8> {
+        base.resize(TokenAccount::LEN, 0);
+        base.push(account_type);
+        base.extend_from_slice(&extension.to_le_bytes());
+        base.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        base.extend_from_slice(value);
+        base.extend_from_slice(&[0; 8]); // unused space Token-2022 leaves at the end
+        base
+    }
+
+    #[test]
+    fn reads_the_base_of_extended_accounts() {
+        let account = TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(account, &mut data).unwrap();
+        assert_eq!(unpack_account(&data), Ok(account));
+        assert_eq!(
+            unpack_account(&extended(data.clone(), ACCOUNT_TYPE_ACCOUNT)),
+            Ok(account)
+        );
+        assert_eq!(
+            unpack_account(&extended(data.clone(), ACCOUNT_TYPE_MINT)),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            unpack_account(&data[..TokenAccount::LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mint = Mint {
+            mint_authority: COption::None,
+            supply: 1_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut data).unwrap();
+        assert_eq!(unpack_mint(&extended(data.clone(), ACCOUNT_TYPE_MINT)), Ok(mint));
+        assert_eq!(
+            unpack_mint(&extended(data, ACCOUNT_TYPE_ACCOUNT)),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn refuses_transfer_fees_and_hooks() {
+        let mut account = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(
+            TokenAccount {
+                state: AccountState::Initialized,
+                ..TokenAccount::default()
+            },
+            &mut account,
+        )
+        .unwrap();
+        let mut mint = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                is_initialized: true,
+                ..Mint::default()
+            },
+            &mut mint,
+        )
+        .unwrap();
+        let refused = ProgramError::from(EscrowError::UnsupportedTokenExtension);
+
+        for extension in &[EXTENSION_TRANSFER_FEE_CONFIG, EXTENSION_TRANSFER_HOOK] {
+            let data = with_extension(mint.clone(), ACCOUNT_TYPE_MINT, *extension, &[0; 64]);
+            assert_eq!(unpack_mint(&data), Err(refused.clone()));
+        }
+        for extension in &[EXTENSION_TRANSFER_FEE_AMOUNT, EXTENSION_TRANSFER_HOOK_ACCOUNT] {
+            let data = with_extension(account.clone(), ACCOUNT_TYPE_ACCOUNT, *extension, &[0; 8]);
+            assert_eq!(unpack_account(&data), Err(refused.clone()));
+            assert_eq!(unpack_account_unchecked(&data), Err(refused.clone()));
+        }
+
+        // found behind another extension too, and an entry running off the end is garbled
+        let mut data = extended(mint.clone(), ACCOUNT_TYPE_MINT);
+        data.truncate(data.len() - 8);
+        data.extend_from_slice(&EXTENSION_TRANSFER_HOOK.to_le_bytes());
+        data.extend_from_slice(&[64, 0]);
+        assert_eq!(unpack_mint(&data), Err(ProgramError::InvalidAccountData));
+        data.extend_from_slice(&[0; 64]);
+        assert_eq!(unpack_mint(&data), Err(refused.clone()));
+    }
+
+    #[test]
+    fn retarget_keeps_the_instruction() {
+        let key = Pubkey::new_unique();
+        let built =
+            spl_token::instruction::close_account(&spl_token::id(), &key, &key, &key, &[])
+                .unwrap();
+        let retargeted = retarget(built.clone(), &token_2022::id());
+        assert_eq!(retargeted.program_id, token_2022::id());
+        assert_eq!(retargeted.accounts, built.accounts);
+        assert_eq!(retargeted.data, built.data);
+        assert!(is_token_program(&retargeted.program_id));
+        assert!(!is_token_program(&key));
+    }
+
+    #[cfg(feature = "create-receive-account")]
+    #[test]
+    fn associated_address_depends_on_the_token_program() {
+        let (wallet, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let address = associated_address(&wallet, &mint, &spl_token::id());
+        assert_ne!(address, associated_address(&wallet, &mint, &token_2022::id()));
+        assert_ne!(address, associated_address(&mint, &wallet, &spl_token::id()));
+
+        let create = create_associated_account(&wallet, &wallet, &mint, &token_2022::id());
+        assert_eq!(create.program_id, associated_token::id());
+        assert_eq!(
+            create.accounts[1].pubkey,
+            associated_address(&wallet, &mint, &token_2022::id())
+        );
+        assert_eq!(create.accounts[5].pubkey, token_2022::id());
+    }
+}
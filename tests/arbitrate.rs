@@ -0,0 +1,95 @@
+#![cfg(all(feature = "program", feature = "arbitration"))]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError};
+
+use common::{
+    arbitrate_data, init_escrow_with_arbiter_data, token_balance, token_owner, wallet, Trade,
+    PROGRAM_ID,
+};
+
+fn arbitrated_trade(deposit: u64) -> (Trade, AccountInfo<'static>) {
+    let trade = Trade::with_deposit(deposit, 100, 50);
+    let arbiter = wallet(0);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_with_arbiter_data(50, 100, arbiter.key),
+    )
+    .unwrap();
+    (trade, arbiter)
+}
+
+#[test]
+fn arbiter_releases_offer_to_taker() {
+    let (trade, arbiter) = arbitrated_trade(120);
+    assert_eq!(trade.escrow_state().arbiter, Some(*arbiter.key));
+
+    // the release is a transfer checked against the offered mint
+    let mut accounts = trade.arbitrate_accounts(&arbiter);
+    accounts[7] = trade.mint_y.clone();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &arbitrate_data(true)),
+        Err(EscrowError::MintMismatch.into())
+    );
+
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.arbitrate_accounts(&arbiter),
+        &arbitrate_data(true),
+    )
+    .unwrap();
+
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 20);
+    assert_eq!(token_owner(&trade.alice_temp_x), *trade.alice.key);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn arbiter_refunds_vault_to_maker() {
+    let (trade, arbiter) = arbitrated_trade(100);
+    let accounts = trade.arbitrate_accounts(&arbiter);
+
+    Processor::process(&PROGRAM_ID, &accounts[..6], &arbitrate_data(false)).unwrap();
+
+    assert_eq!(token_balance(&trade.bob_x), 0);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_owner(&trade.alice_temp_x), *trade.alice.key);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn arbitrate_rejects_anyone_but_the_arbiter() {
+    let (trade, arbiter) = arbitrated_trade(100);
+
+    let mut accounts = trade.arbitrate_accounts(&arbiter);
+    accounts[0] = trade.bob.clone();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &arbitrate_data(true)),
+        Err(EscrowError::NotArbiter.into())
+    );
+
+    let mut accounts = trade.arbitrate_accounts(&arbiter);
+    accounts[0].is_signer = false;
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &arbitrate_data(true)),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    // an escrow made with plain InitEscrow has no arbiter at all
+    let plain = Trade::new(100, 50);
+    plain.init().unwrap();
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &plain.arbitrate_accounts(&arbiter),
+            &arbitrate_data(false),
+        ),
+        Err(EscrowError::NotArbiter.into())
+    );
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use std::slice;
+
+use solana_escrow::{error::EscrowError, processor::Processor, state::MAX_BASKET_LEN};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+
+use common::{
+    exchange_basket_data, init_basket_escrow_data, mint_account, token_account, token_balance,
+    token_owner, update_expected_amount_data, Trade, PROGRAM_ID,
+};
+
+/// A second token in the bundle: Alice's vault of it, holding 40, Bob's empty account and
+/// its mint
+fn second_token(
+    trade: &Trade,
+) -> (AccountInfo<'static>, AccountInfo<'static>, AccountInfo<'static>) {
+    let mint_z = mint_account(2);
+    (
+        token_account(mint_z.key, trade.alice.key, 40),
+        token_account(mint_z.key, trade.bob.key, 0),
+        mint_z,
+    )
+}
+
+fn init_basket(trade: &Trade, vaults: &[AccountInfo<'static>]) -> ProgramResult {
+    let mut accounts = trade.init_accounts();
+    accounts.extend_from_slice(vaults);
+    Processor::process(
+        &PROGRAM_ID,
+        &accounts,
+        &init_basket_escrow_data(trade.expected, vaults.len() as u8),
+    )
+}
+
+fn exchange_basket(trade: &Trade, legs: &[AccountInfo<'static>]) -> ProgramResult {
+    capped_exchange_basket(trade, legs, None)
+}
+
+fn capped_exchange_basket(
+    trade: &Trade,
+    legs: &[AccountInfo<'static>],
+    max_payment: Option<u64>,
+) -> ProgramResult {
+    let mut accounts = trade.exchange_accounts();
+    accounts.extend_from_slice(legs);
+    Processor::process(
+        &PROGRAM_ID,
+        &accounts,
+        &exchange_basket_data(trade.offered, (legs.len() / 3) as u8, max_payment),
+    )
+}
+
+#[test]
+fn basket_of_two_tokens_goes_for_one_payment() {
+    let trade = Trade::new(100, 50);
+    let (alice_temp_z, bob_z, mint_z) = second_token(&trade);
+    init_basket(&trade, slice::from_ref(&alice_temp_z)).unwrap();
+    assert_eq!(token_owner(&alice_temp_z), *trade.pda.key);
+    assert_eq!(trade.escrow_state().basket_vaults(), [*alice_temp_z.key]);
+
+    // taking only the temp token account would strand the rest of the bundle
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::WrongAccountCount.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    let alice_lamports = trade.alice.lamports();
+    let reclaimed =
+        trade.alice_temp_x.lamports() + alice_temp_z.lamports() + trade.escrow.lamports();
+    exchange_basket(&trade, &[alice_temp_z.clone(), bob_z.clone(), mint_z.clone()]).unwrap();
+
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&bob_z), 40);
+    assert_eq!(token_balance(&trade.bob_y), 0);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(alice_temp_z.lamports(), 0);
+    assert_eq!(trade.escrow.data_len(), 0);
+    assert_eq!(trade.alice.lamports(), alice_lamports + reclaimed);
+}
+
+#[test]
+fn basket_is_capped_and_paid_out_only_in_its_own_mints() {
+    let trade = Trade::new(100, 50);
+    let too_many: Vec<_> = (0..=MAX_BASKET_LEN).map(|_| second_token(&trade).0).collect();
+    assert_eq!(
+        init_basket(&trade, &too_many),
+        Err(EscrowError::TooManyAccounts.into())
+    );
+
+    let (alice_temp_z, bob_z, mint_z) = second_token(&trade);
+    init_basket(&trade, slice::from_ref(&alice_temp_z)).unwrap();
+    // Bob's Y account is in the wrong mint for the Z vault
+    assert_eq!(
+        exchange_basket(&trade, &[alice_temp_z.clone(), trade.bob_y.clone(), mint_z]),
+        Err(EscrowError::MintMismatch.into())
+    );
+    // and so is the mint the checked transfer would be held to
+    assert_eq!(
+        exchange_basket(&trade, &[alice_temp_z.clone(), bob_z, trade.mint_y.clone()]),
+        Err(EscrowError::MintMismatch.into())
+    );
+    assert_eq!(token_balance(&alice_temp_z), 40);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+}
+
+#[test]
+fn basket_taker_is_held_to_the_price_they_saw() {
+    let trade = Trade::new(100, 50);
+    let (alice_temp_z, bob_z, mint_z) = second_token(&trade);
+    init_basket(&trade, slice::from_ref(&alice_temp_z)).unwrap();
+    let legs = [alice_temp_z.clone(), bob_z.clone(), mint_z];
+
+    // Alice reprices the bundle just ahead of Bob's take
+    Processor::process(
+        &PROGRAM_ID,
+        &[trade.alice.clone(), trade.escrow.clone()],
+        &update_expected_amount_data(60),
+    )
+    .unwrap();
+    assert_eq!(
+        capped_exchange_basket(&trade, &legs, Some(50)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(token_balance(&alice_temp_z), 40);
+    assert_eq!(token_balance(&bob_z), 0);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+}
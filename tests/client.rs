@@ -0,0 +1,87 @@
+//! Builds with or without the `program` feature, as a client depending on the crate with
+//! `default-features = false` sees it
+
+use solana_escrow::{
+    error::EscrowError,
+    instruction::EscrowInstruction,
+    state::{
+        vault_authority, Escrow, EscrowStatus, RoundingMode, Side, ESCROW_VERSION, MAX_BASKET_LEN,
+    },
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+#[test]
+fn client_builds_instructions_and_reads_state_without_the_processor() {
+    let program_id = Pubkey::new_unique();
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let ix = EscrowInstruction::init_escrow(
+        &program_id, &keys[0], &keys[1], &keys[2], &keys[3], 50, 100,
+    );
+    assert_eq!(ix.program_id, program_id);
+    assert_eq!(
+        EscrowInstruction::unpack(&ix.data),
+        Ok(EscrowInstruction::InitEscrow {
+            amount: 50,
+            offered_amount: 100,
+            side: Side::Sell,
+            expiry_unix_timestamp: 0,
+            start_unix_timestamp: 0,
+            min_fill_amount: 0,
+            rounding: RoundingMode::FloorToMaker,
+        })
+    );
+
+    let escrow = Escrow {
+        version: ESCROW_VERSION,
+        is_initialized: true,
+        initializer_pubkey: keys[0],
+        temp_token_account_pubkey: keys[1],
+        initializer_token_to_receive_account_pubkey: keys[2],
+        expected_amount: 50,
+        offered_amount: 100,
+        arbiter: None,
+        price: None,
+        side: Side::Sell,
+        bump: vault_authority(&program_id, &keys[3]).1,
+        expiry_unix_timestamp: 0,
+        expected_mint: Pubkey::new_unique(),
+        deposited_mint: Pubkey::new_unique(),
+        fee_basis_points: 0,
+        fee_account: keys[2],
+        deposited_is_native: false,
+        token_program: spl_token::id(),
+        allowed_taker: Pubkey::default(),
+        status: EscrowStatus::Active,
+        basket_len: 0,
+        basket: [Pubkey::default(); MAX_BASKET_LEN],
+        start_unix_timestamp: 0,
+        auction: None,
+        min_fill_amount: 0,
+        rounding: RoundingMode::FloorToMaker,
+    };
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(escrow, &mut data).unwrap();
+    let escrow = Escrow::unpack(&data).unwrap();
+    let ix = EscrowInstruction::exchange(
+        &program_id,
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &keys[3],
+        &escrow,
+    );
+    assert_eq!(
+        EscrowInstruction::unpack(&ix.data),
+        Ok(EscrowInstruction::Exchange {
+            min_acceptable_amount: 100,
+            max_payment: Some(50),
+            expected_vault_amount: None,
+            create_missing_accounts: false,
+        })
+    );
+
+    assert_eq!(
+        ProgramError::from(EscrowError::ExpectedAmountMismatch),
+        ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32)
+    );
+}
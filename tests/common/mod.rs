@@ -0,0 +1,914 @@
+//! In-process harness: builds `AccountInfo`s by hand and routes the processor's
+//! CPIs straight into the SPL token processor through the syscall stubs. Token-2022
+//! instructions run through the same processor, which covers the base instructions
+//! the escrow sends but none of Token-2022's extensions.
+#![allow(dead_code)]
+
+use std::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+    sync::Once,
+};
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::{Clock, Epoch},
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::{self, Sysvar},
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+use solana_escrow::{
+    processor::Processor,
+    state::{config_address, program_data_address, vault_authority, Escrow, RoundingMode},
+    token::token_2022,
+};
+#[cfg(feature = "create-receive-account")]
+use solana_escrow::token::{self, associated_token};
+
+pub const PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// The unix time every `Trade` clock starts at
+pub const NOW: i64 = 1_700_000_000;
+
+thread_local! {
+    static CPI_ENABLED: Cell<bool> = const { Cell::new(true) };
+    static CPI_COUNT: Cell<usize> = const { Cell::new(0) };
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static BEFORE_NEXT_CPI: RefCell<Option<Box<dyn FnOnce()>>> = const { RefCell::new(None) };
+    static UNIX_TIMESTAMP: Cell<i64> = const { Cell::new(NOW) };
+}
+
+/// Makes every CPI on this thread succeed without doing anything, like a token
+/// program that ignores the instruction.
+pub fn disable_cpi() {
+    CPI_ENABLED.with(|enabled| enabled.set(false));
+}
+
+/// Runs `hook` once, as the next CPI on this thread is made and before the callee sees it,
+/// standing in for a program that calls back into the escrow mid-instruction
+pub fn before_next_cpi(hook: impl FnOnce() + 'static) {
+    BEFORE_NEXT_CPI.with(|next| *next.borrow_mut() = Some(Box::new(hook)));
+}
+
+/// How many CPIs the program made on this thread since the last call, disabled ones included
+pub fn take_cpi_count() -> usize {
+    CPI_COUNT.with(|count| count.replace(0))
+}
+
+/// Hands back everything the program logged on this thread since the last call
+pub fn take_logs() -> Vec<String> {
+    LOGS.with(|logs| logs.take())
+}
+
+struct TokenStubs;
+
+impl SyscallStubs for TokenStubs {
+    fn sol_log(&self, message: &str) {
+        LOGS.with(|logs| logs.borrow_mut().push(format!("Program log: {}", message)));
+    }
+
+    // the same time the last clock sysvar account was set to on this thread
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: UNIX_TIMESTAMP.with(|now| now.get()),
+            ..Clock::default()
+        };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        CPI_COUNT.with(|count| count.set(count.get() + 1));
+        if let Some(hook) = BEFORE_NEXT_CPI.with(|next| next.borrow_mut().take()) {
+            hook();
+        }
+        if !CPI_ENABLED.with(|enabled| enabled.get()) {
+            return Ok(());
+        }
+        let pda_signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &PROGRAM_ID))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        let mut infos = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let mut info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .clone();
+            // like the runtime, merge duplicate metas so a key listed as a signer anywhere signs everywhere
+            let is_signer = instruction
+                .accounts
+                .iter()
+                .any(|other| other.pubkey == meta.pubkey && other.is_signer);
+            if is_signer && !info.is_signer {
+                if !pda_signers.contains(info.key) {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                info.is_signer = true;
+            }
+            infos.push(info);
+        }
+
+        invoke_builtin(instruction, &infos)
+    }
+}
+
+/// Runs a token, system or associated-token-account program instruction against accounts
+/// already in meta order
+fn invoke_builtin(instruction: &Instruction, infos: &[AccountInfo]) -> ProgramResult {
+    if instruction.program_id == spl_token::id() || instruction.program_id == token_2022::id() {
+        spl_token::processor::Processor::process(&instruction.program_id, infos, &instruction.data)
+    } else if instruction.program_id == solana_program::system_program::id() {
+        create_account(infos, &instruction.data)
+    } else {
+        #[cfg(feature = "create-receive-account")]
+        if instruction.program_id == associated_token::id() {
+            return create_associated_account(infos, &instruction.data);
+        }
+        Err(ProgramError::IncorrectProgramId)
+    }
+}
+
+/// Runs top-level instructions one after another, the escrow program's included. Every
+/// account an instruction names must be in `accounts`, and signers must already sign.
+/// Unlike a real transaction, instructions that ran before a failure stay applied.
+pub fn process_transaction(
+    instructions: &[Instruction],
+    accounts: &[AccountInfo<'static>],
+) -> ProgramResult {
+    install_stubs();
+    for instruction in instructions {
+        let mut infos = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = accounts
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            infos.push(info.clone());
+        }
+        if instruction.program_id == PROGRAM_ID {
+            Processor::process(&PROGRAM_ID, &infos, &instruction.data)?;
+        } else {
+            invoke_builtin(instruction, &infos)?;
+        }
+    }
+    Ok(())
+}
+
+/// Just enough of the system program's CreateAccount to fund a fresh account. An
+/// `AccountInfo` can't be resized or reassigned, so the account has to be built at
+/// its final size and owner already, see `fresh_account`.
+fn create_account(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // bincode: u32 variant tag, then lamports, space and owner
+    if data.len() != 52 || data[..4] != [0; 4] {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let lamports = u64::from_le_bytes(data[4..12].try_into().unwrap());
+    let space = u64::from_le_bytes(data[12..20].try_into().unwrap());
+    let owner = Pubkey::new_from_array(data[20..52].try_into().unwrap());
+    let (from, to) = (&infos[0], &infos[1]);
+
+    if !from.is_signer || !to.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if to.lamports() != 0 || to.data_len() as u64 != space || *to.owner != owner {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    **from.lamports.borrow_mut() = from
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **to.lamports.borrow_mut() = lamports;
+    Ok(())
+}
+
+/// The associated-token-account program's Create: funds the account at the wallet and
+/// mint's derived address, which like a created keypair account must already be zeroed at
+/// its size and owned by the token program, and writes it initialized for the wallet
+#[cfg(feature = "create-receive-account")]
+fn create_associated_account(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if !data.is_empty() && data != [0] {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (payer, associated, wallet, mint) = (&infos[0], &infos[1], &infos[2], &infos[3]);
+    let token_program = infos[5].key;
+
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *associated.key != token::associated_address(wallet.key, mint.key, token_program) {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if associated.lamports() != 0
+        || associated.data_len() != TokenAccount::LEN
+        || associated.owner != token_program
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let lamports = Rent::default().minimum_balance(TokenAccount::LEN);
+    **payer.lamports.borrow_mut() = payer
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **associated.lamports.borrow_mut() = lamports;
+
+    // what the token program's InitializeAccount writes for a non-native mint
+    token::unpack_mint(&mint.data.borrow())?;
+    let initialized = TokenAccount {
+        mint: *mint.key,
+        owner: *wallet.key,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    };
+    TokenAccount::pack(initialized, &mut associated.data.borrow_mut())
+}
+
+fn install_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        set_syscall_stubs(Box::new(TokenStubs));
+    });
+}
+
+/// Builds an account that lives for the rest of the test process.
+pub fn account(
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+) -> AccountInfo<'static> {
+    AccountInfo::new(
+        Box::leak(Box::new(key)),
+        is_signer,
+        is_writable,
+        Box::leak(Box::new(lamports)),
+        Box::leak(data.into_boxed_slice()),
+        Box::leak(Box::new(owner)),
+        false,
+        Epoch::default(),
+    )
+}
+
+pub fn wallet(lamports: u64) -> AccountInfo<'static> {
+    account(
+        Pubkey::new_unique(),
+        lamports,
+        vec![],
+        solana_program::system_program::id(),
+        true,
+        true,
+    )
+}
+
+pub fn mint_account(decimals: u8) -> AccountInfo<'static> {
+    mint_account_in(&spl_token::id(), decimals)
+}
+
+/// A mint owned by `token_program`, spl-token or Token-2022
+pub fn mint_account_in(token_program: &Pubkey, decimals: u8) -> AccountInfo<'static> {
+    let mint = Mint {
+        mint_authority: COption::None,
+        supply: u64::MAX,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(mint, &mut data).unwrap();
+    account(
+        Pubkey::new_unique(),
+        Rent::default().minimum_balance(Mint::LEN),
+        data,
+        *token_program,
+        false,
+        false,
+    )
+}
+
+pub fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> AccountInfo<'static> {
+    token_account_in(&spl_token::id(), mint, owner, amount)
+}
+
+/// A token account owned by `token_program`, spl-token or Token-2022
+pub fn token_account_in(
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> AccountInfo<'static> {
+    let token = TokenAccount {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    };
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(token, &mut data).unwrap();
+    account(
+        Pubkey::new_unique(),
+        Rent::default().minimum_balance(TokenAccount::LEN),
+        data,
+        *token_program,
+        false,
+        true,
+    )
+}
+
+/// An unfunded keypair account, zeroed at `size` bytes and already owned by `owner`
+/// as the stubbed CreateAccount expects
+pub fn fresh_account(size: usize, owner: Pubkey) -> AccountInfo<'static> {
+    account(Pubkey::new_unique(), 0, vec![0; size], owner, true, true)
+}
+
+/// `wallet`'s associated spl-token account for `mint` before anyone creates it, unfunded
+/// and zeroed as the stubbed Create expects
+#[cfg(feature = "create-receive-account")]
+pub fn missing_associated_account(wallet: &Pubkey, mint: &Pubkey) -> AccountInfo<'static> {
+    account(
+        token::associated_address(wallet, mint, &spl_token::id()),
+        0,
+        vec![0; TokenAccount::LEN],
+        spl_token::id(),
+        false,
+        true,
+    )
+}
+
+#[cfg(feature = "create-receive-account")]
+pub fn associated_token_program() -> AccountInfo<'static> {
+    token_program_account(&associated_token::id())
+}
+
+pub fn system_program() -> AccountInfo<'static> {
+    let mut info = account(
+        solana_program::system_program::id(),
+        1,
+        vec![],
+        solana_program::bpf_loader::id(),
+        false,
+        false,
+    );
+    info.executable = true;
+    info
+}
+
+pub fn rent_sysvar() -> AccountInfo<'static> {
+    let mut info = account(
+        sysvar::rent::id(),
+        1,
+        vec![0; Rent::size_of()],
+        sysvar::id(),
+        false,
+        false,
+    );
+    Rent::default().to_account_info(&mut info).unwrap();
+    info
+}
+
+pub fn clock_sysvar(unix_timestamp: i64) -> AccountInfo<'static> {
+    let info = account(
+        sysvar::clock::id(),
+        1,
+        vec![0; Clock::size_of()],
+        sysvar::id(),
+        false,
+        false,
+    );
+    set_clock(&info, unix_timestamp);
+    info
+}
+
+/// Moves the clock sysvar to `unix_timestamp`, every clone of the account sees it, and so
+/// does the clock syscall on this thread
+pub fn set_clock(clock: &AccountInfo<'static>, unix_timestamp: i64) {
+    UNIX_TIMESTAMP.with(|now| now.set(unix_timestamp));
+    Clock {
+        unix_timestamp,
+        ..Clock::default()
+    }
+    .to_account_info(&mut clock.clone())
+    .unwrap();
+}
+
+/// The program's config account as InitConfig finds it, not created yet
+pub fn config_account() -> AccountInfo<'static> {
+    account(
+        config_address(&PROGRAM_ID).0,
+        0,
+        vec![],
+        solana_program::system_program::id(),
+        false,
+        true,
+    )
+}
+
+/// The program's ProgramData account as the upgradeable loader writes it, with
+/// `upgrade_authority` as the key that may upgrade it, `None` once it is immutable
+pub fn program_data(upgrade_authority: Option<&Pubkey>) -> AccountInfo<'static> {
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    match upgrade_authority {
+        Some(authority) => {
+            data.push(1);
+            data.extend_from_slice(authority.as_ref());
+        }
+        None => data.push(0),
+    }
+    account(
+        program_data_address(&PROGRAM_ID),
+        1,
+        data,
+        solana_program::bpf_loader_upgradeable::id(),
+        false,
+        false,
+    )
+}
+
+pub fn token_program() -> AccountInfo<'static> {
+    token_program_account(&spl_token::id())
+}
+
+/// The executable account of `program_id`, spl-token or Token-2022
+pub fn token_program_account(program_id: &Pubkey) -> AccountInfo<'static> {
+    let mut info = account(
+        *program_id,
+        1,
+        vec![],
+        solana_program::bpf_loader::id(),
+        false,
+        false,
+    );
+    info.executable = true;
+    info
+}
+
+pub fn token_balance(info: &AccountInfo) -> u64 {
+    TokenAccount::unpack_unchecked(&info.data.borrow())
+        .unwrap()
+        .amount
+}
+
+pub fn token_owner(info: &AccountInfo) -> Pubkey {
+    TokenAccount::unpack_unchecked(&info.data.borrow())
+        .unwrap()
+        .owner
+}
+
+/// Freezes a token account in place, as its mint's freeze authority would
+pub fn freeze(info: &AccountInfo) {
+    let mut token = TokenAccount::unpack_unchecked(&info.data.borrow()).unwrap();
+    token.state = AccountState::Frozen;
+    TokenAccount::pack(token, &mut info.data.borrow_mut()).unwrap();
+}
+
+/// The vault authority of the escrow state account `escrow`
+pub fn pda(escrow: &Pubkey) -> Pubkey {
+    vault_authority(&PROGRAM_ID, escrow).0
+}
+
+pub fn pda_account(escrow: &Pubkey) -> AccountInfo<'static> {
+    account(
+        pda(escrow),
+        0,
+        vec![],
+        solana_program::system_program::id(),
+        false,
+        false,
+    )
+}
+
+pub fn init_escrow_data(amount: u64, offered_amount: u64) -> Vec<u8> {
+    let mut data = vec![0];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data
+}
+
+pub fn expiring_init_escrow_data(amount: u64, offered_amount: u64, expiry: i64) -> Vec<u8> {
+    let mut data = init_escrow_data(amount, offered_amount);
+    data.push(0);
+    data.extend_from_slice(&expiry.to_le_bytes());
+    data
+}
+
+pub fn scheduled_init_escrow_data(amount: u64, offered_amount: u64, start: i64) -> Vec<u8> {
+    let mut data = expiring_init_escrow_data(amount, offered_amount, 0);
+    data.extend_from_slice(&start.to_le_bytes());
+    data
+}
+
+pub fn min_fill_init_escrow_data(amount: u64, offered_amount: u64, min_fill: u64) -> Vec<u8> {
+    let mut data = scheduled_init_escrow_data(amount, offered_amount, 0);
+    data.extend_from_slice(&min_fill.to_le_bytes());
+    data
+}
+
+pub fn rounding_init_escrow_data(
+    amount: u64,
+    offered_amount: u64,
+    rounding: RoundingMode,
+) -> Vec<u8> {
+    let mut data = min_fill_init_escrow_data(amount, offered_amount, 0);
+    data.push(rounding as u8);
+    data
+}
+
+pub fn init_escrow_with_fee_data(
+    amount: u64,
+    offered_amount: u64,
+    fee_basis_points: u16,
+    fee_account: &Pubkey,
+) -> Vec<u8> {
+    let mut data = vec![9];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data.extend_from_slice(&fee_basis_points.to_le_bytes());
+    data.extend_from_slice(fee_account.as_ref());
+    data
+}
+
+pub fn init_escrow_multi_data(amount: u64, offered_amount: u64, sources: u8) -> Vec<u8> {
+    let mut data = vec![3];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data.push(sources);
+    data
+}
+
+pub fn init_escrow_with_arbiter_data(
+    amount: u64,
+    offered_amount: u64,
+    arbiter: &Pubkey,
+) -> Vec<u8> {
+    let mut data = vec![4];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data.extend_from_slice(arbiter.as_ref());
+    data
+}
+
+/// `release` picks `ArbiterDecision::Release`, otherwise `Refund`
+pub fn arbitrate_data(release: bool) -> Vec<u8> {
+    vec![5, if release { 0 } else { 1 }]
+}
+
+pub fn exchange_into_new_account_data(amount: u64, max_payment: Option<u64>) -> Vec<u8> {
+    let mut data = vec![6];
+    data.extend_from_slice(&amount.to_le_bytes());
+    if let Some(max_payment) = max_payment {
+        data.extend_from_slice(&max_payment.to_le_bytes());
+    }
+    data
+}
+
+pub fn init_escrow_at_price_data(
+    offered_amount: u64,
+    numerator: u64,
+    denominator: u64,
+) -> Vec<u8> {
+    let mut data = vec![7];
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data.extend_from_slice(&numerator.to_le_bytes());
+    data.extend_from_slice(&denominator.to_le_bytes());
+    data
+}
+
+pub fn exchange_data(amount: u64) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+/// An Exchange that creates whichever receiving account doesn't exist yet
+pub fn creating_exchange_data(amount: u64) -> Vec<u8> {
+    let mut data = exchange_data(amount);
+    data.push(1);
+    data
+}
+
+/// An Exchange that pays no more than `max_payment`
+pub fn capped_exchange_data(amount: u64, max_payment: u64) -> Vec<u8> {
+    let mut data = exchange_data(amount);
+    data.extend_from_slice(&max_payment.to_le_bytes());
+    data
+}
+
+pub fn pinned_exchange_data(amount: u64, expected_vault_amount: u64) -> Vec<u8> {
+    let mut data = capped_exchange_data(amount, u64::MAX);
+    data.extend_from_slice(&expected_vault_amount.to_le_bytes());
+    data
+}
+
+pub fn partial_exchange_data(amount: u64, fill_amount: u64) -> Vec<u8> {
+    let mut data = vec![8];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&fill_amount.to_le_bytes());
+    data
+}
+
+/// A PartialExchange that pays no more than `max_payment` for the fill
+pub fn capped_partial_exchange_data(amount: u64, fill_amount: u64, max_payment: u64) -> Vec<u8> {
+    let mut data = partial_exchange_data(amount, fill_amount);
+    data.extend_from_slice(&max_payment.to_le_bytes());
+    data
+}
+
+pub fn cosigned_exchange_data(amount: u64) -> Vec<u8> {
+    let mut data = vec![2];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+pub fn migrate_escrow_data() -> Vec<u8> {
+    vec![10]
+}
+
+pub fn init_escrow_native_data(amount: u64, offered_amount: u64) -> Vec<u8> {
+    let mut data = vec![11];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data
+}
+
+pub fn update_expected_amount_data(new_amount: u64) -> Vec<u8> {
+    let mut data = vec![12];
+    data.extend_from_slice(&new_amount.to_le_bytes());
+    data
+}
+
+pub fn init_escrow_private_data(
+    amount: u64,
+    offered_amount: u64,
+    allowed_taker: &Pubkey,
+) -> Vec<u8> {
+    let mut data = vec![13];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data.extend_from_slice(allowed_taker.as_ref());
+    data
+}
+
+pub fn reclaim_expired_data() -> Vec<u8> {
+    vec![14]
+}
+
+pub fn init_config_data(unpause_delay: i64, max_expiry_horizon_secs: i64) -> Vec<u8> {
+    let mut data = vec![15];
+    data.extend_from_slice(&unpause_delay.to_le_bytes());
+    data.extend_from_slice(&max_expiry_horizon_secs.to_le_bytes());
+    data
+}
+
+pub fn set_paused_data(paused: bool) -> Vec<u8> {
+    vec![16, paused as u8]
+}
+
+pub fn init_basket_escrow_data(amount: u64, basket_len: u8) -> Vec<u8> {
+    let mut data = vec![17];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(basket_len);
+    data
+}
+
+pub fn exchange_basket_data(amount: u64, basket_len: u8, max_payment: Option<u64>) -> Vec<u8> {
+    let mut data = vec![18];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(basket_len);
+    if let Some(max_payment) = max_payment {
+        data.extend_from_slice(&max_payment.to_le_bytes());
+    }
+    data
+}
+
+pub fn deposit_more_data(additional: u64, expected_amount: Option<u64>) -> Vec<u8> {
+    let mut data = vec![19];
+    data.extend_from_slice(&additional.to_le_bytes());
+    if let Some(expected_amount) = expected_amount {
+        data.extend_from_slice(&expected_amount.to_le_bytes());
+    }
+    data
+}
+
+pub fn init_dutch_auction_data(
+    offered_amount: u64,
+    start_amount: u64,
+    end_amount: u64,
+    start_unix_timestamp: i64,
+    end_unix_timestamp: i64,
+) -> Vec<u8> {
+    let mut data = vec![20];
+    data.extend_from_slice(&offered_amount.to_le_bytes());
+    data.extend_from_slice(&start_amount.to_le_bytes());
+    data.extend_from_slice(&end_amount.to_le_bytes());
+    data.extend_from_slice(&start_unix_timestamp.to_le_bytes());
+    data.extend_from_slice(&end_unix_timestamp.to_le_bytes());
+    data
+}
+
+pub fn exchange_dutch_auction_data(amount: u64, quoted_payment: u64, tolerance: u64) -> Vec<u8> {
+    let mut data = vec![21];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&quoted_payment.to_le_bytes());
+    data.extend_from_slice(&tolerance.to_le_bytes());
+    data
+}
+
+pub fn transfer_initializer_data(
+    new_initializer: &Pubkey,
+    new_token_to_receive_account: Option<&Pubkey>,
+) -> Vec<u8> {
+    let mut data = vec![22];
+    data.extend_from_slice(new_initializer.as_ref());
+    if let Some(new_token_to_receive_account) = new_token_to_receive_account {
+        data.extend_from_slice(new_token_to_receive_account.as_ref());
+    }
+    data
+}
+
+pub fn refund_taker_data(amount: u64) -> Vec<u8> {
+    let mut data = vec![23];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+pub fn set_receive_account_data() -> Vec<u8> {
+    vec![24]
+}
+
+#[cfg(feature = "create-receive-account")]
+pub fn set_receive_ata_data() -> Vec<u8> {
+    vec![26]
+}
+
+pub fn withdraw_data(amount: u64) -> Vec<u8> {
+    let mut data = vec![25];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+/// Alice offers `offered` X tokens out of the `deposit` in her temp account for
+/// `expected` Y tokens, Bob holds enough Y to take the trade.
+pub struct Trade {
+    pub mint_x: AccountInfo<'static>,
+    pub mint_y: AccountInfo<'static>,
+    pub alice: AccountInfo<'static>,
+    pub alice_temp_x: AccountInfo<'static>,
+    pub alice_y: AccountInfo<'static>,
+    pub escrow: AccountInfo<'static>,
+    pub bob: AccountInfo<'static>,
+    pub bob_y: AccountInfo<'static>,
+    pub bob_x: AccountInfo<'static>,
+    pub pda: AccountInfo<'static>,
+    pub rent: AccountInfo<'static>,
+    pub clock: AccountInfo<'static>,
+    pub token_program: AccountInfo<'static>,
+    pub config: AccountInfo<'static>,
+    pub offered: u64,
+    pub expected: u64,
+}
+
+impl Trade {
+    pub fn new(offered: u64, expected: u64) -> Self {
+        Self::with_deposit(offered, offered, expected)
+    }
+
+    pub fn with_deposit(deposit: u64, offered: u64, expected: u64) -> Self {
+        Self::in_program(&spl_token::id(), deposit, offered, expected)
+    }
+
+    /// The same trade with both mints and every token account under Token-2022
+    pub fn token_2022(offered: u64, expected: u64) -> Self {
+        Self::in_program(&token_2022::id(), offered, offered, expected)
+    }
+
+    fn in_program(token_program: &Pubkey, deposit: u64, offered: u64, expected: u64) -> Self {
+        install_stubs();
+        let mint_x = mint_account_in(token_program, 6);
+        let mint_y = mint_account_in(token_program, 9);
+        let alice = wallet(1_000_000_000);
+        let alice_temp_x = token_account_in(token_program, mint_x.key, alice.key, deposit);
+        let alice_y = token_account_in(token_program, mint_y.key, alice.key, 0);
+        let escrow = account(
+            Pubkey::new_unique(),
+            Rent::default().minimum_balance(Escrow::LEN),
+            vec![0; Escrow::LEN],
+            PROGRAM_ID,
+            false,
+            true,
+        );
+        let bob = wallet(1_000_000_000);
+        let bob_y = token_account_in(token_program, mint_y.key, bob.key, expected);
+        let bob_x = token_account_in(token_program, mint_x.key, bob.key, 0);
+        let pda = pda_account(escrow.key);
+        Trade {
+            mint_x,
+            mint_y,
+            alice,
+            alice_temp_x,
+            alice_y,
+            escrow,
+            bob,
+            bob_y,
+            bob_x,
+            pda,
+            rent: rent_sysvar(),
+            clock: clock_sysvar(NOW),
+            token_program: token_program_account(token_program),
+            config: config_account(),
+            offered,
+            expected,
+        }
+    }
+
+    pub fn init_accounts(&self) -> Vec<AccountInfo<'static>> {
+        vec![
+            self.alice.clone(),
+            self.alice_temp_x.clone(),
+            self.alice_y.clone(),
+            self.escrow.clone(),
+            self.rent.clone(),
+            self.token_program.clone(),
+            self.config.clone(),
+        ]
+    }
+
+    pub fn exchange_accounts(&self) -> Vec<AccountInfo<'static>> {
+        vec![
+            self.bob.clone(),
+            self.bob_y.clone(),
+            self.bob_x.clone(),
+            self.alice_temp_x.clone(),
+            self.alice.clone(),
+            self.alice_y.clone(),
+            self.escrow.clone(),
+            self.token_program.clone(),
+            self.pda.clone(),
+            self.clock.clone(),
+            // an escrow without a fee names Alice's Y account as its fee account
+            self.alice_y.clone(),
+            self.mint_y.clone(),
+            self.mint_x.clone(),
+            self.config.clone(),
+        ]
+    }
+
+    /// The Arbitrate accounts, ending with Bob's X account as the release destination
+    pub fn arbitrate_accounts(&self, arbiter: &AccountInfo<'static>) -> Vec<AccountInfo<'static>> {
+        vec![
+            arbiter.clone(),
+            self.alice_temp_x.clone(),
+            self.alice.clone(),
+            self.escrow.clone(),
+            self.token_program.clone(),
+            self.pda.clone(),
+            self.bob_x.clone(),
+            self.mint_x.clone(),
+        ]
+    }
+
+    pub fn init(&self) -> ProgramResult {
+        self.init_with(&self.init_accounts())
+    }
+
+    pub fn init_with(&self, accounts: &[AccountInfo<'static>]) -> ProgramResult {
+        Processor::process(
+            &PROGRAM_ID,
+            accounts,
+            &init_escrow_data(self.expected, self.offered),
+        )
+    }
+
+    pub fn exchange(&self, amount: u64) -> ProgramResult {
+        self.exchange_with(&self.exchange_accounts(), amount)
+    }
+
+    pub fn exchange_with(&self, accounts: &[AccountInfo<'static>], amount: u64) -> ProgramResult {
+        Processor::process(&PROGRAM_ID, accounts, &exchange_data(amount))
+    }
+
+    pub fn escrow_state(&self) -> Escrow {
+        Escrow::unpack_unchecked(&self.escrow.data.borrow()).unwrap()
+    }
+}
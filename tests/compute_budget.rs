@@ -0,0 +1,74 @@
+//! What Exchange spends, pinned so a change that adds to it shows up in review.
+//!
+//! The native harness has no compute meter, so these count what drives the exchange's
+//! compute units on chain instead: every CPI (a fixed cost plus whatever the token program
+//! spends) and every logged line (a fixed cost each, the token program's included). A change
+//! that moves a baseline should update it here and say why in the PR.
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::processor::Processor;
+
+use common::{
+    init_escrow_with_fee_data, take_cpi_count, take_logs, token_account, token_balance, wallet,
+    Trade, PROGRAM_ID,
+};
+
+/// Transfer to the initializer, transfer to the taker, close the vault
+const EXCHANGE_CPIS: usize = 3;
+/// The escrow's own lines and the token program's for each of its instructions
+const EXCHANGE_LOG_LINES: usize = 9;
+
+/// Runs `exchange` on a fresh count and hands back the CPIs it made and the lines it logged
+fn measure(exchange: impl FnOnce()) -> (usize, usize) {
+    take_cpi_count();
+    take_logs();
+    exchange();
+    (take_cpi_count(), take_logs().len())
+}
+
+#[test]
+fn exchange_stays_within_its_baseline() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    let (cpis, log_lines) = measure(|| trade.exchange(100).unwrap());
+
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(cpis, EXCHANGE_CPIS);
+    assert!(
+        log_lines <= EXCHANGE_LOG_LINES,
+        "{} lines logged, baseline {}",
+        log_lines,
+        EXCHANGE_LOG_LINES
+    );
+}
+
+#[test]
+fn fee_costs_one_more_transfer_and_surplus_none() {
+    let trade = Trade::with_deposit(120, 100, 1_000);
+    let operator = wallet(0);
+    let fee = token_account(trade.mint_y.key, operator.key, 0);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_with_fee_data(1_000, 100, 30, fee.key),
+    )
+    .unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[10] = fee.clone();
+
+    let (cpis, log_lines) = measure(|| trade.exchange_with(&accounts, 100).unwrap());
+
+    assert_eq!(token_balance(&fee), 3);
+    // the surplus hands the vault back instead of closing it, one CPI either way
+    assert_eq!(token_balance(&trade.alice_temp_x), 20);
+    assert_eq!(cpis, EXCHANGE_CPIS + 1);
+    assert!(
+        log_lines <= EXCHANGE_LOG_LINES + 2,
+        "{} lines logged, baseline {}",
+        log_lines,
+        EXCHANGE_LOG_LINES + 2
+    );
+}
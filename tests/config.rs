@@ -0,0 +1,282 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{
+    error::EscrowError,
+    processor::Processor,
+    state::{config_address, Config, DEFAULT_MAX_EXPIRY_HORIZON_SECS},
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    program_pack::Pack, pubkey::Pubkey,
+};
+
+use common::{
+    account, expiring_init_escrow_data, init_config_data, program_data, set_clock,
+    set_paused_data, system_program, token_balance, wallet, Trade, NOW, PROGRAM_ID,
+};
+
+/// The config account InitConfig creates, already sized and assigned as the system
+/// program would leave it; the harness's CreateAccount only moves the lamports
+fn created_config() -> AccountInfo<'static> {
+    account(
+        config_address(&PROGRAM_ID).0,
+        0,
+        vec![0; Config::LEN],
+        PROGRAM_ID,
+        false,
+        true,
+    )
+}
+
+fn init_config(trade: &Trade, admin: &AccountInfo<'static>) -> ProgramResult {
+    init_config_with_delay(trade, admin, 0)
+}
+
+fn init_config_with_delay(
+    trade: &Trade,
+    admin: &AccountInfo<'static>,
+    unpause_delay: i64,
+) -> ProgramResult {
+    init_config_with(trade, admin, unpause_delay, DEFAULT_MAX_EXPIRY_HORIZON_SECS)
+}
+
+fn init_config_with(
+    trade: &Trade,
+    admin: &AccountInfo<'static>,
+    unpause_delay: i64,
+    max_expiry_horizon_secs: i64,
+) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &[
+            admin.clone(),
+            trade.config.clone(),
+            system_program(),
+            trade.rent.clone(),
+            program_data(Some(admin.key)),
+        ],
+        &init_config_data(unpause_delay, max_expiry_horizon_secs),
+    )
+}
+
+fn set_paused(trade: &Trade, admin: &AccountInfo<'static>, paused: bool) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &[admin.clone(), trade.config.clone()],
+        &set_paused_data(paused),
+    )
+}
+
+/// Alice's 100 X for 50 Y, expiring at `expiry`
+fn init_expiring(trade: &Trade, expiry: i64) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &expiring_init_escrow_data(50, 100, expiry),
+    )
+}
+
+/// A trade using `config` in place of its own config account
+fn trade_with(config: &AccountInfo<'static>) -> Trade {
+    let mut trade = Trade::new(100, 50);
+    trade.config = config.clone();
+    trade
+}
+
+#[test]
+fn pausing_blocks_new_escrows_and_exchanges() {
+    let admin = wallet(1_000_000_000);
+    let config = created_config();
+    let live = trade_with(&config);
+    init_config(&live, &admin).unwrap();
+    assert!(!Config::unpack(&config.data.borrow()).unwrap().paused);
+    live.init().unwrap();
+
+    set_paused(&live, &admin, true).unwrap();
+    let fresh = trade_with(&config);
+    assert_eq!(fresh.init(), Err(EscrowError::ProgramPaused.into()));
+    assert_eq!(live.exchange(100), Err(EscrowError::ProgramPaused.into()));
+    assert_eq!(token_balance(&live.alice_temp_x), 100);
+
+    set_paused(&live, &admin, false).unwrap();
+    fresh.init().unwrap();
+    live.exchange(100).unwrap();
+    assert_eq!(token_balance(&live.bob_x), 100);
+}
+
+#[test]
+fn unpausing_waits_out_the_timelock() {
+    let admin = wallet(1_000_000_000);
+    let config = created_config();
+    let live = trade_with(&config);
+    assert_eq!(
+        init_config_with_delay(&live, &admin, -1),
+        Err(EscrowError::InvalidAmount.into())
+    );
+    init_config_with_delay(&live, &admin, 3_600).unwrap();
+    live.init().unwrap();
+    let fresh = trade_with(&config);
+    set_paused(&live, &admin, true).unwrap();
+
+    set_clock(&live.clock, NOW + 10);
+    set_paused(&live, &admin, false).unwrap();
+    let unpause_at = NOW + 10 + 3_600;
+    assert_eq!(Config::unpack(&config.data.borrow()).unwrap().pause_until, unpause_at);
+    // restarting again doesn't push the end out
+    set_clock(&live.clock, NOW + 20);
+    set_paused(&live, &admin, false).unwrap();
+    assert_eq!(Config::unpack(&config.data.borrow()).unwrap().pause_until, unpause_at);
+
+    set_clock(&live.clock, unpause_at - 1);
+    assert_eq!(fresh.init(), Err(EscrowError::StillPaused.into()));
+    assert_eq!(live.exchange(100), Err(EscrowError::StillPaused.into()));
+    assert_eq!(token_balance(&live.alice_temp_x), 100);
+
+    set_clock(&live.clock, unpause_at);
+    fresh.init().unwrap();
+    live.exchange(100).unwrap();
+    assert_eq!(token_balance(&live.bob_x), 100);
+
+    // pausing is never held back
+    set_paused(&live, &admin, true).unwrap();
+    assert_eq!(
+        trade_with(&config).init(),
+        Err(EscrowError::ProgramPaused.into())
+    );
+}
+
+#[test]
+fn only_the_admin_can_pause() {
+    let admin = wallet(1_000_000_000);
+    let trade = trade_with(&created_config());
+    init_config(&trade, &admin).unwrap();
+    assert_eq!(
+        init_config(&trade, &trade.bob),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+
+    assert_eq!(
+        set_paused(&trade, &trade.bob, true),
+        Err(EscrowError::NotAdmin.into())
+    );
+    let mut unsigned = admin.clone();
+    unsigned.is_signer = false;
+    assert_eq!(
+        set_paused(&trade, &unsigned, true),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert!(!Config::unpack(&trade.config.data.borrow()).unwrap().paused);
+    trade.init().unwrap();
+}
+
+#[test]
+fn only_the_upgrade_authority_can_init_the_config() {
+    let deployer = wallet(1_000_000_000);
+    let stranger = wallet(1_000_000_000);
+    let trade = trade_with(&created_config());
+    let init_as = |admin: &AccountInfo<'static>, program_data: AccountInfo<'static>| {
+        Processor::process(
+            &PROGRAM_ID,
+            &[
+                admin.clone(),
+                trade.config.clone(),
+                system_program(),
+                trade.rent.clone(),
+                program_data,
+            ],
+            &init_config_data(0, DEFAULT_MAX_EXPIRY_HORIZON_SECS),
+        )
+    };
+
+    // front-running the deploy gets a stranger nothing
+    assert_eq!(
+        init_as(&stranger, program_data(Some(deployer.key))),
+        Err(EscrowError::NotAdmin.into())
+    );
+    assert_eq!(
+        init_as(&stranger, program_data(None)),
+        Err(EscrowError::NotAdmin.into())
+    );
+    let mut look_alike = program_data(Some(stranger.key));
+    look_alike.key = Box::leak(Box::new(Pubkey::new_unique()));
+    assert_eq!(
+        init_as(&stranger, look_alike),
+        Err(EscrowError::AccountMismatch.into())
+    );
+    let mut not_the_loaders = program_data(Some(stranger.key));
+    not_the_loaders.owner = Box::leak(Box::new(PROGRAM_ID));
+    assert_eq!(
+        init_as(&stranger, not_the_loaders),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    assert!(!Config::unpack_unchecked(&trade.config.data.borrow()).unwrap().is_initialized);
+
+    init_as(&deployer, program_data(Some(deployer.key))).unwrap();
+    assert_eq!(
+        Config::unpack(&trade.config.data.borrow()).unwrap().admin,
+        *deployer.key
+    );
+}
+
+#[test]
+fn config_account_must_be_the_programs() {
+    let mut trade = Trade::new(100, 50);
+    trade.config = wallet(0);
+    assert_eq!(trade.init(), Err(EscrowError::AccountMismatch.into()));
+
+    // a look-alike at another address can't pause the program for anyone
+    let admin = wallet(1_000_000_000);
+    let mut data = vec![0; Config::LEN];
+    Config::pack(
+        Config {
+            is_initialized: true,
+            admin: *admin.key,
+            paused: true,
+            unpause_delay: 0,
+            pause_until: 0,
+            max_expiry_horizon_secs: DEFAULT_MAX_EXPIRY_HORIZON_SECS,
+        },
+        &mut data,
+    )
+    .unwrap();
+    trade.config = account(Pubkey::new_unique(), 1, data, PROGRAM_ID, false, true);
+    assert_eq!(trade.init(), Err(EscrowError::AccountMismatch.into()));
+}
+
+#[test]
+fn expiry_stays_within_the_default_horizon() {
+    let trade = Trade::new(100, 50);
+    assert_eq!(
+        init_expiring(&trade, NOW + DEFAULT_MAX_EXPIRY_HORIZON_SECS + 1),
+        Err(EscrowError::ExpiryTooFar.into())
+    );
+    init_expiring(&trade, NOW + DEFAULT_MAX_EXPIRY_HORIZON_SECS).unwrap();
+}
+
+#[test]
+fn expiry_stays_within_the_configured_horizon() {
+    let admin = wallet(1_000_000_000);
+    let config = created_config();
+    let trade = trade_with(&config);
+    assert_eq!(
+        init_config_with(&trade, &admin, 0, 0),
+        Err(EscrowError::InvalidAmount.into())
+    );
+    init_config_with(&trade, &admin, 0, 86_400).unwrap();
+    assert_eq!(
+        Config::unpack(&config.data.borrow()).unwrap().max_expiry_horizon_secs,
+        86_400
+    );
+
+    assert_eq!(
+        init_expiring(&trade, NOW + 86_401),
+        Err(EscrowError::ExpiryTooFar.into())
+    );
+    // the horizon runs from the time of init, not from InitConfig
+    set_clock(&trade.clock, NOW + 1);
+    init_expiring(&trade, NOW + 86_401).unwrap();
+    // no expiry at all is never too far
+    trade_with(&config).init().unwrap();
+}
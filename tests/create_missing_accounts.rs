@@ -0,0 +1,111 @@
+#![cfg(all(feature = "program", feature = "create-receive-account"))]
+
+mod common;
+
+use solana_escrow::{processor::Processor, token};
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, rent::Rent,
+};
+use spl_token::state::Account as TokenAccount;
+
+use common::{
+    associated_token_program, creating_exchange_data, fresh_account, missing_associated_account,
+    process_transaction, system_program, token_balance, token_owner, Trade, PROGRAM_ID,
+};
+
+/// The Exchange accounts with the two programs a creating Exchange adds
+fn creating_accounts(trade: &Trade) -> Vec<AccountInfo<'static>> {
+    let mut accounts = trade.exchange_accounts();
+    accounts.extend([associated_token_program(), system_program()]);
+    accounts
+}
+
+#[test]
+fn exchange_recreates_the_receive_account_the_initializer_closed() {
+    let trade = Trade::new(100, 50);
+    let alice_y = missing_associated_account(trade.alice.key, trade.mint_y.key);
+    let everyone = [
+        trade.alice.clone(),
+        alice_y.clone(),
+        trade.mint_y.clone(),
+        system_program(),
+        trade.token_program.clone(),
+    ];
+    process_transaction(
+        &[token::create_associated_account(
+            trade.alice.key,
+            trade.alice.key,
+            trade.mint_y.key,
+            &spl_token::id(),
+        )],
+        &everyone,
+    )
+    .unwrap();
+    let mut accounts = trade.init_accounts();
+    accounts[2] = alice_y.clone();
+    trade.init_with(&accounts).unwrap();
+
+    // still empty, Alice closes it after making the offer
+    process_transaction(
+        &[spl_token::instruction::close_account(
+            &spl_token::id(),
+            alice_y.key,
+            trade.alice.key,
+            trade.alice.key,
+            &[],
+        )
+        .unwrap()],
+        &everyone,
+    )
+    .unwrap();
+    alice_y.data.borrow_mut().fill(0); // as the runtime leaves an account with no lamports
+    let mut accounts = creating_accounts(&trade);
+    accounts[5] = alice_y.clone();
+    accounts[10] = alice_y.clone();
+
+    let bob_lamports = trade.bob.lamports();
+    Processor::process(&PROGRAM_ID, &accounts, &creating_exchange_data(100)).unwrap();
+
+    let rent = Rent::default().minimum_balance(TokenAccount::LEN);
+    assert_eq!(token_balance(&alice_y), 50);
+    assert_eq!(token_owner(&alice_y), *trade.alice.key);
+    assert_eq!(alice_y.lamports(), rent);
+    assert_eq!(trade.bob.lamports(), bob_lamports - rent);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn exchange_creates_only_missing_associated_accounts() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let bob_x = missing_associated_account(trade.bob.key, trade.mint_x.key);
+    let mut accounts = creating_accounts(&trade);
+
+    // an account that isn't there can only be made at its owner's associated address
+    accounts[2] = fresh_account(TokenAccount::LEN, spl_token::id());
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &creating_exchange_data(100)),
+        Err(ProgramError::InvalidSeeds)
+    );
+    accounts[2] = bob_x.clone();
+    accounts.swap(14, 15);
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &creating_exchange_data(100)),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    // Alice's account exists, so only Bob's is created
+    accounts.swap(14, 15);
+    let bob_lamports = trade.bob.lamports();
+    Processor::process(&PROGRAM_ID, &accounts, &creating_exchange_data(100)).unwrap();
+
+    assert_eq!(token_balance(&bob_x), 100);
+    assert_eq!(token_owner(&bob_x), *trade.bob.key);
+    assert_eq!(
+        trade.bob.lamports(),
+        bob_lamports - Rent::default().minimum_balance(TokenAccount::LEN)
+    );
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
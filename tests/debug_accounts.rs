@@ -0,0 +1,58 @@
+#![cfg(all(feature = "program", feature = "debug-accounts"))]
+
+mod common;
+
+use solana_escrow::{
+    debug_accounts::{append_roles, first_mismatch, AccountRole},
+    error::EscrowError,
+    processor::Processor,
+};
+
+use common::{exchange_data, token_balance, Trade, PROGRAM_ID};
+
+const EXCHANGE_ROLES: [AccountRole; 10] = [
+    AccountRole::Signer,
+    AccountRole::TokenAccount,
+    AccountRole::TokenAccount,
+    AccountRole::TokenAccount,
+    AccountRole::Any,
+    AccountRole::TokenAccount,
+    AccountRole::EscrowState,
+    AccountRole::TokenProgram,
+    AccountRole::Any,
+    AccountRole::ClockSysvar,
+];
+
+fn marked_exchange_data(amount: u64) -> Vec<u8> {
+    let mut data = exchange_data(amount);
+    append_roles(&mut data, &EXCHANGE_ROLES);
+    data
+}
+
+#[test]
+fn marked_exchange_runs_when_roles_match() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    Processor::process(&PROGRAM_ID, &trade.exchange_accounts(), &marked_exchange_data(100))
+        .unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn swapped_accounts_are_pinned_to_their_slot() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts.swap(6, 7);
+
+    assert_eq!(
+        first_mismatch(&PROGRAM_ID, &accounts, &EXCHANGE_ROLES),
+        Some(6)
+    );
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &marked_exchange_data(100)),
+        Err(EscrowError::AccountRoleMismatch.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
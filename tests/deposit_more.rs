@@ -0,0 +1,120 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+
+use common::{
+    capped_exchange_data, deposit_more_data, token_account, token_balance, Trade, PROGRAM_ID,
+};
+
+fn deposit_more_accounts(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    source: &AccountInfo<'static>,
+) -> Vec<AccountInfo<'static>> {
+    vec![
+        signer.clone(),
+        source.clone(),
+        trade.alice_temp_x.clone(),
+        trade.escrow.clone(),
+        trade.token_program.clone(),
+        trade.mint_x.clone(),
+    ]
+}
+
+fn deposit_more(
+    trade: &Trade,
+    source: &AccountInfo<'static>,
+    additional: u64,
+    expected_amount: Option<u64>,
+) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &deposit_more_accounts(trade, &trade.alice, source),
+        &deposit_more_data(additional, expected_amount),
+    )
+}
+
+#[test]
+fn topped_up_escrow_trades_the_larger_offer() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 60);
+
+    deposit_more(&trade, &alice_x, 60, Some(80)).unwrap();
+    assert_eq!(token_balance(&alice_x), 0);
+    assert_eq!(token_balance(&trade.alice_temp_x), 160);
+    let escrow = trade.escrow_state();
+    assert_eq!(escrow.offered_amount, 160);
+    assert_eq!(escrow.expected_amount, 80);
+
+    // the old offer is still within a taker's minimum, but they pay the new price for the new one
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::InsufficientTakerFunds.into())
+    );
+    let mut accounts = trade.exchange_accounts();
+    let bob_y = token_account(trade.mint_y.key, trade.bob.key, 80);
+    accounts[1] = bob_y.clone();
+    trade.exchange_with(&accounts, 160).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 160);
+    assert_eq!(token_balance(&trade.alice_y), 80);
+    assert_eq!(token_balance(&bob_y), 0);
+}
+
+#[test]
+fn top_up_cannot_raise_the_price_past_the_takers_cap() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    let bob_y = token_account(trade.mint_y.key, trade.bob.key, 100);
+    accounts[1] = bob_y.clone();
+
+    // Bob signs for 100 at 50; a token more on the offer keeps his minimum met, and the
+    // price riding along with it lands before him
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 1);
+    deposit_more(&trade, &alice_x, 1, Some(90)).unwrap();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &capped_exchange_data(100, 50)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(token_balance(&bob_y), 100);
+    assert_eq!(token_balance(&trade.alice_temp_x), 101);
+
+    Processor::process(&PROGRAM_ID, &accounts, &capped_exchange_data(100, 90)).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 101);
+    assert_eq!(token_balance(&bob_y), 10);
+}
+
+#[test]
+fn only_the_initializer_tops_up_with_the_offered_token() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 60);
+
+    assert_eq!(
+        deposit_more(&trade, &alice_x, 0, None),
+        Err(EscrowError::InvalidAmount.into())
+    );
+    assert_eq!(
+        deposit_more(&trade, &trade.alice_y, 10, None),
+        Err(EscrowError::MintMismatch.into())
+    );
+    let bob_x = token_account(trade.mint_x.key, trade.bob.key, 60);
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &deposit_more_accounts(&trade, &trade.bob, &bob_x),
+            &deposit_more_data(10, None),
+        ),
+        Err(EscrowError::NotInitializer.into())
+    );
+
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(trade.escrow_state().offered_amount, 100);
+    // without a new expected amount the price stays put
+    deposit_more(&trade, &alice_x, 60, None).unwrap();
+    assert_eq!(trade.escrow_state().expected_amount, 50);
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::entrypoint::ProgramResult;
+
+use common::{
+    exchange_dutch_auction_data, init_dutch_auction_data, partial_exchange_data, set_clock,
+    token_balance, update_expected_amount_data, Trade, NOW, PROGRAM_ID,
+};
+
+/// Alice auctions 100 X, asking 80 Y at `NOW` and falling to 20 Y a minute later
+fn auction() -> Trade {
+    let trade = Trade::new(100, 80);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_dutch_auction_data(100, 80, 20, NOW, NOW + 60),
+    )
+    .unwrap();
+    trade
+}
+
+fn take(trade: &Trade, quoted_payment: u64, tolerance: u64) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.exchange_accounts(),
+        &exchange_dutch_auction_data(100, quoted_payment, tolerance),
+    )
+}
+
+#[test]
+fn auction_payment_falls_as_the_clock_advances() {
+    let schedule = [
+        (NOW - 60, 80),
+        (NOW, 80),
+        (NOW + 15, 65),
+        (NOW + 30, 50),
+        (NOW + 45, 35),
+        (NOW + 60, 20),
+        (NOW + 600, 20),
+    ];
+    for (now, payment) in schedule {
+        let trade = auction();
+        assert_eq!(trade.escrow_state().expected_amount, 80);
+        set_clock(&trade.clock, now);
+
+        // an exact quote leaves no room, so it only lands at the price the clock sets
+        take(&trade, payment, 0).unwrap();
+        assert_eq!(token_balance(&trade.alice_y), payment);
+        assert_eq!(token_balance(&trade.bob_y), 80 - payment);
+        assert_eq!(token_balance(&trade.bob_x), 100);
+    }
+}
+
+#[test]
+fn auction_holds_the_taker_to_their_quote_and_its_own_instruction() {
+    let trade = auction();
+    set_clock(&trade.clock, NOW + 30);
+
+    // quoted at NOW + 15, the price has fallen 15 since
+    assert_eq!(take(&trade, 65, 10), Err(EscrowError::AuctionPriceMoved.into()));
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::InvalidInstruction.into())
+    );
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &trade.exchange_accounts(),
+            &partial_exchange_data(100, 50),
+        ),
+        Err(EscrowError::InvalidInstruction.into())
+    );
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &[trade.alice.clone(), trade.escrow.clone()],
+            &update_expected_amount_data(10),
+        ),
+        Err(EscrowError::InvalidPrice.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    take(&trade, 65, 15).unwrap();
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+
+    // and a fixed-price escrow can't be taken as an auction
+    let fixed = Trade::new(100, 50);
+    fixed.init().unwrap();
+    assert_eq!(take(&fixed, 50, 0), Err(EscrowError::InvalidInstruction.into()));
+}
+
+#[test]
+fn auction_price_must_fall_over_a_window() {
+    for (start_amount, end_amount, end_unix_timestamp) in
+        [(80, 90, NOW + 60), (80, 0, NOW + 60), (80, 20, NOW)]
+    {
+        let trade = Trade::new(100, 80);
+        assert_eq!(
+            Processor::process(
+                &PROGRAM_ID,
+                &trade.init_accounts(),
+                &init_dutch_auction_data(100, start_amount, end_amount, NOW, end_unix_timestamp),
+            ),
+            Err(EscrowError::InvalidPrice.into())
+        );
+        assert!(!trade.escrow_state().is_initialized);
+    }
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::event::{EscrowEvent, EscrowExchanged, EscrowInitialized};
+
+use common::{take_logs, Trade};
+
+fn events() -> Vec<EscrowEvent> {
+    take_logs()
+        .iter()
+        .filter_map(|line| EscrowEvent::from_log(line))
+        .collect()
+}
+
+#[test]
+fn init_and_exchange_log_one_event_each() {
+    let trade = Trade::new(100, 50);
+    take_logs();
+
+    trade.init().unwrap();
+    assert_eq!(
+        events(),
+        vec![EscrowEvent::Initialized(EscrowInitialized {
+            escrow: *trade.escrow.key,
+            initializer: *trade.alice.key,
+            temp_account: *trade.alice_temp_x.key,
+            expected_amount: 50,
+            offered_amount: 100,
+        })]
+    );
+
+    trade.exchange(100).unwrap();
+    assert_eq!(
+        events(),
+        vec![EscrowEvent::Exchanged(EscrowExchanged {
+            escrow: *trade.escrow.key,
+            taker: *trade.bob.key,
+            initializer: *trade.alice.key,
+            amount: 100,
+            payment: 50,
+        })]
+    );
+
+    // a failed exchange has nothing to report
+    assert!(trade.exchange(100).is_err());
+    assert_eq!(events(), vec![]);
+}
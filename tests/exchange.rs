@@ -0,0 +1,838 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{
+    error::EscrowError,
+    processor::Processor,
+    state::{
+        vault_authority, Escrow, EscrowStatus, RoundingMode, Side, ESCROW_VERSION, MAX_BASKET_LEN,
+        VAULT_AUTHORITY_SEED,
+    },
+};
+use solana_program::{
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, rent::Rent,
+};
+use spl_token::error::TokenError;
+use std::{cell::RefCell, rc::Rc};
+
+use common::{
+    account, before_next_cpi, exchange_data, init_escrow_native_data, min_fill_init_escrow_data,
+    cosigned_exchange_data, expiring_init_escrow_data, freeze, init_escrow_at_price_data, init_escrow_with_fee_data, mint_account, partial_exchange_data, pda_account,
+    pinned_exchange_data, scheduled_init_escrow_data,
+    set_clock, token_account, token_program_account, wallet, token_balance, token_owner, Trade,
+    NOW, PROGRAM_ID,
+};
+
+#[test]
+fn exchange_swaps_tokens_and_closes_escrow() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_lamports = trade.alice.lamports();
+    let reclaimed = trade.alice_temp_x.lamports() + trade.escrow.lamports();
+
+    trade.exchange(100).unwrap();
+
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 0);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(trade.alice_temp_x.lamports(), 0);
+    assert_eq!(trade.escrow.lamports(), 0);
+    assert_eq!(trade.escrow.data_len(), 0);
+    assert_eq!(trade.alice.lamports(), alice_lamports + reclaimed);
+}
+
+/// Another of the first trade's initializer's escrows, in the same mints and paying into
+/// the same Y account
+fn another_escrow(first: &Trade, offered: u64, expected: u64) -> Trade {
+    let mut trade = Trade::new(offered, expected);
+    trade.mint_x = first.mint_x.clone();
+    trade.mint_y = first.mint_y.clone();
+    trade.alice = first.alice.clone();
+    trade.alice_temp_x = token_account(first.mint_x.key, first.alice.key, offered);
+    trade.alice_y = first.alice_y.clone();
+    trade.bob_y = token_account(first.mint_y.key, trade.bob.key, expected);
+    trade.bob_x = token_account(first.mint_x.key, trade.bob.key, 0);
+    trade
+}
+
+#[test]
+fn one_initializer_runs_independent_escrows() {
+    let first = Trade::new(100, 50);
+    let second = another_escrow(&first, 200, 20);
+    let third = another_escrow(&first, 300, 30);
+    for trade in [&first, &second, &third] {
+        trade.init().unwrap();
+    }
+    // each vault has its own authority, seeded with its escrow account
+    assert_ne!(first.pda.key, second.pda.key);
+    assert_ne!(second.pda.key, third.pda.key);
+    assert_eq!(token_owner(&third.alice_temp_x), *third.pda.key);
+
+    // another escrow's vault authority can't sign for this one
+    let mut accounts = second.exchange_accounts();
+    accounts[8] = first.pda.clone();
+    assert_eq!(
+        second.exchange_with(&accounts, 200),
+        Err(EscrowError::InvalidPda.into())
+    );
+
+    second.exchange(200).unwrap();
+    assert_eq!(token_balance(&second.bob_x), 200);
+    assert_eq!(token_balance(&first.alice_y), 20);
+    assert_eq!(first.escrow_state().offered_amount, 100);
+    assert_eq!(token_balance(&third.alice_temp_x), 300);
+
+    first.exchange(100).unwrap();
+    third.exchange(300).unwrap();
+    assert_eq!(token_balance(&first.bob_x), 100);
+    assert_eq!(token_balance(&third.bob_x), 300);
+    assert_eq!(token_balance(&first.alice_y), 100);
+    assert_eq!(third.escrow.data_len(), 0);
+}
+
+#[test]
+fn exchange_rejects_aliased_transfer_accounts() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    // the taker's sending account is the initializer's receiving account
+    let mut accounts = trade.exchange_accounts();
+    accounts[1] = trade.alice_y.clone();
+
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::DuplicateAccount.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
+
+#[test]
+fn second_exchange_in_same_transaction_fails_cleanly() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    trade.exchange(100).unwrap();
+
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::EscrowNotInitialized.into())
+    );
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn exchange_nested_under_its_own_transfers_finds_the_escrow_settled() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    let nested = Rc::new(RefCell::new(None));
+    let (accounts, result) = (trade.exchange_accounts(), Rc::clone(&nested));
+    before_next_cpi(move || {
+        let data = exchange_data(100);
+        *result.borrow_mut() = Some(Processor::process(&PROGRAM_ID, &accounts, &data));
+    });
+    trade.exchange(100).unwrap();
+
+    assert_eq!(
+        nested.borrow_mut().take(),
+        Some(Err(EscrowError::EscrowNotInitialized.into()))
+    );
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 0);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert!(trade.escrow.data_is_empty());
+
+    // a partial fill is written down as taken before its tokens move too
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let nested = Rc::new(RefCell::new(None));
+    let (accounts, result) = (trade.exchange_accounts(), Rc::clone(&nested));
+    before_next_cpi(move || {
+        let data = partial_exchange_data(100, 80);
+        *result.borrow_mut() = Some(Processor::process(&PROGRAM_ID, &accounts, &data));
+    });
+    Processor::process(&PROGRAM_ID, &trade.exchange_accounts(), &partial_exchange_data(100, 80))
+        .unwrap();
+
+    assert_eq!(
+        nested.borrow_mut().take(),
+        Some(Err(EscrowError::ExpectedAmountMismatch.into()))
+    );
+    assert_eq!(token_balance(&trade.bob_x), 80);
+    assert_eq!(trade.escrow_state().offered_amount, 20);
+
+    // a native escrow pays out its lamports without a CPI, but is settled ahead of the payment
+    for (fill, nested_error) in [
+        (None, EscrowError::EscrowNotInitialized),
+        (Some(80), EscrowError::ExpectedAmountMismatch),
+    ] {
+        let trade = Trade::new(100, 50);
+        let escrow = account(
+            Pubkey::new_unique(),
+            Rent::default().minimum_balance(Escrow::LEN) + 100,
+            vec![0; Escrow::LEN],
+            PROGRAM_ID,
+            false,
+            true,
+        );
+        Processor::process(
+            &PROGRAM_ID,
+            &[
+                trade.alice.clone(),
+                trade.alice_y.clone(),
+                escrow.clone(),
+                trade.rent.clone(),
+                trade.config.clone(),
+            ],
+            &init_escrow_native_data(50, 100),
+        )
+        .unwrap();
+        let mut accounts = trade.exchange_accounts();
+        accounts[2] = trade.bob.clone();
+        accounts[3] = escrow.clone();
+        accounts[6] = escrow.clone();
+        accounts[12] = account(
+            spl_token::native_mint::id(),
+            0,
+            vec![],
+            spl_token::id(),
+            false,
+            false,
+        );
+        let data = match fill {
+            Some(fill) => partial_exchange_data(100, fill),
+            None => exchange_data(100),
+        };
+
+        let bob_lamports = trade.bob.lamports();
+        let nested = Rc::new(RefCell::new(None));
+        let (nested_accounts, nested_data, result) =
+            (accounts.clone(), data.clone(), Rc::clone(&nested));
+        before_next_cpi(move || {
+            *result.borrow_mut() =
+                Some(Processor::process(&PROGRAM_ID, &nested_accounts, &nested_data));
+        });
+        Processor::process(&PROGRAM_ID, &accounts, &data).unwrap();
+
+        assert_eq!(nested.borrow_mut().take(), Some(Err(nested_error.into())));
+        let fill = fill.unwrap_or(100);
+        assert_eq!(trade.bob.lamports(), bob_lamports + fill);
+        if fill < 100 {
+            assert_eq!(Escrow::unpack(&escrow.data.borrow()).unwrap().offered_amount, 20);
+        } else {
+            assert!(escrow.data_is_empty());
+        }
+    }
+}
+
+#[test]
+fn exchange_takes_any_offer_at_or_above_the_taker_minimum() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(
+        trade.exchange(101),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    // the minimum itself is enough
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn exchange_returns_surplus_deposit_to_initializer() {
+    let trade = Trade::with_deposit(120, 100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(
+        trade.exchange(120),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    trade.exchange(100).unwrap();
+
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 20);
+    assert_eq!(token_owner(&trade.alice_temp_x), *trade.alice.key);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn cosigned_exchange_requires_initializer_signature() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[4].is_signer = false;
+
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &cosigned_exchange_data(100)),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    accounts[4].is_signer = true;
+    Processor::process(&PROGRAM_ID, &accounts, &cosigned_exchange_data(100)).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn exchange_rejects_vault_not_held_by_pda() {
+    let trade = Trade::new(100, 50);
+    // state that names Alice's temp account as its vault without it ever being handed over
+    let escrow = Escrow {
+        version: ESCROW_VERSION,
+        is_initialized: true,
+        initializer_pubkey: *trade.alice.key,
+        temp_token_account_pubkey: *trade.alice_temp_x.key,
+        initializer_token_to_receive_account_pubkey: *trade.alice_y.key,
+        expected_amount: 50,
+        offered_amount: 100,
+        arbiter: None,
+        price: None,
+        side: Side::Sell,
+        bump: vault_authority(&PROGRAM_ID, trade.escrow.key).1,
+        expiry_unix_timestamp: 0,
+        expected_mint: *trade.mint_y.key,
+        deposited_mint: *trade.mint_x.key,
+        fee_basis_points: 0,
+        fee_account: *trade.alice_y.key,
+        deposited_is_native: false,
+        token_program: spl_token::id(),
+        allowed_taker: Pubkey::default(),
+        status: EscrowStatus::Active,
+        basket_len: 0,
+        basket: [Pubkey::default(); MAX_BASKET_LEN],
+        start_unix_timestamp: 0,
+        auction: None,
+        min_fill_amount: 0,
+        rounding: RoundingMode::FloorToMaker,
+    };
+    Escrow::pack(escrow, &mut trade.escrow.data.borrow_mut()).unwrap();
+
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::InvalidAccountOwner.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.bob_x), 0);
+}
+
+#[test]
+fn exchange_rejects_underfunded_taker() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[1] = token_account(trade.mint_y.key, trade.bob.key, 49);
+
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::InsufficientTakerFunds.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 0);
+}
+
+#[test]
+fn exchange_rejects_vault_amount_changed_since_quote() {
+    let trade = Trade::with_deposit(120, 100, 50);
+    trade.init().unwrap();
+    let accounts = trade.exchange_accounts();
+
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &pinned_exchange_data(100, 100)),
+        Err(EscrowError::VaultAmountChanged.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 120);
+
+    Processor::process(&PROGRAM_ID, &accounts, &pinned_exchange_data(100, 120)).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn priced_exchange_charges_for_the_live_vault() {
+    // one Y per three X, rounded up: 100 X quote at 34 Y, the 120 actually deposited cost 40
+    let trade = Trade::with_deposit(120, 100, 40);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_at_price_data(100, 1, 3),
+    )
+    .unwrap();
+    assert_eq!(trade.escrow_state().expected_amount, 34);
+
+    assert_eq!(
+        trade.exchange(121),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    // the 100 first offered is only the taker's minimum, they get the whole live vault
+    trade.exchange(100).unwrap();
+
+    assert_eq!(token_balance(&trade.bob_x), 120);
+    assert_eq!(token_balance(&trade.bob_y), 0);
+    assert_eq!(token_balance(&trade.alice_y), 40);
+    assert_eq!(trade.alice_temp_x.lamports(), 0);
+}
+
+#[test]
+fn init_escrow_at_price_rejects_unpayable_terms() {
+    let trade = Trade::new(100, 50);
+
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &trade.init_accounts(),
+            &init_escrow_at_price_data(100, 1, 0),
+        ),
+        Err(EscrowError::InvalidPrice.into())
+    );
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &trade.init_accounts(),
+            &init_escrow_at_price_data(100, u64::MAX, 1),
+        ),
+        Err(EscrowError::AmountOverflow.into())
+    );
+}
+
+#[test]
+fn exchange_rejects_frozen_initializer_receive_account() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    freeze(&trade.alice_y);
+
+    assert_eq!(trade.exchange(100), Err(EscrowError::AccountFrozen.into()));
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(trade.escrow.data_len(), Escrow::LEN);
+}
+
+#[test]
+fn each_escrow_has_its_own_vault_authority() {
+    let first = Trade::new(100, 50);
+    let second = Trade::new(70, 30);
+    first.init().unwrap();
+    second.init().unwrap();
+    assert_ne!(first.pda.key, second.pda.key);
+
+    second.exchange(70).unwrap();
+
+    assert_eq!(token_balance(&second.bob_x), 70);
+    assert_eq!(token_balance(&second.alice_y), 30);
+    assert_eq!(token_balance(&first.alice_temp_x), 100);
+    assert_eq!(token_owner(&first.alice_temp_x), *first.pda.key);
+    assert!(first.escrow_state().is_initialized);
+}
+
+#[test]
+fn vault_authority_is_derived_from_the_seed_prefix() {
+    let trade = Trade::new(100, 50);
+    let (pda, bump) = Pubkey::find_program_address(
+        &[VAULT_AUTHORITY_SEED, trade.escrow.key.as_ref()],
+        &PROGRAM_ID,
+    );
+    assert_eq!(pda, *trade.pda.key);
+
+    trade.init().unwrap();
+    assert_eq!(token_owner(&trade.alice_temp_x), pda);
+    assert_eq!(trade.escrow_state().bump, bump);
+
+    // the processor signs for the vault with the same seeds
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn exchange_names_a_wrong_initializer_or_token_program() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[4] = wallet(0);
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::NotInitializer.into())
+    );
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[7] = wallet(0);
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::InvalidTokenProgram.into())
+    );
+    accounts[7] = token_program_account(&Pubkey::new_unique());
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::InvalidTokenProgram.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
+
+#[test]
+fn exchange_wants_program_owned_writable_state_and_vault() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    // a copy of the escrow's bytes under another owner says nothing about the trade
+    let mut accounts = trade.exchange_accounts();
+    accounts[6].owner = Box::leak(Box::new(Pubkey::new_unique()));
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    let mut accounts = trade.exchange_accounts();
+    accounts[6].is_writable = false;
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::AccountNotWritable.into())
+    );
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[3].owner = Box::leak(Box::new(PROGRAM_ID));
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    let mut accounts = trade.exchange_accounts();
+    accounts[3].is_writable = false;
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::AccountNotWritable.into())
+    );
+
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    trade.exchange(100).unwrap();
+}
+
+#[test]
+fn exchange_uses_stored_bump_and_rejects_other_pda() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    assert_eq!(
+        trade.escrow_state().bump,
+        vault_authority(&PROGRAM_ID, trade.escrow.key).1
+    );
+
+    let mut accounts = trade.exchange_accounts();
+    // the vault authority of some other escrow
+    accounts[8] = pda_account(&Pubkey::new_unique());
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::InvalidPda.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+
+#[test]
+fn exchange_rejects_escrow_past_its_expiry() {
+    let trade = Trade::new(100, 50);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &expiring_init_escrow_data(50, 100, NOW + 60),
+    )
+    .unwrap();
+    assert_eq!(trade.escrow_state().expiry_unix_timestamp, NOW + 60);
+
+    set_clock(&trade.clock, NOW + 61);
+    assert_eq!(trade.exchange(100), Err(EscrowError::EscrowExpired.into()));
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    // the deadline itself is still in time
+    set_clock(&trade.clock, NOW + 60);
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn exchange_waits_for_escrow_start() {
+    let trade = Trade::new(100, 50);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &scheduled_init_escrow_data(50, 100, NOW + 60),
+    )
+    .unwrap();
+    assert_eq!(trade.escrow_state().start_unix_timestamp, NOW + 60);
+
+    set_clock(&trade.clock, NOW + 59);
+    assert_eq!(trade.exchange(100), Err(EscrowError::EscrowNotStarted.into()));
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    // the start time itself is live
+    set_clock(&trade.clock, NOW + 60);
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn exchange_without_expiry_never_expires_but_needs_the_clock() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    set_clock(&trade.clock, i64::MAX);
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[9] = trade.rent.clone();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(ProgramError::InvalidArgument)
+    );
+
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn exchange_rejects_payment_in_another_mint() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let worthless = mint_account(9);
+    let mut accounts = trade.exchange_accounts();
+    accounts[1] = token_account(worthless.key, trade.bob.key, 50);
+
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::MintMismatch.into())
+    );
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[2] = token_account(worthless.key, trade.bob.key, 0);
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::MintMismatch.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 0);
+}
+
+#[test]
+fn exchange_refuses_a_receive_account_in_the_payment_mint() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[2] = token_account(trade.mint_y.key, trade.bob.key, 0);
+
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::MintMismatch.into())
+    );
+    assert_eq!(token_balance(&accounts[2]), 0);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
+
+#[test]
+fn partial_fills_drain_the_escrow_in_steps() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let accounts = trade.exchange_accounts();
+
+    for fill in [0, 101] {
+        assert_eq!(
+            Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(100, fill)),
+            Err(EscrowError::InvalidFillAmount.into())
+        );
+    }
+
+    // 30 of 100 costs 15 of 50, the rest stays on offer
+    Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(100, 30)).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 30);
+    assert_eq!(token_balance(&trade.alice_y), 15);
+    assert_eq!(token_balance(&trade.alice_temp_x), 70);
+    let escrow = trade.escrow_state();
+    assert_eq!(escrow.offered_amount, 70);
+    assert_eq!(escrow.expected_amount, 35);
+    assert_eq!(escrow.status, EscrowStatus::PartiallyFilled);
+
+    // the taker has to quote the offer as it stands now
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(100, 70)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(70, 70)).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(trade.alice_temp_x.lamports(), 0);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn partial_fills_below_the_minimum_are_refused_unless_they_take_the_rest() {
+    let trade = Trade::new(100, 50);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &min_fill_init_escrow_data(50, 100, 40),
+    )
+    .unwrap();
+    assert_eq!(trade.escrow_state().min_fill_amount, 40);
+    let accounts = trade.exchange_accounts();
+
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(100, 39)),
+        Err(EscrowError::FillTooSmall.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    // 70 of 100 leaves 30, under the minimum but all there is left
+    Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(100, 70)).unwrap();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(30, 29)),
+        Err(EscrowError::FillTooSmall.into())
+    );
+    Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(30, 30)).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn exchange_refuses_a_settled_escrow() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    assert_eq!(trade.escrow_state().status, EscrowStatus::Active);
+
+    for settled in [EscrowStatus::Cancelled, EscrowStatus::Completed] {
+        let escrow = Escrow {
+            status: settled,
+            ..trade.escrow_state()
+        };
+        Escrow::pack(escrow, &mut trade.escrow.data.borrow_mut()).unwrap();
+        assert_eq!(
+            trade.exchange(100),
+            Err(EscrowError::InvalidStateTransition.into())
+        );
+    }
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.bob_x), 0);
+}
+
+#[test]
+fn exchange_skims_the_fee_off_the_payment() {
+    let trade = Trade::new(100, 1_000);
+    let operator = wallet(0);
+    let fee = token_account(trade.mint_y.key, operator.key, 0);
+    let init = |fee_basis_points| {
+        Processor::process(
+            &PROGRAM_ID,
+            &trade.init_accounts(),
+            &init_escrow_with_fee_data(1_000, 100, fee_basis_points, fee.key),
+        )
+    };
+    assert_eq!(init(10_001), Err(EscrowError::InvalidFeeBps.into()));
+    init(30).unwrap();
+    assert_eq!(trade.escrow_state().fee_basis_points, 30);
+
+    let mut accounts = trade.exchange_accounts();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::AccountMismatch.into())
+    );
+
+    accounts[10] = fee.clone();
+    trade.exchange_with(&accounts, 100).unwrap();
+    assert_eq!(token_balance(&fee), 3);
+    assert_eq!(token_balance(&trade.alice_y), 997);
+    assert_eq!(token_balance(&trade.bob_y), 0);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn near_max_amounts_error_instead_of_wrapping() {
+    // the fee is scaled by its basis points before it is divided down
+    let trade = Trade::new(100, u64::MAX);
+    let operator = wallet(0);
+    let fee = token_account(trade.mint_y.key, operator.key, 0);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_with_fee_data(u64::MAX, 100, 30, fee.key),
+    )
+    .unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[10] = fee.clone();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::AmountOverflow.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), u64::MAX);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    // without a fee the same sizes settle exactly, down to the last unit left on offer
+    let trade = Trade::new(u64::MAX, u64::MAX);
+    trade.init().unwrap();
+    let accounts = trade.exchange_accounts();
+    Processor::process(
+        &PROGRAM_ID,
+        &accounts,
+        &partial_exchange_data(u64::MAX, u64::MAX - 1),
+    )
+    .unwrap();
+    assert_eq!(token_balance(&trade.alice_y), u64::MAX - 1);
+    let escrow = trade.escrow_state();
+    assert_eq!(escrow.offered_amount, 1);
+    assert_eq!(escrow.expected_amount, 1);
+}
+
+#[test]
+fn exchange_wants_the_escrows_mints() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    let mut accounts = trade.exchange_accounts();
+    accounts.swap(11, 12);
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::MintMismatch.into())
+    );
+    accounts.swap(11, 12);
+    accounts[12] = mint_account(6);
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::MintMismatch.into())
+    );
+
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn checked_transfer_aborts_on_a_fee_account_in_the_wrong_mint() {
+    let trade = Trade::new(100, 1_000);
+    let operator = wallet(0);
+    // init only stores the fee account's key, so nothing catches the mint until the transfer
+    let fee = token_account(trade.mint_x.key, operator.key, 0);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_with_fee_data(1_000, 100, 30, fee.key),
+    )
+    .unwrap();
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[10] = fee.clone();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(ProgramError::Custom(TokenError::MintMismatch as u32))
+    );
+    assert_eq!(token_balance(&fee), 0);
+    assert_eq!(token_balance(&trade.bob_y), 1_000);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
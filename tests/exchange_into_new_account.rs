@@ -0,0 +1,80 @@
+#![cfg(all(feature = "program", feature = "create-receive-account"))]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{program_error::ProgramError, program_pack::Pack, rent::Rent};
+use spl_token::state::Account as TokenAccount;
+
+use common::{
+    deposit_more_data, exchange_into_new_account_data, fresh_account, system_program,
+    token_account, token_balance, token_owner, Trade, PROGRAM_ID,
+};
+
+#[test]
+fn exchange_creates_and_funds_fresh_receive_account() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let bob_lamports = trade.bob.lamports();
+    let fresh = fresh_account(TokenAccount::LEN, spl_token::id());
+    let mut accounts = trade.exchange_accounts();
+    accounts[2] = fresh.clone();
+    accounts.extend([system_program(), trade.rent.clone()]);
+
+    Processor::process(&PROGRAM_ID, &accounts, &exchange_into_new_account_data(100, None)).unwrap();
+
+    let rent = Rent::default().minimum_balance(TokenAccount::LEN);
+    assert_eq!(token_balance(&fresh), 100);
+    assert_eq!(token_owner(&fresh), *trade.bob.key);
+    assert_eq!(fresh.lamports(), rent);
+    assert_eq!(trade.bob.lamports(), bob_lamports - rent);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn exchange_refuses_an_existing_receive_account() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[2].is_signer = true;
+    accounts.extend([system_program(), trade.rent.clone()]);
+
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &exchange_into_new_account_data(100, None)),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
+
+#[test]
+fn exchange_into_new_account_is_held_to_the_price_the_taker_saw() {
+    let trade = Trade::with_deposit(100, 100, 50);
+    trade.init().unwrap();
+    let fresh = fresh_account(TokenAccount::LEN, spl_token::id());
+    let mut accounts = trade.exchange_accounts();
+    accounts[2] = fresh.clone();
+    accounts.extend([system_program(), trade.rent.clone()]);
+
+    // Alice tops the offer up and asks more for it just ahead of Bob's take
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 20);
+    Processor::process(
+        &PROGRAM_ID,
+        &[
+            trade.alice.clone(),
+            alice_x,
+            trade.alice_temp_x.clone(),
+            trade.escrow.clone(),
+            trade.token_program.clone(),
+            trade.mint_x.clone(),
+        ],
+        &deposit_more_data(20, Some(80)),
+    )
+    .unwrap();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &exchange_into_new_account_data(100, Some(50))),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 120);
+    assert_eq!(token_balance(&trade.bob_y), 50);
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{program_error::ProgramError, program_pack::IsInitialized, pubkey::Pubkey};
+
+use common::{
+    account, disable_cpi, init_escrow_multi_data, pda, token_account, token_balance, token_owner,
+    token_program_account, Trade, PROGRAM_ID,
+};
+
+#[test]
+fn init_escrow_hands_temp_account_to_pda() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(token_owner(&trade.alice_temp_x), pda(trade.escrow.key));
+    let escrow = trade.escrow_state();
+    assert!(escrow.is_initialized());
+    assert_eq!(escrow.initializer_pubkey, *trade.alice.key);
+    assert_eq!(escrow.temp_token_account_pubkey, *trade.alice_temp_x.key);
+    assert_eq!(
+        escrow.initializer_token_to_receive_account_pubkey,
+        *trade.alice_y.key
+    );
+    assert_eq!(escrow.expected_amount, 50);
+    assert_eq!(escrow.expected_mint, *trade.mint_y.key);
+    assert_eq!(escrow.deposited_mint, *trade.mint_x.key);
+}
+
+#[test]
+fn init_escrow_rejects_an_initialized_escrow() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let before = trade.escrow_state();
+
+    // re-initializing would point the live escrow at a vault of Alice's choosing
+    let other_temp = token_account(trade.mint_x.key, trade.alice.key, 100);
+    let mut accounts = trade.init_accounts();
+    accounts[1] = other_temp.clone();
+
+    assert_eq!(
+        trade.init_with(&accounts),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+    assert_eq!(token_owner(&other_temp), *trade.alice.key);
+    assert_eq!(trade.escrow_state(), before);
+}
+
+#[test]
+fn init_escrow_refuses_an_impostor_token_program() {
+    let trade = Trade::new(100, 50);
+    let mut accounts = trade.init_accounts();
+    // executable like the real one, so only its key gives it away
+    accounts[5] = token_program_account(&Pubkey::new_unique());
+
+    assert_eq!(
+        trade.init_with(&accounts),
+        Err(EscrowError::InvalidTokenProgram.into())
+    );
+    assert_eq!(token_owner(&trade.alice_temp_x), *trade.alice.key);
+    assert!(!trade.escrow_state().is_initialized());
+}
+
+#[test]
+fn init_escrow_requires_a_token_account_to_receive_into() {
+    let trade = Trade::new(100, 50);
+    let garbled = account(Pubkey::new_unique(), 0, vec![1; 10], spl_token::id(), false, true);
+    // both are the token program's, but neither could be paid at exchange
+    for receive_account in [trade.mint_y.clone(), garbled] {
+        let mut accounts = trade.init_accounts();
+        accounts[2] = receive_account;
+
+        assert_eq!(
+            trade.init_with(&accounts),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(token_owner(&trade.alice_temp_x), *trade.alice.key);
+        assert!(!trade.escrow_state().is_initialized());
+    }
+}
+
+#[test]
+fn init_escrow_fails_when_authority_is_not_transferred() {
+    let trade = Trade::new(100, 50);
+    disable_cpi();
+
+    assert_eq!(
+        trade.init(),
+        Err(EscrowError::AuthorityTransferFailed.into())
+    );
+    assert!(!trade.escrow_state().is_initialized());
+}
+
+#[test]
+fn init_escrow_reports_unreadable_rent_sysvar() {
+    let trade = Trade::new(100, 50);
+    let mut accounts = trade.init_accounts();
+    accounts[4] = trade.token_program.clone();
+
+    assert_eq!(
+        trade.init_with(&accounts),
+        Err(EscrowError::RentUnavailable.into())
+    );
+}
+
+#[test]
+fn init_escrow_stores_offered_amount_not_balance() {
+    let trade = Trade::with_deposit(120, 100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(trade.escrow_state().offered_amount, 100);
+}
+
+#[test]
+fn init_escrow_rejects_deposit_below_offer() {
+    let trade = Trade::with_deposit(80, 100, 50);
+
+    assert_eq!(trade.init(), Err(EscrowError::InsufficientDeposit.into()));
+    assert_eq!(token_owner(&trade.alice_temp_x), *trade.alice.key);
+}
+
+#[test]
+fn init_escrow_rejects_zero_amounts() {
+    let free = Trade::new(100, 0);
+    assert_eq!(free.init(), Err(EscrowError::InvalidAmount.into()));
+    assert_eq!(token_owner(&free.alice_temp_x), *free.alice.key);
+
+    // an empty temp account covers an offer of nothing, but leaves nothing to take
+    let empty = Trade::with_deposit(0, 0, 50);
+    assert_eq!(empty.init(), Err(EscrowError::InvalidAmount.into()));
+    assert!(!empty.escrow_state().is_initialized());
+}
+
+#[test]
+fn init_escrow_multi_pools_two_sources_for_exchange() {
+    let trade = Trade::with_deposit(40, 100, 50);
+    let first = token_account(trade.mint_x.key, trade.alice.key, 35);
+    let second = token_account(trade.mint_x.key, trade.alice.key, 25);
+    let mut accounts = trade.init_accounts();
+    accounts.extend([trade.mint_y.clone(), first.clone(), second.clone()]);
+
+    // the sources move with transfers checked against the offered mint
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &init_escrow_multi_data(50, 100, 2)),
+        Err(EscrowError::MintMismatch.into())
+    );
+    accounts[7] = trade.mint_x.clone();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &init_escrow_multi_data(50, 110, 2)),
+        Err(EscrowError::SourceTotalMismatch.into())
+    );
+    Processor::process(&PROGRAM_ID, &accounts, &init_escrow_multi_data(50, 100, 2)).unwrap();
+
+    assert_eq!(token_balance(&first), 0);
+    assert_eq!(token_balance(&second), 0);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(trade.escrow_state().offered_amount, 100);
+
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
@@ -0,0 +1,193 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{
+    error::EscrowError,
+    processor::Processor,
+    state::{
+        legacy_vault_authority, vault_authority, Escrow, EscrowStatus, ESCROW_DISCRIMINATOR,
+        ESCROW_VERSION,
+    },
+};
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+use spl_token::state::Account as TokenAccount;
+
+use common::{
+    account, migrate_escrow_data, pda, pda_account, token_balance, token_owner, Trade, PROGRAM_ID,
+};
+
+/// The trade as the original program would have made it: a `V0_LEN` escrow holding only the
+/// keys and the price, and the vault handed to the one PDA every escrow shared
+fn original_escrow(trade: &Trade) -> AccountInfo<'static> {
+    let mut vault = TokenAccount::unpack(&trade.alice_temp_x.data.borrow()).unwrap();
+    vault.owner = legacy_vault_authority(&PROGRAM_ID).0;
+    TokenAccount::pack(vault, &mut trade.alice_temp_x.data.borrow_mut()).unwrap();
+
+    let data = [
+        &[1][..],
+        trade.alice.key.as_ref(),
+        trade.alice_temp_x.key.as_ref(),
+        trade.alice_y.key.as_ref(),
+        &trade.expected.to_le_bytes(),
+    ]
+    .concat();
+    assert_eq!(data.len(), Escrow::V0_LEN);
+    account(
+        Pubkey::new_unique(),
+        Rent::default().minimum_balance(data.len()),
+        data,
+        PROGRAM_ID,
+        false,
+        true,
+    )
+}
+
+fn legacy_pda_account() -> AccountInfo<'static> {
+    account(
+        legacy_vault_authority(&PROGRAM_ID).0,
+        0,
+        vec![],
+        solana_program::system_program::id(),
+        false,
+        false,
+    )
+}
+
+fn new_escrow() -> AccountInfo<'static> {
+    account(
+        Pubkey::new_unique(),
+        Rent::default().minimum_balance(Escrow::LEN),
+        vec![0; Escrow::LEN],
+        PROGRAM_ID,
+        false,
+        true,
+    )
+}
+
+fn migrate_accounts(
+    trade: &Trade,
+    old: &AccountInfo<'static>,
+    new: &AccountInfo<'static>,
+) -> Vec<AccountInfo<'static>> {
+    vec![
+        trade.alice.clone(),
+        old.clone(),
+        new.clone(),
+        trade.alice_temp_x.clone(),
+        trade.token_program.clone(),
+        legacy_pda_account(),
+        trade.rent.clone(),
+        trade.alice_y.clone(),
+    ]
+}
+
+#[test]
+fn migrated_original_escrow_keeps_its_terms_and_can_be_taken() {
+    let trade = Trade::new(100, 50);
+    let old = original_escrow(&trade);
+    let new = new_escrow();
+    assert_eq!(Escrow::unpack(&old.data.borrow()).unwrap().version, 0);
+
+    // nothing but MigrateEscrow reads the original layout
+    let mut accounts = trade.exchange_accounts();
+    accounts[6] = old.clone();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::UnsupportedVersion.into())
+    );
+
+    let alice_lamports = trade.alice.lamports();
+    let old_lamports = old.lamports();
+    Processor::process(
+        &PROGRAM_ID,
+        &migrate_accounts(&trade, &old, &new),
+        &migrate_escrow_data(),
+    )
+    .unwrap();
+
+    let migrated = Escrow::unpack(&new.data.borrow()).unwrap();
+    assert_eq!(migrated.version, ESCROW_VERSION);
+    assert_eq!(migrated.initializer_pubkey, *trade.alice.key);
+    assert_eq!(migrated.temp_token_account_pubkey, *trade.alice_temp_x.key);
+    assert_eq!(
+        migrated.initializer_token_to_receive_account_pubkey,
+        *trade.alice_y.key
+    );
+    assert_eq!(migrated.expected_amount, 50);
+    // the original Exchange traded the whole vault, and the mints come off the accounts
+    assert_eq!(migrated.offered_amount, 100);
+    assert_eq!(migrated.deposited_mint, *trade.mint_x.key);
+    assert_eq!(migrated.expected_mint, *trade.mint_y.key);
+    assert_eq!(migrated.fee_basis_points, 0);
+    assert_eq!(migrated.fee_account, *trade.alice_y.key);
+    assert_eq!(migrated.token_program, spl_token::id());
+    assert_eq!(migrated.status, EscrowStatus::Active);
+    assert_eq!(migrated.bump, vault_authority(&PROGRAM_ID, new.key).1);
+    assert_eq!(token_owner(&trade.alice_temp_x), pda(new.key));
+    assert_eq!(old.lamports(), 0);
+    assert_eq!(old.data_len(), 0);
+    assert_eq!(trade.alice.lamports(), alice_lamports + old_lamports);
+
+    accounts[6] = new.clone();
+    accounts[8] = pda_account(new.key);
+    trade.exchange_with(&accounts, 100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn migrate_only_takes_an_original_escrow_from_its_initializer() {
+    let trade = Trade::new(100, 50);
+    let old = original_escrow(&trade);
+    let new = new_escrow();
+    let migrate = |accounts: &[AccountInfo<'static>]| {
+        Processor::process(&PROGRAM_ID, accounts, &migrate_escrow_data())
+    };
+
+    // already current, nothing to migrate
+    let current = Trade::new(100, 50);
+    current.init().unwrap();
+    assert_eq!(
+        migrate(&migrate_accounts(&current, &current.escrow, &new)),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let mut accounts = migrate_accounts(&trade, &old, &new);
+    accounts[0] = trade.bob.clone();
+    assert_eq!(migrate(&accounts), Err(EscrowError::NotInitializer.into()));
+
+    assert_eq!(
+        migrate(&migrate_accounts(&trade, &old, &old)),
+        Err(EscrowError::DuplicateAccount.into())
+    );
+
+    // the vault only answers to the PDA the original program derived
+    let mut accounts = migrate_accounts(&trade, &old, &new);
+    accounts[5] = pda_account(old.key);
+    assert_eq!(migrate(&accounts), Err(EscrowError::InvalidPda.into()));
+
+    // the expected mint is read off the stored receiving account and no other
+    let mut accounts = migrate_accounts(&trade, &old, &new);
+    accounts[7] = trade.bob_x.clone();
+    assert_eq!(migrate(&accounts), Err(ProgramError::InvalidAccountData));
+
+    assert_eq!(token_owner(&trade.alice_temp_x), legacy_vault_authority(&PROGRAM_ID).0);
+    assert!(Escrow::unpack(&old.data.borrow()).unwrap().is_initialized);
+}
+
+#[test]
+fn unknown_versions_are_refused() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    trade.escrow.data.borrow_mut()[ESCROW_DISCRIMINATOR.len()] = ESCROW_VERSION + 1;
+
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::UnsupportedVersion.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
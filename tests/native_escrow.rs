@@ -0,0 +1,171 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor, state::Escrow};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_pack::Pack, pubkey::Pubkey,
+    rent::Rent,
+};
+
+use common::{
+    account, capped_exchange_data, init_escrow_native_data, partial_exchange_data, token_balance,
+    update_expected_amount_data, Trade, PROGRAM_ID,
+};
+
+const SOL: u64 = 1_000_000_000;
+
+/// An escrow account funded with rent plus `deposit` lamports, as Alice's CreateAccount leaves it
+fn native_escrow(deposit: u64) -> AccountInfo<'static> {
+    account(
+        Pubkey::new_unique(),
+        Rent::default().minimum_balance(Escrow::LEN) + deposit,
+        vec![0; Escrow::LEN],
+        PROGRAM_ID,
+        false,
+        true,
+    )
+}
+
+fn init_native(trade: &Trade, escrow: &AccountInfo<'static>, offered: u64) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &[
+            trade.alice.clone(),
+            trade.alice_y.clone(),
+            escrow.clone(),
+            trade.rent.clone(),
+            trade.config.clone(),
+        ],
+        &init_escrow_native_data(trade.expected, offered),
+    )
+}
+
+/// The Exchange accounts with Bob's wallet taking the lamports, the escrow account
+/// standing in for the vault and the native mint for the offered mint
+fn native_exchange_accounts(
+    trade: &Trade,
+    escrow: &AccountInfo<'static>,
+) -> Vec<AccountInfo<'static>> {
+    let mut accounts = trade.exchange_accounts();
+    accounts[2] = trade.bob.clone();
+    accounts[3] = escrow.clone();
+    accounts[6] = escrow.clone();
+    accounts[12] = account(
+        spl_token::native_mint::id(),
+        0,
+        vec![],
+        spl_token::id(),
+        false,
+        false,
+    );
+    accounts
+}
+
+#[test]
+fn taker_pays_tokens_for_escrowed_sol() {
+    let trade = Trade::new(100, 50);
+    let escrow = native_escrow(2 * SOL);
+    init_native(&trade, &escrow, 2 * SOL).unwrap();
+
+    let state = Escrow::unpack(&escrow.data.borrow()).unwrap();
+    assert!(state.deposited_is_native);
+    assert_eq!(state.temp_token_account_pubkey, *escrow.key);
+    assert_eq!(state.deposited_mint, spl_token::native_mint::id());
+    assert_eq!(state.expected_mint, *trade.mint_y.key);
+
+    let bob_lamports = trade.bob.lamports();
+    let alice_lamports = trade.alice.lamports();
+    let rent = Rent::default().minimum_balance(Escrow::LEN);
+    trade
+        .exchange_with(&native_exchange_accounts(&trade, &escrow), 2 * SOL)
+        .unwrap();
+
+    assert_eq!(trade.bob.lamports(), bob_lamports + 2 * SOL);
+    assert_eq!(token_balance(&trade.bob_y), 0);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(escrow.lamports(), 0);
+    assert_eq!(escrow.data_len(), 0);
+    assert_eq!(trade.alice.lamports(), alice_lamports + rent);
+    // the token side of the fixture never took part
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
+
+#[test]
+fn native_escrow_fills_in_parts() {
+    let trade = Trade::new(100, 50);
+    let escrow = native_escrow(2 * SOL);
+    init_native(&trade, &escrow, 2 * SOL).unwrap();
+    let bob_lamports = trade.bob.lamports();
+
+    Processor::process(
+        &PROGRAM_ID,
+        &native_exchange_accounts(&trade, &escrow),
+        &partial_exchange_data(2 * SOL, SOL),
+    )
+    .unwrap();
+
+    assert_eq!(trade.bob.lamports(), bob_lamports + SOL);
+    assert_eq!(token_balance(&trade.alice_y), 25);
+    let state = Escrow::unpack(&escrow.data.borrow()).unwrap();
+    assert_eq!(state.offered_amount, SOL);
+    assert_eq!(state.expected_amount, 25);
+    assert_eq!(
+        escrow.lamports(),
+        Rent::default().minimum_balance(Escrow::LEN) + SOL
+    );
+
+    trade
+        .exchange_with(&native_exchange_accounts(&trade, &escrow), SOL)
+        .unwrap();
+    assert_eq!(trade.bob.lamports(), bob_lamports + 2 * SOL);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(escrow.data_len(), 0);
+}
+
+#[test]
+fn native_exchange_pays_no_more_than_the_takers_cap() {
+    let trade = Trade::new(100, 50);
+    let escrow = native_escrow(2 * SOL);
+    init_native(&trade, &escrow, 2 * SOL).unwrap();
+    let accounts = native_exchange_accounts(&trade, &escrow);
+    let reprice = |new_amount| {
+        Processor::process(
+            &PROGRAM_ID,
+            &[trade.alice.clone(), escrow.clone()],
+            &update_expected_amount_data(new_amount),
+        )
+    };
+
+    reprice(60).unwrap();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &capped_exchange_data(2 * SOL, 50)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    reprice(50).unwrap();
+    Processor::process(&PROGRAM_ID, &accounts, &capped_exchange_data(2 * SOL, 50)).unwrap();
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn native_escrow_rejects_short_deposits_and_aliased_accounts() {
+    let trade = Trade::new(100, 50);
+    let escrow = native_escrow(SOL);
+    assert_eq!(
+        init_native(&trade, &escrow, 2 * SOL),
+        Err(EscrowError::InsufficientDeposit.into())
+    );
+
+    init_native(&trade, &escrow, SOL).unwrap();
+    // paying the escrow's own lamports back into it would leave the taker with nothing
+    let mut accounts = native_exchange_accounts(&trade, &escrow);
+    accounts[2] = escrow.clone();
+    assert_eq!(
+        trade.exchange_with(&accounts, SOL),
+        Err(EscrowError::DuplicateAccount.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert!(Escrow::unpack(&escrow.data.borrow()).is_ok());
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{open::OpenEscrow, state::{Escrow, RoundingMode, Side}};
+use solana_program::{program_pack::Pack, rent::Rent};
+use spl_token::state::Account as TokenAccount;
+
+use common::{
+    fresh_account, pda, pda_account, process_transaction, system_program, token_balance, token_owner, Trade,
+    NOW, PROGRAM_ID,
+};
+
+#[test]
+fn open_escrow_instructions_run_to_an_exchangeable_escrow() {
+    // Alice's temp account from the fixture stands in for her main X account
+    let trade = Trade::new(100, 50);
+    let temp = fresh_account(TokenAccount::LEN, spl_token::id());
+    let escrow = fresh_account(Escrow::LEN, PROGRAM_ID);
+    let open = OpenEscrow {
+        initializer: *trade.alice.key,
+        initializer_token_account: *trade.alice_temp_x.key,
+        mint: *trade.mint_x.key,
+        decimals: 6,
+        temp_token_account: *temp.key,
+        initializer_token_to_receive_account: *trade.alice_y.key,
+        escrow_account: *escrow.key,
+        amount: 50,
+        offered_amount: 100,
+        side: Side::Buy,
+        expiry_unix_timestamp: NOW + 60,
+        start_unix_timestamp: NOW - 60,
+        min_fill_amount: 10,
+        rounding: RoundingMode::FloorToTaker,
+        token_program: spl_token::id(),
+    };
+    let instructions = open.instructions(&PROGRAM_ID, &Rent::default()).unwrap();
+
+    process_transaction(
+        &instructions,
+        &[
+            trade.alice.clone(),
+            trade.alice_temp_x.clone(),
+            trade.alice_y.clone(),
+            trade.mint_x.clone(),
+            temp.clone(),
+            escrow.clone(),
+            trade.rent.clone(),
+            trade.token_program.clone(),
+            trade.config.clone(),
+            system_program(),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(token_balance(&trade.alice_temp_x), 0);
+    assert_eq!(token_balance(&temp), 100);
+    assert_eq!(token_owner(&temp), pda(escrow.key));
+    let state = Escrow::unpack(&escrow.data.borrow()).unwrap();
+    assert_eq!(state.temp_token_account_pubkey, *temp.key);
+    assert_eq!(state.offered_amount, 100);
+    assert_eq!(state.side, Side::Buy);
+    assert_eq!(state.expiry_unix_timestamp, NOW + 60);
+    assert_eq!(state.start_unix_timestamp, NOW - 60);
+    assert_eq!(state.min_fill_amount, 10);
+    assert_eq!(state.rounding, RoundingMode::FloorToTaker);
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[3] = temp.clone();
+    accounts[6] = escrow.clone();
+    accounts[8] = pda_account(escrow.key);
+    trade.exchange_with(&accounts, 100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::pubkey::Pubkey;
+
+use common::{
+    init_escrow_private_data, token_account, token_balance, wallet, Trade, PROGRAM_ID,
+};
+
+fn init_private(trade: &Trade, allowed_taker: &Pubkey) {
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_private_data(50, 100, allowed_taker),
+    )
+    .unwrap();
+}
+
+#[test]
+fn allowed_taker_takes_a_private_escrow() {
+    let trade = Trade::new(100, 50);
+    init_private(&trade, trade.bob.key);
+    assert_eq!(trade.escrow_state().allowed_taker, *trade.bob.key);
+
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn private_escrow_rejects_any_other_taker() {
+    let trade = Trade::new(100, 50);
+    init_private(&trade, trade.bob.key);
+
+    let carol = wallet(1_000_000_000);
+    let carol_y = token_account(trade.mint_y.key, carol.key, 50);
+    let carol_x = token_account(trade.mint_x.key, carol.key, 0);
+    let mut accounts = trade.exchange_accounts();
+    accounts[0] = carol;
+    accounts[1] = carol_y.clone();
+    accounts[2] = carol_x.clone();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::UnauthorizedTaker.into())
+    );
+    assert_eq!(token_balance(&carol_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    // an escrow made by plain InitEscrow stays open to anyone
+    let open = Trade::new(100, 50);
+    open.init().unwrap();
+    assert_eq!(open.escrow_state().allowed_taker, Pubkey::default());
+    open.exchange(100).unwrap();
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+
+use common::{
+    expiring_init_escrow_data, reclaim_expired_data, set_clock, token_account, token_balance,
+    Trade, NOW, PROGRAM_ID,
+};
+
+/// Alice's escrow offering 100 X for 50 Y, and a token account of hers to reclaim X into
+fn expiring_trade(expiry: i64) -> (Trade, AccountInfo<'static>) {
+    let trade = Trade::new(100, 50);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &expiring_init_escrow_data(50, 100, expiry),
+    )
+    .unwrap();
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 0);
+    (trade, alice_x)
+}
+
+fn reclaim_accounts(
+    trade: &Trade,
+    destination: &AccountInfo<'static>,
+) -> Vec<AccountInfo<'static>> {
+    vec![
+        trade.alice_temp_x.clone(),
+        destination.clone(),
+        trade.alice.clone(),
+        trade.escrow.clone(),
+        trade.token_program.clone(),
+        trade.pda.clone(),
+        trade.clock.clone(),
+        trade.mint_x.clone(),
+    ]
+}
+
+fn reclaim(accounts: &[AccountInfo<'static>]) -> ProgramResult {
+    // nobody signs, as for a crank with no stake in the trade
+    let accounts: Vec<_> = accounts
+        .iter()
+        .cloned()
+        .map(|mut info| {
+            info.is_signer = false;
+            info
+        })
+        .collect();
+    Processor::process(&PROGRAM_ID, &accounts, &reclaim_expired_data())
+}
+
+#[test]
+fn anyone_reclaims_an_expired_escrow_for_the_initializer() {
+    let (trade, alice_x) = expiring_trade(NOW + 60);
+    let accounts = reclaim_accounts(&trade, &alice_x);
+
+    set_clock(&trade.clock, NOW + 60);
+    assert_eq!(reclaim(&accounts), Err(EscrowError::EscrowNotExpired.into()));
+
+    set_clock(&trade.clock, NOW + 61);
+    let alice_lamports = trade.alice.lamports();
+    let reclaimed = trade.alice_temp_x.lamports() + trade.escrow.lamports();
+    reclaim(&accounts).unwrap();
+
+    assert_eq!(token_balance(&alice_x), 100);
+    assert_eq!(trade.alice_temp_x.lamports(), 0);
+    assert_eq!(trade.escrow.data_len(), 0);
+    assert_eq!(trade.alice.lamports(), alice_lamports + reclaimed);
+    assert_eq!(trade.exchange(100), Err(EscrowError::EscrowNotInitialized.into()));
+}
+
+#[test]
+fn reclaim_wants_an_expired_escrow_and_the_initializers_account() {
+    // no deadline, no crank
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 0);
+    set_clock(&trade.clock, i64::MAX);
+    assert_eq!(
+        reclaim(&reclaim_accounts(&trade, &alice_x)),
+        Err(EscrowError::EscrowNotExpired.into())
+    );
+
+    let (trade, _) = expiring_trade(NOW + 60);
+    set_clock(&trade.clock, NOW + 61);
+    assert_eq!(
+        reclaim(&reclaim_accounts(&trade, &trade.bob_x)),
+        Err(EscrowError::AccountMismatch.into())
+    );
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(token_balance(&trade.bob_x), 0);
+}
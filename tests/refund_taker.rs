@@ -0,0 +1,156 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{
+    error::EscrowError,
+    processor::Processor,
+    state::{config_address, Config, DEFAULT_MAX_EXPIRY_HORIZON_SECS},
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    program_pack::Pack,
+};
+
+use common::{account, refund_taker_data, token_account, token_balance, wallet, Trade, PROGRAM_ID};
+
+/// Bob's `held` Y, sent by mistake to an account the escrow's PDA owns
+fn holding_account(trade: &Trade, held: u64) -> AccountInfo<'static> {
+    token_account(trade.mint_y.key, trade.pda.key, held)
+}
+
+/// The refund of `holding` to Bob's Y account, Bob co-signing
+fn refund_accounts(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    holding: &AccountInfo<'static>,
+) -> Vec<AccountInfo<'static>> {
+    vec![
+        signer.clone(),
+        holding.clone(),
+        trade.bob_y.clone(),
+        trade.escrow.clone(),
+        trade.token_program.clone(),
+        trade.pda.clone(),
+        trade.mint_y.clone(),
+        trade.config.clone(),
+        trade.bob.clone(),
+    ]
+}
+
+fn refund(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    holding: &AccountInfo<'static>,
+    amount: u64,
+) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &refund_accounts(trade, signer, holding),
+        &refund_taker_data(amount),
+    )
+}
+
+#[test]
+fn initializer_refunds_what_the_taker_left_with_the_pda() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let holding = holding_account(&trade, 30);
+
+    refund(&trade, &trade.alice, &holding, 20).unwrap();
+    assert_eq!(token_balance(&holding), 10);
+    assert_eq!(token_balance(&trade.bob_y), 70);
+
+    assert_eq!(
+        refund(&trade, &trade.alice, &holding, 11),
+        Err(EscrowError::NothingToRefund.into())
+    );
+    refund(&trade, &trade.alice, &holding, 10).unwrap();
+    assert_eq!(
+        refund(&trade, &trade.alice, &holding, 1),
+        Err(EscrowError::NothingToRefund.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 80);
+    // the escrow itself is untouched
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert!(trade.escrow_state().status.is_open());
+}
+
+#[test]
+fn only_the_initializer_or_admin_refunds_and_never_the_offer() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let holding = holding_account(&trade, 30);
+
+    assert_eq!(
+        refund(&trade, &trade.bob, &holding, 30),
+        Err(EscrowError::NotInitializer.into())
+    );
+    let mut unsigned = trade.alice.clone();
+    unsigned.is_signer = false;
+    assert_eq!(
+        refund(&trade, &unsigned, &holding, 30),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        refund(&trade, &trade.alice, &trade.alice_temp_x, 100),
+        Err(ProgramError::InvalidAccountData)
+    );
+    let alices_own = token_account(trade.mint_y.key, trade.alice.key, 30);
+    assert_eq!(
+        refund(&trade, &trade.alice, &alices_own, 30),
+        Err(EscrowError::InvalidAccountOwner.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+
+    // once a config names an admin, they may refund too
+    let admin = wallet(0);
+    let mut config = vec![0; Config::LEN];
+    Config::pack(
+        Config {
+            is_initialized: true,
+            admin: *admin.key,
+            paused: false,
+            unpause_delay: 0,
+            pause_until: 0,
+            max_expiry_horizon_secs: DEFAULT_MAX_EXPIRY_HORIZON_SECS,
+        },
+        &mut config,
+    )
+    .unwrap();
+    let mut trade = trade;
+    trade.config = account(config_address(&PROGRAM_ID).0, 0, config, PROGRAM_ID, false, true);
+    refund(&trade, &admin, &holding, 30).unwrap();
+    assert_eq!(token_balance(&trade.bob_y), 80);
+}
+
+#[test]
+fn refund_only_goes_to_the_taker_who_cosigns() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let holding = holding_account(&trade, 30);
+    let process = |accounts: &[AccountInfo<'static>]| {
+        Processor::process(&PROGRAM_ID, accounts, &refund_taker_data(30))
+    };
+
+    // the initializer can't send Bob's tokens to herself
+    let mut accounts = refund_accounts(&trade, &trade.alice, &holding);
+    accounts[2] = trade.alice_y.clone();
+    assert_eq!(
+        process(&accounts),
+        Err(EscrowError::InvalidAccountOwner.into())
+    );
+    // nor name Bob without him signing
+    let mut accounts = refund_accounts(&trade, &trade.alice, &holding);
+    accounts[8].is_signer = false;
+    assert_eq!(
+        process(&accounts),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        process(&accounts[..8]),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    assert_eq!(token_balance(&holding), 30);
+    assert_eq!(token_balance(&trade.alice_y), 0);
+}
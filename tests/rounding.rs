@@ -0,0 +1,54 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{processor::Processor, state::RoundingMode};
+
+use common::{
+    init_escrow_with_fee_data, partial_exchange_data, rounding_init_escrow_data, token_account,
+    token_balance, wallet, Trade, PROGRAM_ID,
+};
+
+#[test]
+fn partial_fill_remainder_lands_with_the_party_the_mode_names() {
+    // 1 of 3 is worth 3.33 of 10
+    for (rounding, payment) in [(RoundingMode::FloorToMaker, 4), (RoundingMode::FloorToTaker, 3)] {
+        let trade = Trade::new(3, 10);
+        Processor::process(
+            &PROGRAM_ID,
+            &trade.init_accounts(),
+            &rounding_init_escrow_data(10, 3, rounding),
+        )
+        .unwrap();
+        assert_eq!(trade.escrow_state().rounding, rounding);
+
+        let accounts = trade.exchange_accounts();
+        Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(3, 1)).unwrap();
+        assert_eq!(token_balance(&trade.bob_x), 1);
+        assert_eq!(token_balance(&trade.alice_y), payment);
+        assert_eq!(trade.escrow_state().expected_amount, 10 - payment);
+
+        // the last fill pays whatever is left, so the whole offer still costs the whole price
+        Processor::process(&PROGRAM_ID, &accounts, &partial_exchange_data(2, 2)).unwrap();
+        assert_eq!(token_balance(&trade.alice_y), 10);
+    }
+}
+
+#[test]
+fn fee_remainder_lands_with_the_party_the_mode_names() {
+    // 30 basis points of 333 is 0.999
+    for (rounding, fee_amount) in [(RoundingMode::FloorToMaker, 0), (RoundingMode::FloorToTaker, 1)]
+    {
+        let trade = Trade::new(100, 333);
+        let fee = token_account(trade.mint_y.key, wallet(0).key, 0);
+        let mut data = init_escrow_with_fee_data(333, 100, 30, fee.key);
+        data.push(rounding as u8);
+        Processor::process(&PROGRAM_ID, &trade.init_accounts(), &data).unwrap();
+
+        let mut accounts = trade.exchange_accounts();
+        accounts[10] = fee.clone();
+        trade.exchange_with(&accounts, 100).unwrap();
+        assert_eq!(token_balance(&fee), fee_amount);
+        assert_eq!(token_balance(&trade.alice_y), 333 - fee_amount);
+    }
+}
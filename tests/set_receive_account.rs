@@ -0,0 +1,89 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor, token::token_2022};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+};
+
+use common::{
+    set_receive_account_data, token_account, token_account_in, token_balance, Trade, PROGRAM_ID,
+};
+
+fn redirect(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    new_token_to_receive_account: &AccountInfo<'static>,
+) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &[
+            signer.clone(),
+            trade.escrow.clone(),
+            new_token_to_receive_account.clone(),
+        ],
+        &set_receive_account_data(),
+    )
+}
+
+#[test]
+fn exchange_pays_the_redirected_account() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_other_y = token_account(trade.mint_y.key, trade.alice.key, 0);
+
+    redirect(&trade, &trade.alice, &alice_other_y).unwrap();
+    let state = trade.escrow_state();
+    assert_eq!(state.initializer_token_to_receive_account_pubkey, *alice_other_y.key);
+    assert_eq!(state.fee_account, *alice_other_y.key);
+
+    // the old account no longer matches the state
+    assert_eq!(
+        trade.exchange(100),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[5] = alice_other_y.clone();
+    accounts[10] = alice_other_y.clone();
+    trade.exchange_with(&accounts, 100).unwrap();
+
+    assert_eq!(token_balance(&alice_other_y), 50);
+    assert_eq!(token_balance(&trade.alice_y), 0);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn only_the_initializer_can_redirect_to_an_account_of_the_expected_mint() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let bob_other_y = token_account(trade.mint_y.key, trade.bob.key, 0);
+
+    assert_eq!(
+        redirect(&trade, &trade.bob, &bob_other_y),
+        Err(EscrowError::NotInitializer.into())
+    );
+    let mut unsigned = trade.alice.clone();
+    unsigned.is_signer = false;
+    assert_eq!(
+        redirect(&trade, &unsigned, &bob_other_y),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 0);
+    assert_eq!(
+        redirect(&trade, &trade.alice, &alice_x),
+        Err(EscrowError::MintMismatch.into())
+    );
+    // the right mint under a token program the escrow wasn't made with
+    let alice_2022_y = token_account_in(&token_2022::id(), trade.mint_y.key, trade.alice.key, 0);
+    assert_eq!(
+        redirect(&trade, &trade.alice, &alice_2022_y),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    assert_eq!(
+        trade.escrow_state().initializer_token_to_receive_account_pubkey,
+        *trade.alice_y.key
+    );
+}
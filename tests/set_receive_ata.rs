@@ -0,0 +1,96 @@
+#![cfg(all(feature = "program", feature = "create-receive-account"))]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    program_pack::Pack, rent::Rent,
+};
+use spl_token::state::Account as TokenAccount;
+
+use common::{
+    associated_token_program, missing_associated_account, set_receive_ata_data, system_program,
+    token_account, token_balance, token_owner, Trade, PROGRAM_ID,
+};
+
+fn set_receive_ata(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    associated_account: &AccountInfo<'static>,
+    mint: &AccountInfo<'static>,
+) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &[
+            signer.clone(),
+            trade.escrow.clone(),
+            associated_account.clone(),
+            mint.clone(),
+            trade.token_program.clone(),
+            associated_token_program(),
+            system_program(),
+        ],
+        &set_receive_ata_data(),
+    )
+}
+
+#[test]
+fn exchange_pays_the_associated_account_made_for_it() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_ata = missing_associated_account(trade.alice.key, trade.mint_y.key);
+
+    let alice_lamports = trade.alice.lamports();
+    set_receive_ata(&trade, &trade.alice, &alice_ata, &trade.mint_y).unwrap();
+    let rent = Rent::default().minimum_balance(TokenAccount::LEN);
+    assert_eq!(trade.alice.lamports(), alice_lamports - rent);
+    assert_eq!(token_owner(&alice_ata), *trade.alice.key);
+    let state = trade.escrow_state();
+    assert_eq!(state.initializer_token_to_receive_account_pubkey, *alice_ata.key);
+    assert_eq!(state.fee_account, *alice_ata.key);
+
+    // already there the second time, so it is only pointed at again
+    set_receive_ata(&trade, &trade.alice, &alice_ata, &trade.mint_y).unwrap();
+    assert_eq!(trade.alice.lamports(), alice_lamports - rent);
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[5] = alice_ata.clone();
+    accounts[10] = alice_ata.clone();
+    trade.exchange_with(&accounts, 100).unwrap();
+
+    assert_eq!(token_balance(&alice_ata), 50);
+    assert_eq!(token_balance(&trade.alice_y), 0);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn only_the_initializers_associated_account_for_the_expected_mint_will_do() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_ata = missing_associated_account(trade.alice.key, trade.mint_y.key);
+
+    assert_eq!(
+        set_receive_ata(&trade, &trade.bob, &alice_ata, &trade.mint_y),
+        Err(EscrowError::NotInitializer.into())
+    );
+    let alice_x_ata = missing_associated_account(trade.alice.key, trade.mint_x.key);
+    assert_eq!(
+        set_receive_ata(&trade, &trade.alice, &alice_x_ata, &trade.mint_x),
+        Err(EscrowError::MintMismatch.into())
+    );
+    let bob_ata = missing_associated_account(trade.bob.key, trade.mint_y.key);
+    assert_eq!(
+        set_receive_ata(&trade, &trade.alice, &bob_ata, &trade.mint_y),
+        Err(ProgramError::InvalidSeeds)
+    );
+    let alice_other_y = token_account(trade.mint_y.key, trade.alice.key, 0);
+    assert_eq!(
+        set_receive_ata(&trade, &trade.alice, &alice_other_y, &trade.mint_y),
+        Err(ProgramError::InvalidSeeds)
+    );
+    assert_eq!(
+        trade.escrow_state().initializer_token_to_receive_account_pubkey,
+        *trade.alice_y.key
+    );
+}
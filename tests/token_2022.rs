@@ -0,0 +1,81 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor, token::token_2022};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use spl_token::state::Account as TokenAccount;
+
+use common::{
+    account, init_escrow_data, token_balance, token_owner, token_program, token_program_account,
+    Trade, PROGRAM_ID,
+};
+
+#[test]
+fn token_2022_escrow_trades_through_token_2022() {
+    let trade = Trade::token_2022(100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(trade.escrow_state().token_program, token_2022::id());
+    assert_eq!(token_owner(&trade.alice_temp_x), *trade.pda.key);
+
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+    assert_eq!(trade.escrow.data_len(), 0);
+}
+
+#[test]
+fn escrow_sticks_to_its_token_program() {
+    let trade = Trade::token_2022(100, 50);
+    let mut accounts = trade.init_accounts();
+    accounts[5] = token_program_account(&Pubkey::new_unique());
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &init_escrow_data(50, 100)),
+        Err(EscrowError::InvalidTokenProgram.into())
+    );
+    // spl-token can't move Token-2022 accounts, so it can't be named for them
+    accounts[5] = token_program();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &init_escrow_data(50, 100)),
+        Err(ProgramError::IncorrectProgramId)
+    );
+
+    trade.init().unwrap();
+    let mut accounts = trade.exchange_accounts();
+    accounts[7] = token_program();
+    assert_eq!(
+        trade.exchange_with(&accounts, 100),
+        Err(EscrowError::InvalidTokenProgram.into())
+    );
+    assert_eq!(token_balance(&trade.bob_y), 50);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+}
+
+#[test]
+fn transfer_fee_accounts_are_refused() {
+    let trade = Trade::token_2022(100, 50);
+    // a vault of a fee-bearing mint: past the spl-token length come the account type byte
+    // and the TransferFeeAmount extension Token-2022 puts on every account of such a mint
+    let mut data = trade.alice_temp_x.data.borrow().to_vec();
+    data.push(2);
+    data.extend_from_slice(&[2, 0, 8, 0]);
+    data.extend_from_slice(&[0; 8]);
+    let fee_bearing = account(
+        *trade.alice_temp_x.key,
+        trade.alice_temp_x.lamports(),
+        data,
+        token_2022::id(),
+        false,
+        true,
+    );
+
+    let mut accounts = trade.init_accounts();
+    accounts[1] = fee_bearing.clone();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &init_escrow_data(50, 100)),
+        Err(EscrowError::UnsupportedTokenExtension.into())
+    );
+    let vault = TokenAccount::unpack(&fee_bearing.data.borrow()[..TokenAccount::LEN]).unwrap();
+    assert_eq!(vault.owner, *trade.alice.key);
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use common::{
+    token_account, token_balance, transfer_initializer_data, update_expected_amount_data,
+    wallet, Trade, PROGRAM_ID,
+};
+
+fn transfer(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    new_initializer: &Pubkey,
+    new_token_to_receive_account: Option<&AccountInfo<'static>>,
+) -> ProgramResult {
+    let mut accounts = vec![signer.clone(), trade.escrow.clone()];
+    accounts.extend(new_token_to_receive_account.cloned());
+    Processor::process(
+        &PROGRAM_ID,
+        &accounts,
+        &transfer_initializer_data(new_initializer, new_token_to_receive_account.map(|a| a.key)),
+    )
+}
+
+#[test]
+fn exchange_pays_the_new_initializer() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let carol = wallet(0);
+    let carol_y = token_account(trade.mint_y.key, carol.key, 0);
+
+    transfer(&trade, &trade.alice, carol.key, Some(&carol_y)).unwrap();
+    let state = trade.escrow_state();
+    assert_eq!(state.initializer_pubkey, *carol.key);
+    assert_eq!(state.initializer_token_to_receive_account_pubkey, *carol_y.key);
+    assert_eq!(state.fee_account, *carol_y.key);
+
+    // Alice's accounts no longer match the state
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::NotInitializer.into())
+    );
+
+    let mut accounts = trade.exchange_accounts();
+    accounts[4] = carol.clone();
+    accounts[5] = carol_y.clone();
+    accounts[10] = carol_y.clone();
+    let reclaimed = trade.alice_temp_x.lamports() + trade.escrow.lamports();
+    trade.exchange_with(&accounts, 100).unwrap();
+
+    assert_eq!(token_balance(&carol_y), 50);
+    assert_eq!(token_balance(&trade.alice_y), 0);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+    assert_eq!(carol.lamports(), reclaimed);
+}
+
+#[test]
+fn only_the_initializer_can_hand_over() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(
+        transfer(&trade, &trade.bob, trade.bob.key, None),
+        Err(EscrowError::NotInitializer.into())
+    );
+    let mut unsigned = trade.alice.clone();
+    unsigned.is_signer = false;
+    assert_eq!(
+        transfer(&trade, &unsigned, trade.bob.key, None),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(trade.escrow_state().initializer_pubkey, *trade.alice.key);
+
+    // keeping the receiving account, Alice is still paid but can no longer reprice
+    let carol = wallet(0);
+    transfer(&trade, &trade.alice, carol.key, None).unwrap();
+    assert_eq!(
+        trade.escrow_state().initializer_token_to_receive_account_pubkey,
+        *trade.alice_y.key
+    );
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &[trade.alice.clone(), trade.escrow.clone()],
+            &update_expected_amount_data(10),
+        ),
+        Err(EscrowError::NotInitializer.into())
+    );
+}
+
+#[test]
+fn new_receiving_account_must_hold_the_expected_mint() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let carol = wallet(0);
+
+    let carol_x = token_account(trade.mint_x.key, carol.key, 0);
+    assert_eq!(
+        transfer(&trade, &trade.alice, carol.key, Some(&carol_x)),
+        Err(EscrowError::MintMismatch.into())
+    );
+    assert_eq!(trade.escrow_state().initializer_pubkey, *trade.alice.key);
+}
+
+#[test]
+fn new_receiving_account_must_be_under_the_escrows_token_program() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let carol = wallet(0);
+
+    let mut carol_y = token_account(trade.mint_y.key, carol.key, 0);
+    carol_y.owner = Box::leak(Box::new(Pubkey::new_unique()));
+    assert_eq!(
+        transfer(&trade, &trade.alice, carol.key, Some(&carol_y)),
+        Err(ProgramError::IncorrectProgramId)
+    );
+
+    // the account passed has to be the one named in the data
+    let carol_y = token_account(trade.mint_y.key, carol.key, 0);
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &[trade.alice.clone(), trade.escrow.clone(), carol_y],
+            &transfer_initializer_data(carol.key, Some(trade.alice_y.key)),
+        ),
+        Err(EscrowError::AccountMismatch.into())
+    );
+    assert_eq!(
+        trade.escrow_state().initializer_token_to_receive_account_pubkey,
+        *trade.alice_y.key
+    );
+}
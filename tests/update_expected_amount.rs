@@ -0,0 +1,109 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+};
+
+use common::{
+    capped_exchange_data, capped_partial_exchange_data, init_escrow_at_price_data, token_account,
+    token_balance, update_expected_amount_data, Trade, PROGRAM_ID,
+};
+
+fn update(trade: &Trade, signer: &AccountInfo<'static>, new_amount: u64) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &[signer.clone(), trade.escrow.clone()],
+        &update_expected_amount_data(new_amount),
+    )
+}
+
+#[test]
+fn exchange_pays_the_updated_amount() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    // Bob only holds the old price, so a raise is checked against his balance at once
+    update(&trade, &trade.alice, 60).unwrap();
+    assert_eq!(trade.escrow_state().expected_amount, 60);
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::InsufficientTakerFunds.into())
+    );
+
+    update(&trade, &trade.alice, 40).unwrap();
+    trade.exchange(100).unwrap();
+    assert_eq!(token_balance(&trade.alice_y), 40);
+    assert_eq!(token_balance(&trade.bob_y), 10);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn reprice_between_quote_and_exchange_stops_at_the_takers_cap() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    // Bob can afford more than he was quoted, so only his cap stands in the way
+    let mut accounts = trade.exchange_accounts();
+    accounts[1] = token_account(trade.mint_y.key, trade.bob.key, 100);
+
+    // he signs for the 50 he saw, Alice's raise lands first
+    update(&trade, &trade.alice, 60).unwrap();
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &capped_exchange_data(100, 50)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(
+        Processor::process(&PROGRAM_ID, &accounts, &capped_partial_exchange_data(100, 50, 25)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    assert_eq!(token_balance(&accounts[1]), 100);
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+
+    // a cap at the new price or above it takes the trade
+    Processor::process(&PROGRAM_ID, &accounts, &capped_partial_exchange_data(100, 50, 30))
+        .unwrap();
+    Processor::process(&PROGRAM_ID, &accounts, &capped_exchange_data(50, 30)).unwrap();
+    assert_eq!(token_balance(&accounts[1]), 40);
+    assert_eq!(token_balance(&trade.alice_y), 60);
+    assert_eq!(token_balance(&trade.bob_x), 100);
+}
+
+#[test]
+fn only_the_initializer_can_reprice() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+
+    assert_eq!(
+        update(&trade, &trade.bob, 10),
+        Err(EscrowError::NotInitializer.into())
+    );
+    assert_eq!(
+        update(&trade, &trade.alice, 0),
+        Err(EscrowError::InvalidAmount.into())
+    );
+    let mut unsigned = trade.alice.clone();
+    unsigned.is_signer = false;
+    assert_eq!(
+        update(&trade, &unsigned, 10),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(trade.escrow_state().expected_amount, 50);
+}
+
+#[test]
+fn priced_escrow_cannot_be_given_a_fixed_amount() {
+    let trade = Trade::new(100, 50);
+    Processor::process(
+        &PROGRAM_ID,
+        &trade.init_accounts(),
+        &init_escrow_at_price_data(100, 1, 2),
+    )
+    .unwrap();
+
+    assert_eq!(
+        update(&trade, &trade.alice, 10),
+        Err(EscrowError::InvalidPrice.into())
+    );
+}
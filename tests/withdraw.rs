@@ -0,0 +1,92 @@
+#![cfg(feature = "program")]
+
+mod common;
+
+use solana_escrow::{error::EscrowError, processor::Processor};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+
+use common::{token_account, token_balance, withdraw_data, Trade, PROGRAM_ID};
+
+fn withdraw_accounts(
+    trade: &Trade,
+    signer: &AccountInfo<'static>,
+    destination: &AccountInfo<'static>,
+) -> Vec<AccountInfo<'static>> {
+    vec![
+        signer.clone(),
+        destination.clone(),
+        trade.alice_temp_x.clone(),
+        trade.escrow.clone(),
+        trade.token_program.clone(),
+        trade.pda.clone(),
+        trade.mint_x.clone(),
+    ]
+}
+
+fn withdraw(trade: &Trade, destination: &AccountInfo<'static>, amount: u64) -> ProgramResult {
+    Processor::process(
+        &PROGRAM_ID,
+        &withdraw_accounts(trade, &trade.alice, destination),
+        &withdraw_data(amount),
+    )
+}
+
+#[test]
+fn withdrawn_escrow_trades_the_rest_of_the_offer() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 0);
+
+    withdraw(&trade, &alice_x, 40).unwrap();
+    assert_eq!(token_balance(&alice_x), 40);
+    assert_eq!(token_balance(&trade.alice_temp_x), 60);
+    let escrow = trade.escrow_state();
+    assert_eq!(escrow.offered_amount, 60);
+    assert_eq!(escrow.expected_amount, 50);
+
+    // a taker still counting on the whole offer is turned away
+    assert_eq!(
+        trade.exchange(100),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    trade.exchange(60).unwrap();
+    assert_eq!(token_balance(&trade.bob_x), 60);
+    assert_eq!(token_balance(&trade.alice_y), 50);
+}
+
+#[test]
+fn only_the_initializer_withdraws_less_than_the_offer() {
+    let trade = Trade::new(100, 50);
+    trade.init().unwrap();
+    let alice_x = token_account(trade.mint_x.key, trade.alice.key, 0);
+
+    assert_eq!(
+        withdraw(&trade, &alice_x, 0),
+        Err(EscrowError::InvalidAmount.into())
+    );
+    // the whole offer only comes back by reclaiming
+    for amount in [100, 101] {
+        assert_eq!(
+            withdraw(&trade, &alice_x, amount),
+            Err(EscrowError::InsufficientDeposit.into())
+        );
+    }
+    assert_eq!(
+        withdraw(&trade, &trade.alice_y, 10),
+        Err(EscrowError::MintMismatch.into())
+    );
+    let bob_x = token_account(trade.mint_x.key, trade.bob.key, 0);
+    assert_eq!(
+        Processor::process(
+            &PROGRAM_ID,
+            &withdraw_accounts(&trade, &trade.bob, &bob_x),
+            &withdraw_data(10),
+        ),
+        Err(EscrowError::NotInitializer.into())
+    );
+
+    assert_eq!(token_balance(&trade.alice_temp_x), 100);
+    assert_eq!(trade.escrow_state().offered_amount, 100);
+    withdraw(&trade, &alice_x, 99).unwrap();
+    assert_eq!(trade.escrow_state().offered_amount, 1);
+}